@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use serde::Deserialize;
+use untanglers_core::config::OptimizeConfig;
+use untanglers_core::hierarchy_optimizer::{Hierarchy, HierarchyOptimizer};
+use untanglers_core::layout_optimizer::LayoutOptimizer;
+use untanglers_core::optimizer_ops::OptimizerOps;
+use untanglers_core::strategy::{strategy_from_name, LayerStrategy};
+use untanglers_core::svg::SvgOptions;
+
+/// Minimise edge crossings in a layered graph and render the result as SVG
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+  /// JSON file with `nodes`, `edges` and optionally `intra_edges` and `hierarchy`
+  input: PathBuf,
+
+  /// Where to write the SVG, defaults to stdout
+  #[arg(short, long)]
+  output: Option<PathBuf>,
+
+  #[arg(long, default_value_t = 1.)]
+  start_temp: f64,
+
+  #[arg(long, default_value_t = 0.1)]
+  end_temp: f64,
+
+  #[arg(long, default_value_t = 5)]
+  steps: usize,
+
+  #[arg(long, default_value_t = 200)]
+  max_iterations: usize,
+
+  /// Number of optimisation passes, 0 renders the input order as is
+  #[arg(long, default_value_t = 10)]
+  passes: usize,
+
+  /// Strategies applied to every layer in each pass, in order. Defaults to annealing with the settings above
+  #[arg(long = "strategy", value_parser = ["annealing", "barycenter", "greedy", "tabu"])]
+  strategies: Vec<String>,
+
+  /// Finish with a greedy polish including 2-opt and block reversals
+  #[arg(long)]
+  polish: bool,
+
+  /// Colour edges that are involved in a crossing
+  #[arg(long)]
+  highlight_crossings: bool,
+
+  #[arg(long)]
+  no_labels: bool,
+}
+
+/// Node ids can be given as either strings or integers
+#[derive(Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(untagged)]
+enum Node {
+  Int(i64),
+  Str(String),
+}
+
+impl Display for Node {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Node::Int(i) => write!(f, "{i}"),
+      Node::Str(s) => write!(f, "{s}"),
+    }
+  }
+}
+
+#[derive(Deserialize)]
+struct Graph {
+  nodes: Vec<Vec<Node>>,
+  edges: Vec<Vec<(Node, Node, usize)>>,
+  intra_edges: Option<Vec<Vec<(Node, Node, usize)>>>,
+  hierarchy: Option<Hierarchy>,
+}
+
+fn strategies(args: &Args) -> Result<Vec<Box<dyn LayerStrategy>>, Box<dyn std::error::Error>> {
+  let config = OptimizeConfig::builder()
+    .start_temp(args.start_temp)
+    .end_temp(args.end_temp)
+    .steps(args.steps)
+    .max_iterations(args.max_iterations)
+    .passes(args.passes)
+    .build()?;
+  let annealing = || -> Box<dyn LayerStrategy> { Box::new(config.annealing()) };
+
+  if args.strategies.is_empty() {
+    return Ok(vec![annealing()]);
+  }
+
+  args
+    .strategies
+    .iter()
+    .map(|name| match name.as_str() {
+      "annealing" => Ok(annealing()),
+      _ => Ok(strategy_from_name(name, &HashMap::new())?),
+    })
+    .collect()
+}
+
+fn run(args: &Args) -> Result<String, Box<dyn std::error::Error>> {
+  let graph: Graph = serde_json::from_str(&fs::read_to_string(&args.input)?)?;
+  let options = SvgOptions {
+    highlight_crossings: args.highlight_crossings,
+    show_labels: !args.no_labels,
+    ..Default::default()
+  };
+
+  let strategies = strategies(args)?;
+  let intra_edges = graph.intra_edges.unwrap_or_else(|| vec![vec![]; graph.nodes.len()]);
+
+  let svg = match graph.hierarchy {
+    None => {
+      let mut optimizer = LayoutOptimizer::with_intra_edges(graph.nodes, graph.edges, intra_edges)?;
+      let before = optimizer.count_crossings();
+      let mut after = optimizer.optimize_with(&strategies, args.passes)?;
+      if args.polish {
+        after = optimizer.polish(true, true)?;
+      }
+      eprintln!("Edge crossings: {before} -> {after}");
+      optimizer.to_svg(&options)
+    }
+    Some(hierarchy) => {
+      let mut optimizer = HierarchyOptimizer::with_intra_edges(graph.nodes, graph.edges, hierarchy, intra_edges)?;
+      let before = optimizer.count_crossings();
+      let mut after = optimizer.optimize_with(&strategies, args.passes)?;
+      if args.polish {
+        after = optimizer.polish(true, true)?;
+      }
+      eprintln!("Edge crossings: {before} -> {after}");
+      optimizer.to_svg(&options)
+    }
+  };
+
+  Ok(svg)
+}
+
+fn main() -> ExitCode {
+  let args = Args::parse();
+
+  let result = run(&args).and_then(|svg| match &args.output {
+    Some(path) => Ok(fs::write(path, svg)?),
+    None => {
+      print!("{svg}");
+      Ok(())
+    }
+  });
+
+  match result {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(err) => {
+      eprintln!("error: {err}");
+      ExitCode::FAILURE
+    }
+  }
+}
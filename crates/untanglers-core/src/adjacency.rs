@@ -0,0 +1,169 @@
+use crate::strategy::MappedEdges;
+
+/**
+ * Weighted edges between the node ids of two layers in compressed sparse row form, see `NodeIndex`. The neighbours of
+ * source id `a` are `targets[offsets[a]..offsets[a + 1]]` as (target id, weight), in the order the edges were given.
+ * Sources without a row, e.g. ids interned after the adjacency was built, have no neighbours.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Adjacency {
+  offsets: Vec<usize>,
+  targets: Vec<(usize, usize)>,
+}
+
+impl Adjacency {
+  /// * `source_count` Number of ids in the source layer, every edge has to start below it
+  /// * `edges` As (source id, target id, weight)
+  pub fn new(source_count: usize, edges: &[(usize, usize, usize)]) -> Self {
+    let mut offsets = vec![0; source_count + 1];
+    for (a, _, _) in edges {
+      offsets[a + 1] += 1;
+    }
+    for i in 0..source_count {
+      offsets[i + 1] += offsets[i];
+    }
+
+    let mut next = offsets.clone();
+    let mut targets = vec![(0, 0); edges.len()];
+    for (a, b, weight) in edges {
+      targets[next[*a]] = (*b, *weight);
+      next[*a] += 1;
+    }
+
+    Self { offsets, targets }
+  }
+
+  /// The same edges from the target layer to the source layer.
+  pub fn inverted(&self, target_count: usize) -> Self {
+    let edges = self.edges().map(|(a, b, weight)| (b, a, weight)).collect::<Vec<_>>();
+    Self::new(target_count, &edges)
+  }
+
+  pub fn neighbours(&self, source: usize) -> &[(usize, usize)] {
+    match self.offsets.get(source + 1) {
+      Some(end) => &self.targets[self.offsets[source]..*end],
+      None => &[],
+    }
+  }
+
+  /// Adds an edge at the end of the neighbours of `source`, which may lie past the sources the adjacency was built with.
+  pub fn insert(&mut self, source: usize, target: usize, weight: usize) {
+    if source + 1 >= self.offsets.len() {
+      let end = self.targets.len();
+      self.offsets.resize(source + 2, end);
+    }
+    self.targets.insert(self.offsets[source + 1], (target, weight));
+    for offset in &mut self.offsets[source + 1..] {
+      *offset += 1;
+    }
+  }
+
+  /// Removes an edge, returns its weight.
+  pub fn remove(&mut self, source: usize, target: usize) -> Option<usize> {
+    let end = *self.offsets.get(source + 1)?;
+    let index = self.offsets[source]
+      + self.targets[self.offsets[source]..end]
+        .iter()
+        .position(|(b, _)| *b == target)?;
+    for offset in &mut self.offsets[source + 1..] {
+      *offset -= 1;
+    }
+    Some(self.targets.remove(index).1)
+  }
+
+  /// Keeps only the edges (source id, target id, weight) for which `keep` returns true.
+  pub fn retain(&mut self, keep: impl Fn(usize, usize, usize) -> bool) {
+    let edges = self
+      .edges()
+      .filter(|(a, b, weight)| keep(*a, *b, *weight))
+      .collect::<Vec<_>>();
+    *self = Self::new(self.offsets.len() - 1, &edges);
+  }
+
+  pub fn weight_mut(&mut self, source: usize, target: usize) -> Option<&mut usize> {
+    let end = *self.offsets.get(source + 1)?;
+    self.targets[self.offsets[source]..end]
+      .iter_mut()
+      .find(|(b, _)| *b == target)
+      .map(|(_, weight)| weight)
+  }
+
+  /// All edges as (source id, target id, weight), ordered by source id.
+  pub fn edges(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+    self.offsets.windows(2).enumerate().flat_map(move |(a, range)| {
+      self.targets[range[0]..range[1]]
+        .iter()
+        .map(move |(b, weight)| (a, *b, *weight))
+    })
+  }
+
+  pub fn len(&self) -> usize {
+    self.targets.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.targets.is_empty()
+  }
+
+  /**
+   * The edges with both ends replaced by their position, ordered by source position.
+   *
+   * * `order` The source ids in their current order
+   * * `target_positions` The position of every target id, see `Optimizer::positions`
+   */
+  pub fn map(&self, order: &[usize], target_positions: &[usize]) -> MappedEdges {
+    let mut mapped_edges = Vec::with_capacity(self.len());
+    for (position, a) in order.iter().enumerate() {
+      for (b, weight) in self.neighbours(*a) {
+        mapped_edges.push((position, target_positions[*b], *weight));
+      }
+    }
+    mapped_edges
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_adjacency() {
+    let mut adjacency = Adjacency::new(3, &[(2, 0, 1), (0, 1, 2), (2, 1, 3)]);
+    assert_eq!(adjacency.neighbours(0), &[(1, 2)]);
+    assert!(adjacency.neighbours(1).is_empty());
+    assert_eq!(adjacency.neighbours(2), &[(0, 1), (1, 3)]);
+    assert!(adjacency.neighbours(5).is_empty());
+    assert_eq!(
+      adjacency.edges().collect::<Vec<_>>(),
+      vec![(0, 1, 2), (2, 0, 1), (2, 1, 3)]
+    );
+
+    let inverted = adjacency.inverted(2);
+    assert_eq!(inverted.neighbours(1), &[(0, 2), (2, 3)]);
+    assert_eq!(inverted.inverted(3), adjacency);
+
+    // Source ids 2, 0, 1 at positions 0, 1, 2 and target ids 0, 1 at positions 1, 0
+    assert_eq!(
+      adjacency.map(&[2, 0, 1], &[1, 0]),
+      vec![(0, 1, 1), (0, 0, 3), (1, 0, 2)]
+    );
+
+    *adjacency.weight_mut(2, 1).unwrap() = 5;
+    assert_eq!(adjacency.neighbours(2), &[(0, 1), (1, 5)]);
+    assert!(adjacency.weight_mut(1, 1).is_none());
+
+    adjacency.insert(4, 0, 1);
+    adjacency.insert(0, 0, 2);
+    assert_eq!(adjacency.neighbours(0), &[(1, 2), (0, 2)]);
+    assert_eq!(adjacency.neighbours(4), &[(0, 1)]);
+    assert_eq!(adjacency.remove(2, 0), Some(1));
+    assert_eq!(adjacency.remove(2, 0), None);
+    assert_eq!(
+      adjacency.edges().collect::<Vec<_>>(),
+      vec![(0, 1, 2), (0, 0, 2), (2, 1, 5), (4, 0, 1)]
+    );
+
+    adjacency.retain(|_, b, _| b != 0);
+    assert_eq!(adjacency.edges().collect::<Vec<_>>(), vec![(0, 1, 2), (2, 1, 5)]);
+  }
+}
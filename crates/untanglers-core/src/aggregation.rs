@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 pub fn aggregate_pairwise_matrix(pairwise_matrix: &[f64], group_sizes: &[usize]) -> Vec<f64> {
   let new_size = group_sizes.len();
   let mut result: Vec<f64> = vec![0.; new_size * new_size];
@@ -36,6 +38,25 @@ pub fn aggregate_pairwise_matrix(pairwise_matrix: &[f64], group_sizes: &[usize])
   result
 }
 
+/**
+ * Merges edges between nodes into edges between their groups, summing the weights, e.g. for counting crossings
+ * between the bundles of edges that connect two groups. Edges with both ends in the same pair of groups collapse into
+ * a single edge.
+ *
+ * * `groups1`, `groups2` The group index of each node of the two layers, by position
+ */
+pub fn aggregate_edges(
+  mapped_edges: &[(usize, usize, usize)],
+  groups1: &[usize],
+  groups2: &[usize],
+) -> Vec<(usize, usize, usize)> {
+  let mut weights = BTreeMap::new();
+  for (u, v, weight) in mapped_edges {
+    *weights.entry((groups1[*u], groups2[*v])).or_insert(0) += weight;
+  }
+  weights.into_iter().map(|((u, v), weight)| (u, v, weight)).collect()
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -49,4 +70,11 @@ mod tests {
     let expected_matrix: Vec<f64> = vec![0., 9., 0., -9., 0., 0., 0., 0., 0.];
     assert_eq!(aggregated_matrix, expected_matrix);
   }
+
+  #[test]
+  fn test_aggregate_edges() {
+    let edges = vec![(0, 2, 1), (1, 2, 2), (2, 0, 1), (2, 1, 3)];
+    let aggregated = aggregate_edges(&edges, &[0, 0, 1], &[0, 0, 1]);
+    assert_eq!(aggregated, vec![(0, 1, 3), (1, 0, 4)]);
+  }
 }
@@ -4,6 +4,7 @@ use untanglers_core::{
   mapping::map_edges,
   pairwise::get_pairwise_matrix,
   reducer::swap_nodes,
+  strategy::EnergyTerms,
   utils::{generate_bipartite_graph, timeit},
 };
 
@@ -23,45 +24,22 @@ pub fn main() {
     get_pairwise_matrix(nodes_left.len(), nodes_right.len(), &mapped_edges)
   });
   let mut nodes = (0..nodes_left.len()).collect_vec();
-  let mut crossing_count = timeit("Count crossings", || count_crossings(&nodes_left, &nodes_right, &edges)) as i64;
+  let mut crossing_count = timeit("Count crossings", || count_crossings(&nodes_left, &nodes_right, &edges)) as f64;
+  let terms = EnergyTerms::pairwise(pairwise_matrix, crossing_count);
   log::info!("Start: {} edge crossings", crossing_count);
 
   (nodes, crossing_count) = timeit("Crossings Benchmark 1e3", || {
-    swap_nodes(
-      nodes_left.len(),
-      &pairwise_matrix,
-      1000,
-      10.,
-      crossing_count,
-      nodes,
-      &None,
-    )
+    swap_nodes(&terms, 1000, 10., crossing_count, nodes, &None)
   });
   log::info!("1e3: {} edge crossings", crossing_count);
 
   (nodes, crossing_count) = timeit("Crossings Benchmark 1e4", || {
-    swap_nodes(
-      nodes_left.len(),
-      &pairwise_matrix,
-      10000,
-      1.,
-      crossing_count,
-      nodes,
-      &None,
-    )
+    swap_nodes(&terms, 10000, 1., crossing_count, nodes, &None)
   });
   log::info!("1e4: {} edge crossings", crossing_count);
 
   (_, crossing_count) = timeit("Crossings Benchmark 1e5", || {
-    swap_nodes(
-      nodes_left.len(),
-      &pairwise_matrix,
-      100000,
-      0.1,
-      crossing_count,
-      nodes,
-      &None,
-    )
+    swap_nodes(&terms, 100000, 0.1, crossing_count, nodes, &None)
   });
   log::info!("1e5: {} edge crossings", crossing_count);
 }
@@ -0,0 +1,240 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+
+use crate::error::OptimizerError;
+use crate::group_tree::GroupNames;
+
+/// A group that spans consecutive layers, e.g. a pipeline with nodes in several layers. In every layer it covers it is
+/// represented by the coarsest group with its name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompoundGroup {
+  pub name: String,
+  pub first_layer: usize,
+  pub last_layer: usize,
+}
+
+impl CompoundGroup {
+  pub fn covers(&self, layer_index: usize) -> bool {
+    (self.first_layer..=self.last_layer).contains(&layer_index)
+  }
+}
+
+/// Names of the coarsest groups of a layer, empty if the layer has no hierarchy.
+fn top_names(group_names: &GroupNames, layer_index: usize) -> &[String] {
+  group_names[layer_index].last().map_or(&[], |names| names.as_slice())
+}
+
+/// The compound groups in a layer, as indices into `compound_groups` in their current order from top to bottom.
+pub fn layer_compound_order(
+  compound_groups: &[CompoundGroup],
+  group_names: &GroupNames,
+  layer_index: usize,
+) -> Vec<usize> {
+  let index: HashMap<&str, usize> = compound_groups
+    .iter()
+    .enumerate()
+    .filter(|(_, group)| group.covers(layer_index))
+    .map(|(i, group)| (group.name.as_str(), i))
+    .collect();
+
+  top_names(group_names, layer_index)
+    .iter()
+    .filter_map(|name| index.get(name.as_str()).copied())
+    .collect()
+}
+
+/**
+ * Checks that every compound group is present as a coarsest group in each layer it covers and that all layers agree on
+ * the relative order of the compound groups. Returns one order of all compound groups, top to bottom, that is
+ * consistent with every layer.
+ */
+pub fn validate_compound_groups(
+  compound_groups: &[CompoundGroup],
+  group_names: &GroupNames,
+) -> Result<Vec<usize>, OptimizerError> {
+  let layer_count = group_names.len();
+  let mut seen = HashSet::new();
+
+  for group in compound_groups {
+    if !seen.insert(&group.name) {
+      return Err(OptimizerError::DuplicateCompoundGroup {
+        name: group.name.clone(),
+      });
+    }
+    if group.first_layer > group.last_layer || group.last_layer >= layer_count {
+      return Err(OptimizerError::InvalidCompoundRange {
+        name: group.name.clone(),
+        first_layer: group.first_layer,
+        last_layer: group.last_layer,
+        layer_count,
+      });
+    }
+
+    for layer_index in group.first_layer..=group.last_layer {
+      let count = top_names(group_names, layer_index)
+        .iter()
+        .filter(|name| **name == group.name)
+        .count();
+      if count != 1 {
+        return Err(OptimizerError::MissingCompoundGroup {
+          name: group.name.clone(),
+          layer_index,
+        });
+      }
+    }
+  }
+
+  // Every layer orders the compound groups it contains, combine these into a single order
+  let n = compound_groups.len();
+  let mut successors = vec![HashSet::new(); n];
+  let mut predecessor_count = vec![0; n];
+  for layer_index in 0..layer_count {
+    for (a, b) in layer_compound_order(compound_groups, group_names, layer_index)
+      .into_iter()
+      .tuple_windows()
+    {
+      if successors[a].insert(b) {
+        predecessor_count[b] += 1;
+      }
+    }
+  }
+
+  let mut order = Vec::with_capacity(n);
+  let mut ready = (0..n).filter(|i| predecessor_count[*i] == 0).collect_vec();
+  while let Some(group) = ready.pop() {
+    order.push(group);
+    for successor in successors[group].iter().sorted() {
+      predecessor_count[*successor] -= 1;
+      if predecessor_count[*successor] == 0 {
+        ready.push(*successor);
+      }
+    }
+  }
+
+  if order.len() < n {
+    let group = (0..n).find(|i| predecessor_count[*i] > 0).unwrap();
+    return Err(OptimizerError::InconsistentCompoundOrder {
+      name: compound_groups[group].name.clone(),
+    });
+  }
+  Ok(order)
+}
+
+/// Puts `items` into the positions that its members currently hold in `order`, the other entries stay in place.
+fn reassign_slots(order: &[usize], items: &[usize]) -> Vec<usize> {
+  let members: HashSet<&usize> = items.iter().collect();
+  let mut items = items.iter();
+  order
+    .iter()
+    .map(|entry| {
+      if members.contains(entry) {
+        *items.next().unwrap()
+      } else {
+        *entry
+      }
+    })
+    .collect()
+}
+
+/// Updates the global order of the compound groups to the order they have in the given layer.
+pub fn update_compound_order(
+  compound_order: &[usize],
+  compound_groups: &[CompoundGroup],
+  group_names: &GroupNames,
+  layer_index: usize,
+) -> Vec<usize> {
+  let layer_order = layer_compound_order(compound_groups, group_names, layer_index);
+  reassign_slots(compound_order, &layer_order)
+}
+
+/// New order of the coarsest groups of a layer that puts its compound groups in the global order, as indices into the
+/// current order. Other groups keep their position.
+pub fn align_layer(
+  compound_order: &[usize],
+  compound_groups: &[CompoundGroup],
+  group_names: &GroupNames,
+  layer_index: usize,
+) -> Vec<usize> {
+  let top_names = top_names(group_names, layer_index);
+  let positions: HashMap<&str, usize> = top_names
+    .iter()
+    .enumerate()
+    .map(|(i, name)| (name.as_str(), i))
+    .collect();
+
+  let layer_order = layer_compound_order(compound_groups, group_names, layer_index);
+  let target = compound_order
+    .iter()
+    .filter(|group| layer_order.contains(group))
+    .copied()
+    .collect_vec();
+  let name_position = |group: &usize| positions[compound_groups[*group].name.as_str()];
+
+  reassign_slots(
+    &(0..top_names.len()).collect_vec(),
+    &target.iter().map(name_position).collect_vec(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn names(layers: &[&[&str]]) -> GroupNames {
+    layers
+      .iter()
+      .map(|top| vec![top.iter().map(|name| name.to_string()).collect()])
+      .collect()
+  }
+
+  fn compound(name: &str, first_layer: usize, last_layer: usize) -> CompoundGroup {
+    CompoundGroup {
+      name: name.to_string(),
+      first_layer,
+      last_layer,
+    }
+  }
+
+  #[test]
+  fn test_validate() {
+    let group_names = names(&[&["a", "x", "c"], &["y", "b", "a", "c"], &["b", "c"]]);
+    let groups = vec![compound("a", 0, 1), compound("b", 1, 2), compound("c", 0, 2)];
+    assert_eq!(validate_compound_groups(&groups, &group_names).unwrap(), vec![1, 0, 2]);
+
+    let groups = vec![compound("a", 0, 1), compound("c", 0, 1), compound("b", 1, 1)];
+    assert!(validate_compound_groups(&groups, &group_names).is_ok());
+
+    let inconsistent = names(&[&["a", "c"], &["c", "a"]]);
+    assert!(matches!(
+      validate_compound_groups(&[compound("a", 0, 1), compound("c", 0, 1)], &inconsistent),
+      Err(OptimizerError::InconsistentCompoundOrder { .. })
+    ));
+    assert!(matches!(
+      validate_compound_groups(&[compound("x", 0, 1)], &group_names),
+      Err(OptimizerError::MissingCompoundGroup { layer_index: 1, .. })
+    ));
+    assert!(matches!(
+      validate_compound_groups(&[compound("c", 1, 3)], &group_names),
+      Err(OptimizerError::InvalidCompoundRange { .. })
+    ));
+    assert!(matches!(
+      validate_compound_groups(&[compound("c", 0, 0), compound("c", 1, 1)], &group_names),
+      Err(OptimizerError::DuplicateCompoundGroup { .. })
+    ));
+  }
+
+  #[test]
+  fn test_align() {
+    let groups = vec![compound("a", 0, 1), compound("b", 1, 2), compound("c", 0, 2)];
+
+    // Layer 1 moved c to the top
+    let group_names = names(&[&["a", "x", "c"], &["c", "y", "b", "a"], &["b", "c"]]);
+    let order = update_compound_order(&[1, 0, 2], &groups, &group_names, 1);
+    assert_eq!(order, vec![2, 1, 0]);
+
+    assert_eq!(align_layer(&order, &groups, &group_names, 0), vec![2, 1, 0]);
+    assert_eq!(align_layer(&order, &groups, &group_names, 1), vec![0, 1, 2, 3]);
+    assert_eq!(align_layer(&order, &groups, &group_names, 2), vec![1, 0]);
+  }
+}
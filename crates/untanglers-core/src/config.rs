@@ -0,0 +1,186 @@
+use crate::error::OptimizerError;
+use crate::strategy::Annealing;
+
+/**
+ * Settings of the simulated annealing in `optimize` and `cooldown`, created with `OptimizeConfig::builder` which
+ * rejects values that can't work. The default is what the builder starts from: 1 to 0.1 in 5 steps of 200 iterations,
+ * and 10 passes.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptimizeConfig {
+  start_temp: f64,
+  end_temp: f64,
+  steps: usize,
+  max_iterations: usize,
+  passes: usize,
+}
+
+impl Default for OptimizeConfig {
+  fn default() -> Self {
+    Self {
+      start_temp: 1.,
+      end_temp: 0.1,
+      steps: 5,
+      max_iterations: 200,
+      passes: 10,
+    }
+  }
+}
+
+impl OptimizeConfig {
+  pub fn builder() -> OptimizeConfigBuilder {
+    OptimizeConfigBuilder {
+      config: Self::default(),
+    }
+  }
+
+  /// A builder that starts from this config, to change some of its settings
+  pub fn to_builder(&self) -> OptimizeConfigBuilder {
+    OptimizeConfigBuilder { config: self.clone() }
+  }
+
+  /// Temperature of the first annealing step, 0 only accepts swaps that don't add crossings
+  pub fn start_temp(&self) -> f64 {
+    self.start_temp
+  }
+
+  /// Temperature of the last annealing step, the temperature falls geometrically in between
+  pub fn end_temp(&self) -> f64 {
+    self.end_temp
+  }
+
+  pub fn steps(&self) -> usize {
+    self.steps
+  }
+
+  /// Sweeps over the layer at each temperature, fewer if no crossings are left
+  pub fn max_iterations(&self) -> usize {
+    self.max_iterations
+  }
+
+  /// Number of sweeps over all layers in `optimize`, 0 keeps the current order. `cooldown` ignores it.
+  pub fn passes(&self) -> usize {
+    self.passes
+  }
+
+  pub fn annealing(&self) -> Annealing {
+    Annealing::from(self.clone())
+  }
+
+  fn validate(&self) -> Result<(), OptimizerError> {
+    let invalid = |message: String| Err(OptimizerError::InvalidConfig { message });
+
+    for (name, temperature) in [("start_temp", self.start_temp), ("end_temp", self.end_temp)] {
+      if !temperature.is_finite() || temperature < 0. {
+        return invalid(format!("{name} must be a finite number >= 0, got {temperature}"));
+      }
+    }
+    if self.end_temp > self.start_temp {
+      return invalid(format!(
+        "end_temp {} is above start_temp {}, the temperature can only fall",
+        self.end_temp, self.start_temp
+      ));
+    }
+    if self.steps == 0 {
+      return invalid("steps must be at least 1".to_string());
+    }
+    if self.max_iterations == 0 {
+      return invalid("max_iterations must be at least 1".to_string());
+    }
+    Ok(())
+  }
+}
+
+/// Builds an `OptimizeConfig`, settings that aren't set keep their default.
+#[derive(Clone, Debug)]
+pub struct OptimizeConfigBuilder {
+  config: OptimizeConfig,
+}
+
+impl OptimizeConfigBuilder {
+  pub fn start_temp(mut self, start_temp: f64) -> Self {
+    self.config.start_temp = start_temp;
+    self
+  }
+
+  pub fn end_temp(mut self, end_temp: f64) -> Self {
+    self.config.end_temp = end_temp;
+    self
+  }
+
+  /// Sets both temperatures, for annealing at a constant temperature
+  pub fn temperature(self, temperature: f64) -> Self {
+    self.start_temp(temperature).end_temp(temperature)
+  }
+
+  pub fn steps(mut self, steps: usize) -> Self {
+    self.config.steps = steps;
+    self
+  }
+
+  pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+    self.config.max_iterations = max_iterations;
+    self
+  }
+
+  pub fn passes(mut self, passes: usize) -> Self {
+    self.config.passes = passes;
+    self
+  }
+
+  pub fn build(self) -> Result<OptimizeConfig, OptimizerError> {
+    self.config.validate()?;
+    Ok(self.config)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_build() {
+    assert_eq!(OptimizeConfig::builder().build().unwrap(), OptimizeConfig::default());
+
+    let config = OptimizeConfig::builder()
+      .start_temp(2.)
+      .end_temp(0.)
+      .steps(3)
+      .max_iterations(50)
+      .passes(0)
+      .build()
+      .unwrap();
+    assert_eq!(
+      (
+        config.start_temp(),
+        config.end_temp(),
+        config.steps(),
+        config.max_iterations(),
+        config.passes()
+      ),
+      (2., 0., 3, 50, 0)
+    );
+
+    let config = OptimizeConfig::builder().temperature(0.5).build().unwrap();
+    assert_eq!((config.start_temp(), config.end_temp()), (0.5, 0.5));
+  }
+
+  #[test]
+  fn test_validate() {
+    let invalid = [
+      OptimizeConfig::builder().start_temp(0.5).end_temp(1.),
+      OptimizeConfig::builder().end_temp(-0.1),
+      OptimizeConfig::builder().temperature(-1.),
+      OptimizeConfig::builder().start_temp(f64::NAN),
+      OptimizeConfig::builder().start_temp(f64::INFINITY),
+      OptimizeConfig::builder().steps(0),
+      OptimizeConfig::builder().max_iterations(0),
+    ];
+    for builder in invalid {
+      assert!(
+        matches!(builder.clone().build(), Err(OptimizerError::InvalidConfig { .. })),
+        "{builder:?}"
+      );
+    }
+  }
+}
@@ -37,3 +37,84 @@ where
   let mapped_edges = map_edges(nodes1, nodes2, edges);
   _count_crossings(nodes2.len(), &mapped_edges)
 }
+
+/**
+ * Like `_count_crossings`, but instead of the total returns for every node of either layer the crossings that its
+ * edges are involved in. Every crossing is counted for all four endpoints, so each side sums to twice the total.
+ * Quadratic in the number of edges, meant for aggregated edges between groups.
+ */
+pub fn crossings_per_node(
+  count1: usize,
+  count2: usize,
+  mapped_edges: &[(usize, usize, usize)],
+) -> (Vec<usize>, Vec<usize>) {
+  let mut totals1 = vec![0; count1];
+  let mut totals2 = vec![0; count2];
+
+  for (i, (u1, v1, weight1)) in mapped_edges.iter().enumerate() {
+    for (u2, v2, weight2) in &mapped_edges[i + 1..] {
+      if (u1 < u2 && v1 > v2) || (u1 > u2 && v1 < v2) {
+        let crossings = weight1 * weight2;
+        totals1[*u1] += crossings;
+        totals1[*u2] += crossings;
+        totals2[*v1] += crossings;
+        totals2[*v2] += crossings;
+      }
+    }
+  }
+
+  (totals1, totals2)
+}
+
+/**
+ * Counts crossings between edges within a single layer. These are drawn as arcs on one side of the layer, so two arcs
+ * cross exactly when their endpoints interleave, and arcs that share an endpoint never cross. Crossings with edges to
+ * the neighbouring layers are not counted, so the result only depends on the order of this layer.
+ */
+pub fn _count_intra_crossings(mapped_edges: &[(usize, usize, usize)]) -> usize {
+  let arcs = mapped_edges
+    .iter()
+    .map(|(a, b, w)| (*a.min(b), *a.max(b), *w))
+    .collect::<Vec<_>>();
+
+  let mut crossings = 0;
+  for (i, (start1, end1, weight1)) in arcs.iter().enumerate() {
+    for (start2, end2, weight2) in &arcs[i + 1..] {
+      if (start1 < start2 && start2 < end1 && end1 < end2) || (start2 < start1 && start1 < end2 && end2 < end1) {
+        crossings += weight1 * weight2;
+      }
+    }
+  }
+
+  crossings
+}
+
+pub fn count_intra_crossings<T>(nodes: &[T], edges: &[(T, T, usize)]) -> usize
+where
+  T: Eq + Hash + Clone,
+{
+  _count_intra_crossings(&map_edges(nodes, nodes, edges))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_crossings_per_node() {
+    let edges = vec![(0, 1, 1), (1, 0, 2), (1, 1, 1), (2, 0, 3)];
+    let (totals1, totals2) = crossings_per_node(3, 2, &edges);
+    assert_eq!(totals1, vec![5, 5, 6]);
+    assert_eq!(totals2, vec![8, 8]);
+    assert_eq!(totals1.iter().sum::<usize>(), 2 * _count_crossings(2, &edges));
+  }
+
+  #[test]
+  fn test_intra_crossings() {
+    let nodes = vec![0, 1, 2, 3, 4];
+    assert_eq!(count_intra_crossings(&nodes, &[(0, 2, 1), (1, 3, 2)]), 2);
+    assert_eq!(count_intra_crossings(&nodes, &[(0, 3, 1), (1, 2, 2)]), 0);
+    assert_eq!(count_intra_crossings(&nodes, &[(0, 2, 1), (2, 4, 2)]), 0);
+    assert_eq!(count_intra_crossings(&nodes, &[(3, 1, 3), (4, 2, 2), (0, 2, 1)]), 9);
+  }
+}
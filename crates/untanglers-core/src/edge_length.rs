@@ -0,0 +1,149 @@
+use itertools::Itertools;
+
+/// Vertical coordinate of a node, layers are centred on each other like they are drawn.
+pub fn centred_position(position: usize, node_count: usize) -> f64 {
+  position as f64 - (node_count as f64 - 1.) / 2.
+}
+
+/**
+ * Weighted vertical edge length Σ w * |y_a - y_b| between a swappable layer and its static neighbours.
+ *
+ * Unlike crossings this cost depends on absolute positions, so it can't be expressed as a pairwise matrix. Instead
+ * each swappable unit (a node, or a group of nodes when swapping at a coarser granularity) keeps its edges as
+ * (offset within the unit, static position, weight), which lets a swap be evaluated in O(degree) given where the
+ * pair starts.
+ */
+pub struct EdgeLength {
+  weight: f64,
+  node_count: usize,
+  sizes: Vec<usize>,
+  members: Vec<Vec<(usize, f64, f64)>>,
+}
+
+impl EdgeLength {
+  /// * `static_layers` For each neighbouring layer its node count and the edges mapped to (swappable, static, weight)
+  /// * `groups` Sizes of the swappable units, if None every node is its own unit
+  #[allow(clippy::type_complexity)]
+  pub fn new(
+    weight: f64,
+    node_count: usize,
+    static_layers: &[(usize, &[(usize, usize, usize)])],
+    groups: Option<&[usize]>,
+  ) -> Self {
+    let sizes = match groups {
+      Some(groups) => groups.to_vec(),
+      None => vec![1; node_count],
+    };
+
+    // Units are contiguous in the order the swappable nodes were given in
+    let mut unit_of = Vec::with_capacity(node_count);
+    for (unit, size) in sizes.iter().enumerate() {
+      for offset in 0..*size {
+        unit_of.push((unit, offset));
+      }
+    }
+
+    let mut members = vec![vec![]; sizes.len()];
+    for (static_count, mapped_edges) in static_layers {
+      for (swappable_id, static_id, edge_weight) in *mapped_edges {
+        let (unit, offset) = unit_of[*swappable_id];
+        members[unit].push((offset, centred_position(*static_id, *static_count), *edge_weight as f64));
+      }
+    }
+
+    Self {
+      weight,
+      node_count,
+      sizes,
+      members,
+    }
+  }
+
+  pub fn size(&self, unit: usize) -> usize {
+    self.sizes[unit]
+  }
+
+  fn unit_cost(&self, unit: usize, start: usize) -> f64 {
+    self.members[unit]
+      .iter()
+      .map(|(offset, static_position, edge_weight)| {
+        edge_weight * (centred_position(start + offset, self.node_count) - static_position).abs()
+      })
+      .sum()
+  }
+
+  /// Weighted cost of an ordering of the units.
+  pub fn cost(&self, order: &[usize]) -> f64 {
+    self.range_cost(order, 0)
+  }
+
+  /// Weighted cost of a run of units placed from node position `start` onwards.
+  pub fn range_cost(&self, units: &[usize], start: usize) -> f64 {
+    let starts = units.iter().scan(start, |start, unit| {
+      *start += self.sizes[*unit];
+      Some(*start - self.sizes[*unit])
+    });
+
+    self.weight
+      * units
+        .iter()
+        .zip_eq(starts)
+        .map(|(unit, start)| self.unit_cost(*unit, start))
+        .sum::<f64>()
+  }
+
+  /// Weighted reduction in cost when swapping `unit_a` starting at `start` with `unit_b` directly after it.
+  pub fn swap_delta(&self, unit_a: usize, unit_b: usize, start: usize) -> f64 {
+    let before = self.unit_cost(unit_a, start) + self.unit_cost(unit_b, start + self.sizes[unit_a]);
+    let after = self.unit_cost(unit_b, start) + self.unit_cost(unit_a, start + self.sizes[unit_b]);
+    self.weight * (before - after)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_node_swaps() {
+    // 4 swappable nodes, 2 static nodes above and 3 below
+    let edges1 = vec![(0, 1, 1), (2, 0, 2)];
+    let edges2 = vec![(1, 2, 1), (3, 0, 3), (0, 1, 1)];
+    let edge_length = EdgeLength::new(0.5, 4, &[(2, &edges1), (3, &edges2)], None);
+
+    // Positions -1.5 .. 1.5 vs -0.5, 0.5 and -1, 0, 1
+    let expected = 1. * 2. + 2. * 1. + 1. * 1.5 + 3. * 2.5 + 1. * 1.5;
+    assert_eq!(edge_length.cost(&[0, 1, 2, 3]), 0.5 * expected);
+
+    for (order, start) in [(vec![0, 1, 2, 3], 0), (vec![3, 1, 0, 2], 1), (vec![3, 1, 2, 0], 2)] {
+      let mut swapped = order.clone();
+      swapped.swap(start, start + 1);
+      assert_eq!(
+        edge_length.swap_delta(order[start], order[start + 1], start),
+        edge_length.cost(&order) - edge_length.cost(&swapped)
+      );
+    }
+  }
+
+  #[test]
+  fn test_group_swaps() {
+    let edges = vec![(0, 4, 1), (1, 0, 2), (2, 3, 1), (4, 1, 5), (5, 2, 1)];
+    let edge_length = EdgeLength::new(1., 6, &[(6, &edges)], Some(&[2, 1, 3]));
+
+    assert_eq!(edge_length.size(2), 3);
+    assert_eq!(
+      edge_length.range_cost(&[1, 2], 2),
+      edge_length.cost(&[0, 1, 2]) - edge_length.range_cost(&[0], 0)
+    );
+    assert_eq!(edge_length.cost(&[0, 1, 2]), 4. + 2. + 1. + 15. + 3.);
+
+    assert_eq!(
+      edge_length.swap_delta(1, 2, 2),
+      edge_length.cost(&[0, 1, 2]) - edge_length.cost(&[0, 2, 1])
+    );
+    assert_eq!(
+      edge_length.swap_delta(2, 0, 0),
+      edge_length.cost(&[2, 0, 1]) - edge_length.cost(&[0, 2, 1])
+    );
+  }
+}
@@ -1,3 +1,5 @@
+use std::fmt::Debug;
+
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -24,19 +26,271 @@ pub enum OptimizerError {
     self_size: usize,
   },
 
+  #[error(
+    "Node {position}{} of layer {layer_index} has a group path of length {depth}, expected {expected}",
+    named(node)
+  )]
+  GroupDepthMismatch {
+    position: usize,
+    node: Option<String>,
+    layer_index: usize,
+    depth: usize,
+    expected: usize,
+  },
+
+  #[error("Node {position}{} of layer {layer_index} has no group path", named(node))]
+  MissingGroupPath {
+    position: usize,
+    node: Option<String>,
+    layer_index: usize,
+  },
+
+  #[error("Compound group {name:?} is defined more than once")]
+  DuplicateCompoundGroup { name: String },
+
+  #[error(
+    "Compound group {name:?} spans layers {first_layer} to {last_layer}, expected a range within 0..{layer_count}"
+  )]
+  InvalidCompoundRange {
+    name: String,
+    first_layer: usize,
+    last_layer: usize,
+    layer_count: usize,
+  },
+
+  #[error("Compound group {name:?} needs exactly one coarsest group with its name in layer {layer_index}")]
+  MissingCompoundGroup { name: String, layer_index: usize },
+
+  #[error("Layers disagree on the relative order of compound group {name:?}")]
+  InconsistentCompoundOrder { name: String },
+
   #[error("expected n-1 edge layers for n node layers, got E={edges} vs N={layers}")]
   EdgeLayerMismatch { edges: usize, layers: usize },
 
-  #[error("Duplicate edge in layer {layer_index}: ({node_a}, {node_b})")]
+  #[error("expected one list of intra-layer edges for each node layer, got {edges} vs {layers}")]
+  IntraEdgeLayerMismatch { edges: usize, layers: usize },
+
+  #[error(
+    "Intra-layer edge {edge_index}{} of layer {layer_index} connects a node to itself",
+    named(edge)
+  )]
+  SelfLoop {
+    edge_index: usize,
+    edge: Option<String>,
+    layer_index: usize,
+  },
+
+  #[error(
+    "Edge {edge_index}{} of layer {layer_index} connects the same nodes as an earlier edge",
+    named(edge)
+  )]
   DuplicateEdge {
-    node_a: String,
-    node_b: String,
+    edge_index: usize,
+    edge: Option<String>,
     layer_index: usize,
   },
 
-  #[error("Edges contains missing node {node_name:?} at layer {layer_index}")]
-  MissingNode { node_name: String, layer_index: usize },
+  #[error(
+    "Edge {edge_index}{} of layer {layer_index} connects a node that is missing from its layer",
+    named(edge)
+  )]
+  MissingNode {
+    edge_index: usize,
+    edge: Option<String>,
+    layer_index: usize,
+  },
+
+  #[error("expected one reference ordering for each node layer, got {reference} vs {layers}")]
+  ReferenceMismatch { reference: usize, layers: usize },
+
+  #[error("The weight of the {term} must be a finite number >= 0, got {weight}")]
+  InvalidWeight { term: String, weight: f64 },
+
+  #[error("Node{} is already part of layer {layer_index}, at position {position}", named(node))]
+  DuplicateNode {
+    position: usize,
+    node: Option<String>,
+    layer_index: usize,
+  },
+
+  #[error("Position {position} is out of range for layer {layer_index} with {node_count} nodes")]
+  InvalidPosition {
+    position: usize,
+    node_count: usize,
+    layer_index: usize,
+  },
+
+  #[error("Node{} not found in layer {layer_index}", named(node))]
+  UnknownNode { node: Option<String>, layer_index: usize },
+
+  #[error("Edge{} not found in layer {layer_index}", named(edge))]
+  UnknownEdge { edge: Option<String>, layer_index: usize },
+
+  #[error("Group index out of range at layer {layer_index}: {group_index} > {group_count} - 1")]
+  InvalidGroup {
+    layer_index: usize,
+    group_index: usize,
+    group_count: usize,
+  },
+
+  #[error("Objective returned a matrix of size {size} for layer {layer_index} with {node_count} nodes")]
+  ObjectiveSizeMismatch {
+    layer_index: usize,
+    size: usize,
+    node_count: usize,
+  },
+
+  #[error("Objective failed: {message}")]
+  ObjectiveError { message: String },
+
+  #[error("Unknown strategy {name:?}")]
+  UnknownStrategy { name: String },
+
+  #[error("Unknown parameter {parameter:?} for strategy {strategy:?}")]
+  UnknownParameter { strategy: String, parameter: String },
+
+  #[error("Parameter {parameter:?} for strategy {strategy:?} must be a whole number >= 0, got {value}")]
+  InvalidParameter {
+    strategy: String,
+    parameter: String,
+    value: f64,
+  },
+
+  #[error("Strategy returned an order of {size} units for a layer with {unit_count} units that is not a permutation")]
+  InvalidStrategyOrder { size: usize, unit_count: usize },
+
+  #[error("Exact solver is limited to {max_nodes} nodes, the graph has {node_count}")]
+  TooManyNodes { node_count: usize, max_nodes: usize },
+
+  #[error("Granularity out of range at layer {layer_index}: {granularity} > {level_count} - 1")]
+  InvalidGranularity {
+    layer_index: usize,
+    granularity: usize,
+    level_count: usize,
+  },
 
   #[error("Layer index out of range: {layer_index} > {layer_count} - 1")]
   InvalidLayer { layer_index: usize, layer_count: usize },
+
+  #[error("Optimizers with objectives can't be serialized, clear them first and add them again after restoring")]
+  ObjectivesNotSerializable,
+
+  #[error("Invalid snapshot: {message}")]
+  InvalidSnapshot { message: String },
+
+  #[error("Invalid optimize config: {message}")]
+  InvalidConfig { message: String },
+
+  #[error("Optimization was interrupted")]
+  Interrupted,
+}
+
+/// The name of a node or edge in an error message, if the caller could describe it.
+fn named(name: &Option<String>) -> String {
+  name.as_ref().map_or(String::new(), |name| format!(" {name}"))
+}
+
+/**
+ * Errors about specific nodes and edges hold their position, since the optimizers only require `Eq + Hash + Clone`
+ * from the node type. Callers that know the node type, e.g. the bindings, can name the nodes with these methods when
+ * the type implements `Debug`.
+ */
+impl OptimizerError {
+  /// Names the node of `UnknownNode`, `DuplicateNode`, `GroupDepthMismatch` and `MissingGroupPath`.
+  pub fn with_node(mut self, node: &impl Debug) -> Self {
+    match &mut self {
+      Self::UnknownNode { node: name, .. }
+      | Self::DuplicateNode { node: name, .. }
+      | Self::GroupDepthMismatch { node: name, .. }
+      | Self::MissingGroupPath { node: name, .. } => *name = Some(format!("{node:?}")),
+      _ => {}
+    }
+    self
+  }
+
+  /// Names the edge of `UnknownEdge`, `DuplicateEdge`, `MissingNode` and `SelfLoop`.
+  pub fn with_edge(mut self, node_a: &impl Debug, node_b: &impl Debug) -> Self {
+    match &mut self {
+      Self::UnknownEdge { edge: name, .. }
+      | Self::DuplicateEdge { edge: name, .. }
+      | Self::MissingNode { edge: name, .. }
+      | Self::SelfLoop { edge: name, .. } => *name = Some(format!("({node_a:?}, {node_b:?})")),
+      _ => {}
+    }
+    self
+  }
+
+  /// Names the node or edge of an error about a graph that failed validation, `nodes` and `edges` are the lists the
+  /// positions refer to.
+  pub fn with_graph<T: Debug>(self, nodes: &[Vec<T>], edges: &[Vec<(T, T, usize)>]) -> Self {
+    match self {
+      Self::DuplicateNode {
+        position, layer_index, ..
+      }
+      | Self::GroupDepthMismatch {
+        position, layer_index, ..
+      }
+      | Self::MissingGroupPath {
+        position, layer_index, ..
+      } => match nodes.get(layer_index).and_then(|layer| layer.get(position)) {
+        Some(node) => self.with_node(node),
+        None => self,
+      },
+      Self::DuplicateEdge {
+        edge_index,
+        layer_index,
+        ..
+      }
+      | Self::MissingNode {
+        edge_index,
+        layer_index,
+        ..
+      }
+      | Self::SelfLoop {
+        edge_index,
+        layer_index,
+        ..
+      } => match edges.get(layer_index).and_then(|layer| layer.get(edge_index)) {
+        Some((node_a, node_b, _)) => self.with_edge(node_a, node_b),
+        None => self,
+      },
+      _ => self,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_named() {
+    let err = OptimizerError::UnknownNode {
+      node: None,
+      layer_index: 1,
+    };
+    assert_eq!(err.to_string(), "Node not found in layer 1");
+    assert_eq!(err.with_node(&"b").to_string(), "Node \"b\" not found in layer 1");
+
+    let err = OptimizerError::MissingNode {
+      edge_index: 1,
+      edge: None,
+      layer_index: 0,
+    };
+    let edges = vec![vec![(1, 3, 1), (2, 4, 1)]];
+    assert_eq!(
+      err.with_graph(&[vec![1, 2], vec![3]], &edges).to_string(),
+      "Edge 1 (2, 4) of layer 0 connects a node that is missing from its layer"
+    );
+
+    let err = OptimizerError::DuplicateNode {
+      position: 0,
+      node: None,
+      layer_index: 0,
+    };
+    assert_eq!(
+      err.with_graph(&[vec!["a", "a"]], &[]).to_string(),
+      "Node \"a\" is already part of layer 0, at position 0"
+    );
+  }
 }
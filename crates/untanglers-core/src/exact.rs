@@ -0,0 +1,328 @@
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use itertools::Itertools;
+
+use crate::count_crossings::_count_crossings;
+use crate::error::OptimizerError;
+use crate::strategy::MappedEdges;
+
+/// Search nodes visited between checks of the time limit.
+const DEADLINE_CHECK_INTERVAL: usize = 1024;
+
+pub struct ExactSolution {
+  /// For each layer the new order as indices into the original order
+  pub orders: Vec<Vec<usize>>,
+  pub crossings: usize,
+  /// False if the time limit was hit, `orders` is then the best layout found so far
+  pub optimal: bool,
+}
+
+/**
+ * Lower bound on the crossings between two layers that holds for any order of both: for every pair of nodes (u1, u2)
+ * in the first layer and every pair of their neighbours (v, w), either the edges u1-w and u2-v cross or u1-v and u2-w
+ * do, whatever the orders.
+ */
+fn pair_lower_bound(size1: usize, size2: usize, edges: &[(usize, usize, usize)]) -> usize {
+  let mut weights = vec![0; size1 * size2];
+  for (u, v, weight) in edges {
+    weights[u * size2 + v] = *weight;
+  }
+  let weight = |u: usize, v: usize| weights[u * size2 + v];
+
+  let mut bound = 0;
+  for (u1, u2) in (0..size1).tuple_combinations() {
+    for (v, w) in (0..size2).tuple_combinations() {
+      bound += (weight(u1, w) * weight(u2, v)).min(weight(u1, v) * weight(u2, w));
+    }
+  }
+  bound
+}
+
+struct BranchAndBound {
+  layer_sizes: Vec<usize>,
+  /// For each layer and node the neighbours in the previous layer with the edge weights
+  left_neighbours: Vec<Vec<Vec<(usize, usize)>>>,
+  /// Lower bound on the crossings between layer i and all layers after it, for orders that aren't fixed yet
+  future_bounds: Vec<usize>,
+  orders: Vec<Vec<usize>>,
+  best_orders: Vec<Vec<usize>>,
+  best_crossings: usize,
+  deadline: Option<Instant>,
+  visited: usize,
+  timed_out: bool,
+}
+
+impl BranchAndBound {
+  /// Crossings between the edges to the previous layer of each pair of nodes (A, B) in `layer`, given that A comes
+  /// first. The previous layer is already fixed, so these don't depend on any other node.
+  fn pair_crossings(&self, layer: usize) -> Vec<usize> {
+    let size = self.layer_sizes[layer];
+    let mut crossings = vec![0; size * size];
+    if layer == 0 {
+      return crossings;
+    }
+
+    let mut positions = vec![0; self.layer_sizes[layer - 1]];
+    for (position, node) in self.orders[layer - 1].iter().enumerate() {
+      positions[*node] = position;
+    }
+
+    let neighbours = &self.left_neighbours[layer];
+    for a in 0..size {
+      for b in 0..size {
+        if a != b {
+          crossings[a * size + b] = neighbours[a]
+            .iter()
+            .cartesian_product(&neighbours[b])
+            .filter(|((v, _), (u, _))| positions[*v] > positions[*u])
+            .map(|((_, w1), (_, w2))| w1 * w2)
+            .sum();
+        }
+      }
+    }
+    crossings
+  }
+
+  fn out_of_time(&mut self) -> bool {
+    self.visited += 1;
+    if !self.timed_out && self.visited.is_multiple_of(DEADLINE_CHECK_INTERVAL) {
+      self.timed_out = self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+    }
+    self.timed_out
+  }
+
+  fn solve_layer(&mut self, layer: usize, cost: usize) {
+    if layer == self.layer_sizes.len() {
+      if cost < self.best_crossings {
+        self.best_crossings = cost;
+        self.best_orders.clone_from(&self.orders);
+      }
+      return;
+    }
+
+    let size = self.layer_sizes[layer];
+    let crossings = self.pair_crossings(layer);
+    let remaining_bound = (0..size)
+      .tuple_combinations()
+      .map(|(a, b)| crossings[a * size + b].min(crossings[b * size + a]))
+      .sum();
+
+    self.orders[layer].clear();
+    let mut remaining = (0..size).collect_vec();
+    self.place(layer, &crossings, &mut remaining, cost, remaining_bound);
+  }
+
+  /**
+   * Places the next node of `layer`. Every remaining node comes after the placed one, so its crossings with them are
+   * fixed at this point. The crossings between pairs of remaining nodes are bounded by the cheaper of their two orders.
+   */
+  fn place(
+    &mut self,
+    layer: usize,
+    crossings: &[usize],
+    remaining: &mut Vec<usize>,
+    cost: usize,
+    remaining_bound: usize,
+  ) {
+    if remaining.is_empty() {
+      return self.solve_layer(layer + 1, cost);
+    }
+    if self.out_of_time() {
+      return;
+    }
+
+    let size = self.layer_sizes[layer];
+    let future_bound = self.future_bounds[layer];
+    let mut candidates = remaining
+      .iter()
+      .filter(|b| {
+        // Reversing every layer gives the same crossings, so only layouts with the first node of the first layer
+        // before its last node are considered
+        !(layer == 0 && remaining.len() == 1 && size > 1 && **b < self.orders[0][0])
+      })
+      .map(|b| {
+        let (mut added, mut bound_drop) = (0, 0);
+        for a in remaining.iter().filter(|a| *a != b) {
+          added += crossings[b * size + a];
+          bound_drop += crossings[a * size + b].min(crossings[b * size + a]);
+        }
+        (*b, cost + added, remaining_bound - bound_drop)
+      })
+      .filter(|(_, new_cost, new_bound)| new_cost + new_bound + future_bound < self.best_crossings)
+      .collect_vec();
+    candidates.sort_by_key(|(_, new_cost, new_bound)| new_cost + new_bound);
+
+    for (b, new_cost, new_bound) in candidates {
+      if self.timed_out {
+        return;
+      }
+      // The best layout may have improved in a sibling branch
+      if new_cost + new_bound + future_bound >= self.best_crossings {
+        continue;
+      }
+
+      let position = remaining.iter().position(|node| *node == b).unwrap();
+      remaining.remove(position);
+      self.orders[layer].push(b);
+
+      self.place(layer, crossings, remaining, new_cost, new_bound);
+
+      self.orders[layer].pop();
+      remaining.insert(position, b);
+    }
+  }
+}
+
+/**
+ * Exact multilayer crossing minimisation by branch and bound. Layers are ordered from first to last, placing one node
+ * at a time, so the previous layer is always fixed and the crossings of each placed node with the previous layer are
+ * known exactly. Partial layouts are pruned with two lower bounds: the cheaper order of every pair of nodes that
+ * remain in the current layer, and a bound on the crossings between the later layers that holds for any order, see
+ * `pair_lower_bound`.
+ *
+ * The search starts from the given order, so if the time limit is hit the result is never worse than that.
+ *
+ * * `layer_sizes` The number of nodes in each layer
+ * * `edges` For each pair of neighbouring layers the edges as indices into both layers
+ * * `max_nodes` Guard against runaway searches, the search space grows factorially with the layer sizes
+ */
+pub fn solve_exact(
+  layer_sizes: &[usize],
+  edges: &[MappedEdges],
+  max_nodes: usize,
+  time_limit: Option<Duration>,
+) -> Result<ExactSolution, OptimizerError> {
+  let node_count = layer_sizes.iter().sum();
+  if node_count > max_nodes {
+    return Err(OptimizerError::TooManyNodes { node_count, max_nodes });
+  }
+
+  let mut left_neighbours = layer_sizes.iter().map(|size| vec![vec![]; *size]).collect_vec();
+  for (i, layer_edges) in edges.iter().enumerate() {
+    for (u, v, weight) in layer_edges {
+      left_neighbours[i + 1][*v].push((*u, *weight));
+    }
+  }
+
+  let mut future_bounds = vec![0; layer_sizes.len() + 1];
+  for i in (0..edges.len()).rev() {
+    future_bounds[i] = future_bounds[i + 1] + pair_lower_bound(layer_sizes[i], layer_sizes[i + 1], &edges[i]);
+  }
+
+  let orders = layer_sizes.iter().map(|size| (0..*size).collect_vec()).collect_vec();
+  let crossings = edges
+    .iter()
+    .enumerate()
+    .map(|(i, layer_edges)| _count_crossings(layer_sizes[i + 1], layer_edges))
+    .sum();
+
+  let mut search = BranchAndBound {
+    layer_sizes: layer_sizes.to_vec(),
+    left_neighbours,
+    future_bounds,
+    orders: orders.clone(),
+    best_orders: orders,
+    best_crossings: crossings,
+    deadline: time_limit.map(|limit| Instant::now() + limit),
+    visited: 0,
+    timed_out: false,
+  };
+  if search.best_crossings > search.future_bounds[0] {
+    search.solve_layer(0, 0);
+  }
+
+  Ok(ExactSolution {
+    orders: search.best_orders,
+    crossings: search.best_crossings,
+    optimal: !search.timed_out,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::generate_multipartite_graph;
+
+  fn mapped_graph(layer_sizes: &[usize]) -> Vec<MappedEdges> {
+    let (nodes, edges) = generate_multipartite_graph(layer_sizes.to_vec()).unwrap();
+    (0..edges.len())
+      .map(|i| crate::mapping::map_edges(&nodes[i], &nodes[i + 1], &edges[i]))
+      .collect()
+  }
+
+  fn crossings(layer_sizes: &[usize], edges: &[MappedEdges], orders: &[Vec<usize>]) -> usize {
+    let positions = orders
+      .iter()
+      .map(|order| crate::mapping::invert_vec(order))
+      .collect_vec();
+    edges
+      .iter()
+      .enumerate()
+      .map(|(i, layer_edges)| {
+        let mapped = layer_edges
+          .iter()
+          .map(|(u, v, w)| (positions[i][u], positions[i + 1][v], *w))
+          .collect_vec();
+        _count_crossings(layer_sizes[i + 1], &mapped)
+      })
+      .sum()
+  }
+
+  /// Minimum over all combinations of permutations of all layers
+  fn brute_force(layer_sizes: &[usize], edges: &[MappedEdges]) -> usize {
+    layer_sizes
+      .iter()
+      .map(|size| (0..*size).permutations(*size))
+      .multi_cartesian_product()
+      .map(|orders| crossings(layer_sizes, edges, &orders))
+      .min()
+      .unwrap()
+  }
+
+  #[test]
+  fn test_pair_lower_bound() {
+    // K2,2 always has at least one crossing, a perfect matching can have none
+    assert_eq!(pair_lower_bound(2, 2, &[(0, 0, 1), (0, 1, 1), (1, 0, 1), (1, 1, 2)]), 1);
+    assert_eq!(pair_lower_bound(2, 2, &[(0, 1, 1), (1, 0, 1)]), 0);
+  }
+
+  #[test]
+  fn test_ground_truth() {
+    for layer_sizes in [vec![4, 4], vec![3, 4, 3], vec![3, 3, 3, 2]] {
+      for _ in 0..5 {
+        let edges = mapped_graph(&layer_sizes);
+        let solution = solve_exact(&layer_sizes, &edges, 20, None).unwrap();
+        assert!(solution.optimal);
+        assert_eq!(solution.crossings, crossings(&layer_sizes, &edges, &solution.orders));
+        assert_eq!(solution.crossings, brute_force(&layer_sizes, &edges));
+      }
+    }
+  }
+
+  #[test]
+  fn test_limits() {
+    let layer_sizes = vec![10, 10, 10];
+    let edges = mapped_graph(&layer_sizes);
+    assert!(matches!(
+      solve_exact(&layer_sizes, &edges, 20, None),
+      Err(OptimizerError::TooManyNodes {
+        node_count: 30,
+        max_nodes: 20
+      })
+    ));
+
+    // A zero time limit still returns a valid layout that is no worse than the start
+    let solution = solve_exact(&layer_sizes, &edges, 30, Some(Duration::ZERO)).unwrap();
+    let start = crossings(
+      &layer_sizes,
+      &edges,
+      &layer_sizes.iter().map(|s| (0..*s).collect()).collect_vec(),
+    );
+    assert!(solution.crossings <= start);
+    assert_eq!(solution.crossings, crossings(&layer_sizes, &edges, &solution.orders));
+  }
+}
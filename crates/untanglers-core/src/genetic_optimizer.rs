@@ -0,0 +1,483 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::hash::Hash;
+use std::sync::atomic::Ordering;
+
+use itertools::Itertools;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::config::OptimizeConfig;
+use crate::error::OptimizerError;
+use crate::hierarchy_optimizer::{Hierarchy, HierarchyOptimizer};
+use crate::objective::Objective;
+use crate::optimizer_ops::{impl_optimizer_ops, OptimizerInternalOps, OptimizerOps};
+use crate::stability::StabilityMetric;
+use crate::strategy::{Annealing, LayerStrategy};
+use crate::svg::SvgOptions;
+
+#[derive(Clone, Debug)]
+pub struct GeneticConfig {
+  pub population_size: usize,
+  /// Number of best individuals that are carried over unchanged to the next generation, at least 1 so the best layout
+  /// found is never lost
+  pub elite_count: usize,
+  /// Number of individuals that compete for each parent slot
+  pub tournament_size: usize,
+  /// Probability that a layer of a child is mutated by exchanging two of its nodes or groups
+  pub mutation_rate: f64,
+  /// Annealing applied to every layer of each new individual, if None individuals are only evaluated
+  pub local_search: Option<Annealing>,
+}
+
+impl Default for GeneticConfig {
+  fn default() -> Self {
+    Self {
+      population_size: 20,
+      elite_count: 2,
+      tournament_size: 3,
+      mutation_rate: 0.2,
+      local_search: Some(
+        OptimizeConfig::builder()
+          .start_temp(0.1)
+          .end_temp(0.01)
+          .steps(2)
+          .max_iterations(20)
+          .build()
+          .unwrap()
+          .annealing(),
+      ),
+    }
+  }
+}
+
+impl GeneticConfig {
+  fn validate(&self) -> Result<(), OptimizerError> {
+    if self.elite_count == 0 {
+      return Err(OptimizerError::InvalidConfig {
+        message: "elite_count must be at least 1".to_string(),
+      });
+    }
+    Ok(())
+  }
+}
+
+/// Group membership of the nodes of a layer, which stays fixed while the order changes. Nodes are identified by their
+/// position in the order at the start of the optimisation.
+struct LayerGroups {
+  /// For every level, coarsest first, the group of each node
+  levels: Vec<Vec<usize>>,
+}
+
+impl LayerGroups {
+  fn new(hierarchy: &[Vec<usize>]) -> Self {
+    let levels = hierarchy
+      .iter()
+      .rev()
+      .map(|group_sizes| {
+        group_sizes
+          .iter()
+          .enumerate()
+          .flat_map(|(group, size)| std::iter::repeat_n(group, *size))
+          .collect_vec()
+      })
+      .collect();
+    Self { levels }
+  }
+
+  /// The order of the groups at each level, coarsest first, followed by the order of the nodes themselves.
+  fn sequences(&self, order: &[usize]) -> Vec<Vec<usize>> {
+    let mut sequences = self
+      .levels
+      .iter()
+      .map(|groups| order.iter().map(|node| groups[*node]).dedup().collect_vec())
+      .collect_vec();
+    sequences.push(order.to_vec());
+    sequences
+  }
+
+  /// Inverse of `sequences`: nodes are ordered by their coarsest group first and their own position last, so groups
+  /// stay contiguous for any combination of sequences.
+  fn arrange(&self, sequences: &[Vec<usize>]) -> Vec<usize> {
+    let ranks = sequences
+      .iter()
+      .map(|sequence| {
+        let mut rank = vec![0; sequence.len()];
+        for (position, id) in sequence.iter().enumerate() {
+          rank[*id] = position;
+        }
+        rank
+      })
+      .collect_vec();
+
+    let node_ranks = &ranks[self.levels.len()];
+    (0..node_ranks.len())
+      .sorted_by_cached_key(|node| {
+        self
+          .levels
+          .iter()
+          .zip(&ranks)
+          .map(|(groups, rank)| rank[groups[*node]])
+          .chain([node_ranks[*node]])
+          .collect_vec()
+      })
+      .collect()
+  }
+
+  /// Group names of an order, fine to coarse, given the names of the groups at the start of the optimisation.
+  fn names(&self, order: &[usize], names: &[Vec<String>]) -> Vec<Vec<String>> {
+    let sequences = self.sequences(order);
+    sequences[..self.levels.len()]
+      .iter()
+      .rev()
+      .zip(names)
+      .map(|(sequence, level_names)| sequence.iter().map(|group| level_names[*group].clone()).collect())
+      .collect()
+  }
+
+  /// Group sizes of an order in the format of `Hierarchy`, fine to coarse.
+  fn hierarchy(&self, order: &[usize]) -> Vec<Vec<usize>> {
+    self
+      .levels
+      .iter()
+      .rev()
+      .map(|groups| {
+        order
+          .iter()
+          .chunk_by(|node| groups[**node])
+          .into_iter()
+          .map(|(_, members)| members.count())
+          .collect()
+      })
+      .collect()
+  }
+}
+
+/// Order crossover: a random slice is copied from `parent1`, the remaining items fill the other positions in the order
+/// they have in `parent2`.
+fn order_crossover(rng: &mut impl Rng, parent1: &[usize], parent2: &[usize]) -> Vec<usize> {
+  let n = parent1.len();
+  if n < 2 {
+    return parent1.to_vec();
+  }
+
+  let (mut start, mut end) = (rng.random_range(0..n), rng.random_range(0..n));
+  if start > end {
+    (start, end) = (end, start);
+  }
+
+  let kept: HashSet<usize> = parent1[start..=end].iter().copied().collect();
+  let mut rest = parent2.iter().filter(|id| !kept.contains(id));
+
+  (0..n)
+    .map(|i| match i {
+      i if (start..=end).contains(&i) => parent1[i],
+      _ => *rest.next().unwrap(),
+    })
+    .collect()
+}
+
+#[derive(Clone)]
+struct Individual {
+  orders: Vec<Vec<usize>>,
+  cost: f64,
+}
+
+/**
+ * Memetic algorithm over the orders of all layers at once. Per-layer optimisers only ever change one layer, so they
+ * can get stuck where several layers would have to change together. This keeps a population of complete layouts that
+ * are recombined with an order crossover on every layer, mutated, and improved with a short annealing pass over every
+ * layer. If a hierarchy is given, crossover and mutation act on the order of the groups at every level so groups always
+ * stay contiguous.
+ *
+ * Individuals are ranked by the full cost, see `OptimizerOps::cost`.
+ */
+#[derive(Clone)]
+pub struct GeneticOptimizer<T>
+where
+  T: Eq + Hash + Clone,
+{
+  inner: HierarchyOptimizer<T>,
+  pub config: GeneticConfig,
+}
+
+impl_optimizer_ops!(GeneticOptimizer<T>, inner.optimizer);
+
+impl<T> GeneticOptimizer<T>
+where
+  T: Eq + Hash + Clone,
+{
+  pub fn new(
+    node_layers: Vec<Vec<T>>,
+    edges: Vec<Vec<(T, T, usize)>>,
+    hierarchy: Option<Hierarchy>,
+    config: GeneticConfig,
+  ) -> Result<Self, OptimizerError> {
+    config.validate()?;
+    let hierarchy = hierarchy.unwrap_or_else(|| vec![vec![]; node_layers.len()]);
+    let inner = HierarchyOptimizer::new(node_layers, edges, hierarchy)?;
+    Ok(Self { inner, config })
+  }
+
+  pub fn get_hierarchy(&self) -> Hierarchy {
+    self.inner.get_hierarchy()
+  }
+
+  pub fn to_svg(&self, options: &SvgOptions) -> String
+  where
+    T: Display,
+  {
+    self.inner.to_svg(options)
+  }
+
+  /// See `HierarchyOptimizer::to_snapshot`, the config is not part of the snapshot.
+  pub fn to_snapshot(&self) -> Result<(Vec<Vec<T>>, Vec<u8>), OptimizerError> {
+    self.inner.to_snapshot()
+  }
+
+  pub fn from_snapshot(node_layers: Vec<Vec<T>>, bytes: &[u8], config: GeneticConfig) -> Result<Self, OptimizerError> {
+    config.validate()?;
+    let inner = HierarchyOptimizer::from_snapshot(node_layers, bytes)?;
+    Ok(Self { inner, config })
+  }
+
+  /// Runs the given number of generations starting from the current layout, keeps the best layout found and returns
+  /// its crossing count. The current layout is part of the initial population and the best individuals survive every
+  /// generation, so the cost never increases.
+  pub fn optimize(&mut self, generations: usize) -> Result<usize, OptimizerError> {
+    let mut rng = rand::rng();
+    let config = self.config.clone();
+    let population_size = config.population_size.max(1);
+    let local_search: Vec<Box<dyn LayerStrategy>> = match &config.local_search {
+      Some(annealing) => vec![Box::new(annealing.clone())],
+      None => vec![],
+    };
+
+    let nodes = self.inner.optimizer.node_layers.clone();
+    let indices: Vec<HashMap<usize, usize>> = nodes
+      .iter()
+      .map(|layer| layer.iter().enumerate().map(|(i, node)| (*node, i)).collect())
+      .collect();
+    let groups = self.inner.hierarchy.iter().map(|h| LayerGroups::new(h)).collect_vec();
+    let group_names = self.inner.group_names.clone();
+    let interrupt = self.inner.optimizer.interrupt.clone();
+
+    let mut evaluate = |orders: Vec<Vec<usize>>, improve: bool| -> Result<Individual, OptimizerError> {
+      for (layer_index, order) in orders.iter().enumerate() {
+        self.inner.optimizer.node_layers[layer_index] = order.iter().map(|i| nodes[layer_index][*i]).collect();
+        self.inner.hierarchy[layer_index] = groups[layer_index].hierarchy(order);
+        self.inner.group_names[layer_index] = groups[layer_index].names(order, &group_names[layer_index]);
+      }
+      if improve && !local_search.is_empty() {
+        self.inner.optimize_with(&local_search, 1)?;
+      }
+
+      let orders = self
+        .inner
+        .optimizer
+        .node_layers
+        .iter()
+        .zip(&indices)
+        .map(|(layer, index)| layer.iter().map(|node| index[node]).collect())
+        .collect();
+      Ok(Individual {
+        orders,
+        cost: self.inner.cost()?,
+      })
+    };
+
+    let mut population = vec![evaluate(nodes.iter().map(|l| (0..l.len()).collect()).collect(), false)?];
+    while population.len() < population_size {
+      let orders = groups
+        .iter()
+        .zip(&population[0].orders)
+        .map(|(layer_groups, order)| {
+          let mut sequences = layer_groups.sequences(order);
+          sequences.iter_mut().for_each(|sequence| sequence.shuffle(&mut rng));
+          layer_groups.arrange(&sequences)
+        })
+        .collect();
+      population.push(evaluate(orders, true)?);
+    }
+
+    for _generation in 0..generations {
+      if interrupt
+        .as_ref()
+        .is_some_and(|interrupt| interrupt.load(Ordering::Relaxed))
+      {
+        return Err(OptimizerError::Interrupted);
+      }
+      population.sort_by(|a, b| a.cost.total_cmp(&b.cost));
+      let mut next = population[..config.elite_count.min(population_size)].to_vec();
+
+      while next.len() < population_size {
+        let mut tournament = || {
+          (0..config.tournament_size.max(1))
+            .map(|_| &population[rng.random_range(0..population.len())])
+            .min_by(|a, b| a.cost.total_cmp(&b.cost))
+            .unwrap()
+        };
+        let (parent1, parent2) = (tournament(), tournament());
+
+        let orders = groups
+          .iter()
+          .enumerate()
+          .map(|(layer_index, layer_groups)| {
+            let sequences1 = layer_groups.sequences(&parent1.orders[layer_index]);
+            let sequences2 = layer_groups.sequences(&parent2.orders[layer_index]);
+            let mut sequences = sequences1
+              .iter()
+              .zip(&sequences2)
+              .map(|(s1, s2)| order_crossover(&mut rng, s1, s2))
+              .collect_vec();
+
+            if rng.random::<f64>() < config.mutation_rate {
+              let level = rng.random_range(0..sequences.len());
+              let sequence = &mut sequences[level];
+              if sequence.len() > 1 {
+                let (a, b) = (rng.random_range(0..sequence.len()), rng.random_range(0..sequence.len()));
+                sequence.swap(a, b);
+              }
+            }
+
+            layer_groups.arrange(&sequences)
+          })
+          .collect();
+
+        next.push(evaluate(orders, true)?);
+      }
+
+      population = next;
+    }
+
+    let best = population.into_iter().min_by(|a, b| a.cost.total_cmp(&b.cost)).unwrap();
+    evaluate(best.orders, false)?;
+    Ok(self.count_crossings())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::gen_multi_graph;
+
+  #[test]
+  fn test_order_crossover() {
+    let mut rng = rand::rng();
+    let parent1 = vec![0, 1, 2, 3, 4, 5, 6, 7];
+    let parent2 = vec![7, 6, 5, 4, 3, 2, 1, 0];
+
+    for _ in 0..20 {
+      let child = order_crossover(&mut rng, &parent1, &parent2);
+      assert_eq!(child.iter().sorted().copied().collect_vec(), parent1);
+
+      // Everything outside the copied slice is in reverse order
+      let start = child.iter().zip(&parent1).position(|(c, p)| c == p).unwrap();
+      let end = child.iter().zip(&parent1).rposition(|(c, p)| c == p).unwrap();
+      let rest = child[..start].iter().chain(&child[end + 1..]).collect_vec();
+      assert!(rest.windows(2).all(|w| w[0] > w[1]), "{child:?}");
+    }
+  }
+
+  #[test]
+  fn test_layer_groups() {
+    // Nodes 0..7 in groups [2, 1, 3, 1] at the finest level and [3, 4] at the coarse level
+    let groups = LayerGroups::new(&[vec![2, 1, 3, 1], vec![3, 4]]);
+    let order = vec![3, 4, 5, 6, 1, 0, 2];
+    assert_eq!(
+      groups.sequences(&order),
+      vec![vec![1, 0], vec![2, 3, 0, 1], order.clone()]
+    );
+    assert_eq!(groups.hierarchy(&order), vec![vec![3, 1, 2, 1], vec![4, 3]]);
+    let names = vec![vec!["a", "b", "c", "d"], vec!["x", "y"]]
+      .into_iter()
+      .map(|level| level.into_iter().map(String::from).collect())
+      .collect_vec();
+    assert_eq!(
+      groups.names(&order, &names),
+      vec![vec!["c", "d", "a", "b"], vec!["y", "x"]]
+    );
+
+    // Any combination of sequences keeps the groups contiguous
+    let arranged = groups.arrange(&[vec![0, 1], vec![3, 2, 1, 0], vec![6, 5, 4, 3, 2, 1, 0]]);
+    assert_eq!(arranged, vec![2, 1, 0, 6, 5, 4, 3]);
+    assert_eq!(groups.hierarchy(&arranged), vec![vec![1, 2, 1, 3], vec![3, 4]]);
+  }
+
+  #[test]
+  fn test_optimize() {
+    let (nodes, edges) = gen_multi_graph(4, 30).unwrap();
+    let mut optimizer = GeneticOptimizer::new(nodes, edges, None, GeneticConfig::default()).unwrap();
+    let start_crossings = optimizer.count_crossings();
+
+    let end_crossings = optimizer.optimize(5).unwrap();
+    assert_eq!(end_crossings, optimizer.count_crossings());
+    assert!(end_crossings < start_crossings);
+  }
+
+  #[test]
+  fn test_elitism() {
+    let (nodes, edges) = gen_multi_graph(3, 20).unwrap();
+    let config = GeneticConfig {
+      population_size: 4,
+      elite_count: 0,
+      mutation_rate: 1.,
+      local_search: None,
+      ..Default::default()
+    };
+    assert!(matches!(
+      GeneticOptimizer::new(nodes.clone(), edges.clone(), None, config.clone()),
+      Err(OptimizerError::InvalidConfig { .. })
+    ));
+
+    // Random children are mostly worse, the elite keeps the best layout anyway
+    let config = GeneticConfig {
+      elite_count: 1,
+      ..config
+    };
+    let mut optimizer = GeneticOptimizer::new(nodes, edges, None, config).unwrap();
+    for _ in 0..10 {
+      let cost = optimizer.cost().unwrap();
+      optimizer.optimize(3).unwrap();
+      assert!(optimizer.cost().unwrap() <= cost);
+    }
+  }
+
+  #[test]
+  fn test_optimize_hierarchy() {
+    let hierarchy: Hierarchy = vec![vec![vec![4, 9, 6, 11], vec![13, 17]], vec![], vec![vec![10, 20]]];
+    let (nodes, edges) = gen_multi_graph(3, 30).unwrap();
+    let config = GeneticConfig {
+      population_size: 8,
+      local_search: None,
+      ..Default::default()
+    };
+    let mut optimizer = GeneticOptimizer::new(nodes.clone(), edges, Some(hierarchy.clone()), config).unwrap();
+    let start_cost = optimizer.cost().unwrap();
+    optimizer.optimize(10).unwrap();
+    assert!(optimizer.cost().unwrap() <= start_cost);
+
+    // Every group still holds the same nodes
+    let new_nodes = optimizer.get_nodes();
+    let new_hierarchy = optimizer.get_hierarchy();
+    for layer_index in 0..3 {
+      for (level, group_sizes) in hierarchy[layer_index].iter().enumerate() {
+        let members = |nodes: &[i32], sizes: &[usize]| {
+          let mut start = 0;
+          sizes
+            .iter()
+            .map(|size| {
+              start += size;
+              nodes[start - size..start].iter().copied().sorted().collect_vec()
+            })
+            .sorted()
+            .collect_vec()
+        };
+        assert_eq!(
+          members(&nodes[layer_index], group_sizes),
+          members(&new_nodes[layer_index], &new_hierarchy[layer_index][level])
+        );
+      }
+    }
+  }
+}
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+
+use itertools::Itertools;
+
+use crate::error::OptimizerError;
+use crate::utils::validate_node_uniqueness;
+
+/// For each layer and level, fine to coarse, the name of each group. Has the same shape as `Hierarchy`.
+pub type GroupNames = Vec<Vec<Vec<String>>>;
+
+/// A named group of nodes, as an alternative to giving a hierarchy as group sizes that have to line up with the order
+/// of the nodes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GroupTree<T> {
+  Group { name: String, children: Vec<GroupTree<T>> },
+  Node(T),
+}
+
+/// Group sizes and names of a single layer, fine to coarse, along with the node order they line up with.
+pub struct NamedLayer<T> {
+  pub nodes: Vec<T>,
+  pub hierarchy: Vec<Vec<usize>>,
+  pub names: Vec<Vec<String>>,
+}
+
+/// Names for a hierarchy given as group sizes, the groups at each level are numbered from the start of the layer.
+pub fn default_group_names(layer_hierarchy: &[Vec<usize>]) -> Vec<Vec<String>> {
+  layer_hierarchy
+    .iter()
+    .map(|groups| (0..groups.len()).map(|i| i.to_string()).collect())
+    .collect()
+}
+
+fn flatten<T: Clone>(tree: &[GroupTree<T>], path: &mut Vec<String>, nodes: &mut Vec<(T, Vec<String>)>) {
+  for child in tree {
+    match child {
+      GroupTree::Node(node) => nodes.push((node.clone(), path.clone())),
+      GroupTree::Group { name, children } => {
+        path.push(name.clone());
+        flatten(children, path, nodes);
+        path.pop();
+      }
+    }
+  }
+}
+
+/// The nodes of a tree in the order they appear in, which is the order positions in errors refer to.
+pub fn tree_nodes<T: Clone>(tree: &[GroupTree<T>]) -> Vec<T> {
+  let mut nodes = vec![];
+  flatten(tree, &mut vec![], &mut nodes);
+  nodes.into_iter().map(|(node, _)| node).collect()
+}
+
+/**
+ * Orders the nodes so every group is contiguous and derives the group sizes. Groups are identified by their full path,
+ * so groups with the same name in different parents are distinct. Groups keep the order in which they first appear,
+ * nodes keep their order within their group.
+ *
+ * * `nodes` Each node with its group path, coarse to fine. All paths need the same length.
+ */
+fn named_layer<T>(layer_index: usize, nodes: Vec<(T, Vec<String>)>) -> Result<NamedLayer<T>, OptimizerError>
+where
+  T: Eq + Hash + Clone,
+{
+  // Checked before reordering, so the position refers to the given order
+  validate_node_uniqueness(layer_index, nodes.iter().map(|(node, _)| node))?;
+
+  let depth = nodes.first().map_or(0, |(_, path)| path.len());
+  if let Some((position, (_, path))) = nodes.iter().enumerate().find(|(_, (_, path))| path.len() != depth) {
+    return Err(OptimizerError::GroupDepthMismatch {
+      position,
+      node: None,
+      layer_index,
+      depth: path.len(),
+      expected: depth,
+    });
+  }
+
+  // Rank every prefix of the paths by its first appearance
+  let mut ranks = HashMap::<&[String], usize>::new();
+  for (_, path) in &nodes {
+    for k in 1..=depth {
+      let rank = ranks.len();
+      ranks.entry(&path[..k]).or_insert(rank);
+    }
+  }
+  let keys = nodes
+    .iter()
+    .map(|(_, path)| (1..=depth).map(|k| ranks[&path[..k]]).collect_vec())
+    .collect_vec();
+  let nodes = nodes
+    .iter()
+    .zip(keys)
+    .sorted_by(|(_, key1), (_, key2)| key1.cmp(key2))
+    .map(|(node, _)| node)
+    .collect_vec();
+
+  let (mut hierarchy, mut names) = (vec![], vec![]);
+  for k in (0..depth).rev() {
+    let groups = nodes.iter().chunk_by(|(_, path)| &path[..=k]);
+    let (sizes, level_names): (Vec<usize>, Vec<String>) = groups
+      .into_iter()
+      .map(|(prefix, members)| (members.count(), prefix[k].clone()))
+      .unzip();
+    hierarchy.push(sizes);
+    names.push(level_names);
+  }
+
+  Ok(NamedLayer {
+    nodes: nodes.into_iter().map(|(node, _)| node.clone()).collect(),
+    hierarchy,
+    names,
+  })
+}
+
+/// Derives the node order, group sizes and names of a layer from its groups. All nodes need to be at the same depth.
+pub fn layer_from_tree<T>(layer_index: usize, tree: &[GroupTree<T>]) -> Result<NamedLayer<T>, OptimizerError>
+where
+  T: Eq + Hash + Clone,
+{
+  let mut nodes = vec![];
+  flatten(tree, &mut vec![], &mut nodes);
+  named_layer(layer_index, nodes)
+}
+
+/// Derives the node order, group sizes and names of a layer from the group path of every node, coarse to fine.
+pub fn layer_from_paths<T>(
+  layer_index: usize,
+  nodes: &[T],
+  paths: &HashMap<T, Vec<String>>,
+) -> Result<NamedLayer<T>, OptimizerError>
+where
+  T: Eq + Hash + Clone,
+{
+  let nodes = nodes
+    .iter()
+    .enumerate()
+    .map(|(position, node)| match paths.get(node) {
+      Some(path) => Ok((node.clone(), path.clone())),
+      None => Err(OptimizerError::MissingGroupPath {
+        position,
+        node: None,
+        layer_index,
+      }),
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+  named_layer(layer_index, nodes)
+}
+
+fn subtree<T: Clone>(
+  nodes: &[T],
+  layer_hierarchy: &[Vec<usize>],
+  names: &[Vec<String>],
+  level_count: usize,
+  range: Range<usize>,
+) -> Vec<GroupTree<T>> {
+  if level_count == 0 {
+    return nodes[range].iter().cloned().map(GroupTree::Node).collect();
+  }
+
+  let level = level_count - 1;
+  let mut start = 0;
+  let mut tree = vec![];
+  for (size, name) in layer_hierarchy[level].iter().zip(&names[level]) {
+    let group = start..start + size;
+    start += size;
+    if group.start >= range.start && group.end <= range.end {
+      tree.push(GroupTree::Group {
+        name: name.clone(),
+        children: subtree(nodes, layer_hierarchy, names, level, group),
+      });
+    }
+  }
+  tree
+}
+
+/// Inverse of `layer_from_tree`: the groups of a layer as a tree, in the current order.
+pub fn layer_to_tree<T: Clone>(
+  nodes: &[T],
+  layer_hierarchy: &[Vec<usize>],
+  names: &[Vec<String>],
+) -> Vec<GroupTree<T>> {
+  subtree(nodes, layer_hierarchy, names, layer_hierarchy.len(), 0..nodes.len())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn group<T>(name: &str, children: Vec<GroupTree<T>>) -> GroupTree<T> {
+    GroupTree::Group {
+      name: name.to_string(),
+      children,
+    }
+  }
+
+  fn nodes(ids: &[i32]) -> Vec<GroupTree<i32>> {
+    ids.iter().map(|id| GroupTree::Node(*id)).collect()
+  }
+
+  #[test]
+  fn test_tree() {
+    let tree = vec![
+      group("db1", vec![group("a", nodes(&[1, 2])), group("b", nodes(&[3]))]),
+      group("db2", vec![group("a", nodes(&[4, 5, 6]))]),
+    ];
+    let layer = layer_from_tree(0, &tree).unwrap();
+    assert_eq!(layer.nodes, vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(layer.hierarchy, vec![vec![2, 1, 3], vec![3, 3]]);
+    assert_eq!(layer.names, vec![vec!["a", "b", "a"], vec!["db1", "db2"]]);
+    assert_eq!(layer_to_tree(&layer.nodes, &layer.hierarchy, &layer.names), tree);
+
+    let tree = vec![group("db1", vec![group("a", nodes(&[1]))]), group("db2", nodes(&[2]))];
+    assert!(matches!(
+      layer_from_tree(3, &tree),
+      Err(OptimizerError::GroupDepthMismatch {
+        node: None,
+        position: 1,
+        layer_index: 3,
+        depth: 1,
+        expected: 2,
+      })
+    ));
+
+    let layer = layer_from_tree(0, &nodes(&[3, 1, 2])).unwrap();
+    assert_eq!(layer.nodes, vec![3, 1, 2]);
+    assert!(layer.hierarchy.is_empty());
+  }
+
+  #[test]
+  fn test_paths() {
+    let path = |p: &[&str]| p.iter().map(|s| s.to_string()).collect_vec();
+    let paths = HashMap::from([
+      (1, path(&["db1", "a"])),
+      (2, path(&["db2", "a"])),
+      (3, path(&["db1", "b"])),
+      (4, path(&["db1", "a"])),
+      (5, path(&["db2", "a"])),
+    ]);
+
+    // Groups are ordered by first appearance, nodes keep their order within a group
+    let layer = layer_from_paths(0, &[5, 3, 1, 2, 4], &paths).unwrap();
+    assert_eq!(layer.nodes, vec![5, 2, 3, 1, 4]);
+    assert_eq!(layer.hierarchy, vec![vec![2, 1, 2], vec![2, 3]]);
+    assert_eq!(layer.names, vec![vec!["a", "b", "a"], vec!["db2", "db1"]]);
+
+    assert!(matches!(
+      layer_from_paths(0, &[1, 6], &paths),
+      Err(OptimizerError::MissingGroupPath {
+        node: None,
+        position: 1,
+        layer_index: 0
+      })
+    ));
+
+    // Duplicates are reported at their first position in the given order, not the grouped one
+    assert!(matches!(
+      layer_from_paths(0, &[5, 3, 5], &paths),
+      Err(OptimizerError::DuplicateNode { position: 0, .. })
+    ));
+  }
+}
@@ -1,4 +1,3 @@
-use std::fmt::{Debug, Display};
 use std::hash::Hash;
 
 use itertools::Itertools;
@@ -7,7 +6,7 @@ use crate::error::OptimizerError;
 
 pub fn reorder_node_groups<T>(nodes: &[T], group_sizes: &[usize], new_indices: &[usize]) -> Vec<T>
 where
-  T: Eq + Hash + Clone + Display + Debug,
+  T: Eq + Hash + Clone,
 {
   let mut new_nodes = Vec::<T>::with_capacity(nodes.len());
 
@@ -20,8 +19,10 @@ where
   new_nodes
 }
 
-pub fn reorder_group(parent_groups: &[usize], groups: &[usize], new_order: &[usize]) -> Vec<usize> {
-  let mut new_groups = Vec::<usize>::with_capacity(groups.len());
+/// Indices of `groups` in their new order when the parent groups are put in `new_order`, every group moves along with
+/// the parent that contains it.
+pub fn reorder_group_indices(parent_groups: &[usize], groups: &[usize], new_order: &[usize]) -> Vec<usize> {
+  let mut new_indices = Vec::<usize>::with_capacity(groups.len());
 
   for parent_index in new_order {
     let parent_start: usize = parent_groups[0..*parent_index].iter().sum();
@@ -30,30 +31,49 @@ pub fn reorder_group(parent_groups: &[usize], groups: &[usize], new_order: &[usi
     for i in 0..groups.len() {
       let group_start: usize = groups[0..i].iter().sum();
       if group_start >= parent_start && group_start < parent_end {
-        new_groups.push(groups[i]);
+        new_indices.push(i);
       }
     }
   }
 
-  new_groups
+  new_indices
 }
 
-pub fn reorder_hierarchy(hierarchy: &[Vec<usize>], granularity: usize, new_order: &[usize]) -> Vec<Vec<usize>> {
+pub fn reorder_group(parent_groups: &[usize], groups: &[usize], new_order: &[usize]) -> Vec<usize> {
+  reorder_group_indices(parent_groups, groups, new_order)
+    .iter()
+    .map(|i| groups[*i])
+    .collect_vec()
+}
+
+/// For each level the new order of its groups as indices into the old order, when the groups at `granularity` are put
+/// in `new_order`. Coarser levels keep their order.
+pub fn hierarchy_permutation(hierarchy: &[Vec<usize>], granularity: usize, new_order: &[usize]) -> Vec<Vec<usize>> {
   // group_sizes_layers should be in order fine -> coarse
-  let layer_count = hierarchy.len();
-  let mut new_hierarchy = Vec::<Vec<usize>>::with_capacity(layer_count);
-
-  for l in 0..layer_count {
-    if l > granularity {
-      new_hierarchy.push(hierarchy[l].clone());
-    } else if l == granularity {
-      new_hierarchy.push(new_order.iter().map(|i| hierarchy[l][*i]).collect_vec());
-    } else {
-      new_hierarchy.push(reorder_group(&hierarchy[granularity], &hierarchy[l], new_order));
-    }
-  }
+  (0..hierarchy.len())
+    .map(|l| {
+      if l > granularity {
+        (0..hierarchy[l].len()).collect_vec()
+      } else if l == granularity {
+        new_order.to_vec()
+      } else {
+        reorder_group_indices(&hierarchy[granularity], &hierarchy[l], new_order)
+      }
+    })
+    .collect_vec()
+}
 
-  new_hierarchy
+/// Applies a permutation from `hierarchy_permutation` to anything stored per group, e.g. sizes or names.
+pub fn permute_levels<L: Clone>(levels: &[Vec<L>], permutation: &[Vec<usize>]) -> Vec<Vec<L>> {
+  levels
+    .iter()
+    .zip(permutation)
+    .map(|(level, indices)| indices.iter().map(|i| level[*i].clone()).collect_vec())
+    .collect_vec()
+}
+
+pub fn reorder_hierarchy(hierarchy: &[Vec<usize>], granularity: usize, new_order: &[usize]) -> Vec<Vec<usize>> {
+  permute_levels(hierarchy, &hierarchy_permutation(hierarchy, granularity, new_order))
 }
 
 pub fn get_borders(child_groups: &[usize], parent_groups: &[usize]) -> Vec<usize> {
@@ -120,6 +140,46 @@ pub fn groups_and_borders(
   }
 }
 
+/// Index of the group that contains the node at `position`.
+pub fn group_at(groups: &[usize], position: usize) -> usize {
+  let mut group_end = 0;
+  groups
+    .iter()
+    .position(|group_size| {
+      group_end += group_size;
+      group_end > position
+    })
+    .expect("Position out of range, did you validate the hierarchy?")
+}
+
+/// Grows the finest group `group_index` by one node, along with every coarser group that contains it.
+/// Returns the position directly after the group, which is where the new node belongs.
+pub fn grow_group(hierarchy: &mut [Vec<usize>], group_index: usize) -> usize {
+  let position = hierarchy[0][..=group_index].iter().sum();
+
+  for groups in hierarchy.iter_mut() {
+    let group_index = group_at(groups, position - 1);
+    groups[group_index] += 1;
+  }
+
+  position
+}
+
+/// Shrinks every group that contains the node at `position`, groups that end up empty are dropped. Returns the level
+/// and index of every dropped group.
+pub fn shrink_groups(hierarchy: &mut [Vec<usize>], position: usize) -> Vec<(usize, usize)> {
+  let mut dropped = vec![];
+  for (level, groups) in hierarchy.iter_mut().enumerate() {
+    let group_index = group_at(groups, position);
+    groups[group_index] -= 1;
+    if groups[group_index] == 0 {
+      groups.remove(group_index);
+      dropped.push((level, group_index));
+    }
+  }
+  dropped
+}
+
 pub fn validate_hierarchy(
   layer_index: usize,
   node_count: usize,
@@ -202,6 +262,9 @@ mod tests {
     let new_order: Vec<usize> = vec![1, 3, 0, 2];
     let new_child_groups = reorder_group(&parent_groups, &child_groups, &new_order);
     assert_eq!(new_child_groups, vec![3, 3, 14, 15, 10, 13, 7, 20, 15]);
+
+    let new_indices = reorder_group_indices(&parent_groups, &child_groups, &new_order);
+    assert_eq!(new_indices, vec![3, 4, 5, 8, 0, 1, 2, 6, 7]);
   }
 
   #[test]
@@ -250,6 +313,38 @@ mod tests {
         vec![50, 50],
       ]
     );
+
+    // Anything stored per group moves along with the sizes
+    let names = group_layers
+      .iter()
+      .map(|groups| (0..groups.len()).map(|i| format!("{i}")).collect_vec())
+      .collect_vec();
+    let permutation = hierarchy_permutation(&group_layers, 1, &new_order);
+    assert_eq!(
+      permute_levels(&names, &permutation),
+      vec![
+        vec!["3", "4", "5", "8", "0", "1", "2", "6", "7"],
+        vec!["1", "3", "0", "2"],
+        vec!["0", "1"],
+      ]
+    );
+  }
+
+  #[test]
+  fn test_grow_and_shrink_groups() {
+    let mut hierarchy: Vec<Vec<usize>> = vec![vec![2, 1, 3], vec![3, 3]];
+
+    assert_eq!(grow_group(&mut hierarchy, 1), 3);
+    assert_eq!(hierarchy, vec![vec![2, 2, 3], vec![4, 3]]);
+    assert!(validate_hierarchy(0, 7, &hierarchy).is_ok());
+
+    assert_eq!(shrink_groups(&mut hierarchy, 6), vec![]);
+    assert_eq!(hierarchy, vec![vec![2, 2, 2], vec![4, 2]]);
+
+    shrink_groups(&mut hierarchy, 0);
+    assert_eq!(shrink_groups(&mut hierarchy, 0), vec![(0, 0)]);
+    assert_eq!(hierarchy, vec![vec![2, 2], vec![2, 2]]);
+    assert!(validate_hierarchy(0, 4, &hierarchy).is_ok());
   }
 
   #[test]
@@ -1,29 +1,52 @@
-use std::fmt::{Debug, Display};
+use std::collections::HashMap;
+use std::fmt::Display;
 use std::hash::Hash;
 
+use itertools::Itertools;
+
+use crate::aggregation::aggregate_edges;
+use crate::compound::{align_layer, update_compound_order, validate_compound_groups, CompoundGroup};
+use crate::config::OptimizeConfig;
+use crate::count_crossings::{_count_crossings, crossings_per_node};
 use crate::error::OptimizerError;
-use crate::hierarchy::{groups_and_borders, reorder_hierarchy, reorder_node_groups, validate_hierarchy};
+use crate::group_tree::{
+  default_group_names, layer_from_paths, layer_from_tree, layer_to_tree, GroupNames, GroupTree, NamedLayer,
+};
+use crate::hierarchy::{
+  group_at, groups_and_borders, grow_group, hierarchy_permutation, permute_levels, reorder_node_groups, shrink_groups,
+  validate_hierarchy,
+};
+use crate::local_search::MAX_POLISH_SWEEPS;
 use crate::mapping::reorder_nodes;
+use crate::objective::Objective;
 use crate::optimizer::Optimizer;
 use crate::optimizer_ops::{impl_optimizer_ops, OptimizerInternalOps, OptimizerOps};
-use crate::reducer::reduce_crossings;
-use crate::utils::{validate_edge_uniqueness, validate_layers};
+use crate::snapshot::{optimizer_snapshot, restore_parts, Snapshot};
+use crate::stability::StabilityMetric;
+use crate::strategy::{GreedySwitch, LayerStrategy, MappedEdges, Tabu};
+use crate::svg::{render_svg, SvgOptions};
+use crate::utils::{validate_edge_uniqueness, validate_layers, validate_permutation};
 
 pub type Hierarchy = Vec<Vec<Vec<usize>>>;
 
+#[derive(Clone)]
 pub struct HierarchyOptimizer<T>
 where
-  T: Eq + Hash + Clone + Display + Debug,
+  T: Eq + Hash + Clone,
 {
-  optimizer: Optimizer<T>,
-  hierarchy: Hierarchy,
+  pub(crate) optimizer: Optimizer<T>,
+  pub(crate) hierarchy: Hierarchy,
+  pub(crate) group_names: GroupNames,
+  pub(crate) compound_groups: Vec<CompoundGroup>,
+  /// Indices into `compound_groups` from top to bottom
+  pub(crate) compound_order: Vec<usize>,
 }
 
 impl_optimizer_ops!(HierarchyOptimizer<T>);
 
 impl<T> HierarchyOptimizer<T>
 where
-  T: Eq + Hash + Clone + Display + Debug,
+  T: Eq + Hash + Clone,
 {
   pub fn new(
     node_layers: Vec<Vec<T>>,
@@ -45,7 +68,81 @@ where
     validate_edge_uniqueness(&edges)?;
 
     let optimizer = Optimizer::new(node_layers, edges);
-    Ok(Self { optimizer, hierarchy })
+    let group_names = hierarchy.iter().map(|h| default_group_names(h)).collect();
+    Ok(Self {
+      optimizer,
+      hierarchy,
+      group_names,
+      compound_groups: vec![],
+      compound_order: vec![],
+    })
+  }
+
+  /// Like `new`, with the nodes and hierarchy of every layer given as a tree of named groups. The initial order of the
+  /// nodes follows the tree.
+  pub fn from_group_tree(
+    trees: Vec<Vec<GroupTree<T>>>,
+    edges: Vec<Vec<(T, T, usize)>>,
+  ) -> Result<Self, OptimizerError> {
+    let layers = trees
+      .iter()
+      .enumerate()
+      .map(|(layer_index, tree)| layer_from_tree(layer_index, tree))
+      .collect::<Result<Vec<_>, _>>()?;
+    Self::from_named_layers(layers, edges)
+  }
+
+  /// Like `new`, with the hierarchy given as the group path of every node, coarse to fine. Nodes are reordered so each
+  /// group is contiguous, groups keep the order in which they first appear.
+  pub fn from_group_paths(
+    node_layers: Vec<Vec<T>>,
+    edges: Vec<Vec<(T, T, usize)>>,
+    paths: Vec<HashMap<T, Vec<String>>>,
+  ) -> Result<Self, OptimizerError> {
+    if paths.len() != node_layers.len() {
+      return Err(OptimizerError::HierarchyMismatch {
+        hierarchy: paths.len(),
+        layers: node_layers.len(),
+      });
+    }
+
+    let layers = node_layers
+      .iter()
+      .zip(&paths)
+      .enumerate()
+      .map(|(layer_index, (nodes, layer_paths))| layer_from_paths(layer_index, nodes, layer_paths))
+      .collect::<Result<Vec<_>, _>>()?;
+    Self::from_named_layers(layers, edges)
+  }
+
+  fn from_named_layers(layers: Vec<NamedLayer<T>>, edges: Vec<Vec<(T, T, usize)>>) -> Result<Self, OptimizerError> {
+    let (mut node_layers, mut hierarchy, mut group_names) = (vec![], vec![], vec![]);
+    for layer in layers {
+      node_layers.push(layer.nodes);
+      hierarchy.push(layer.hierarchy);
+      group_names.push(layer.names);
+    }
+
+    let mut hierarchy_optimizer = Self::new(node_layers, edges, hierarchy)?;
+    hierarchy_optimizer.group_names = group_names;
+    Ok(hierarchy_optimizer)
+  }
+
+  /// Like `new`, with additional edges between nodes in the same layer, one list per layer.
+  pub fn with_intra_edges(
+    node_layers: Vec<Vec<T>>,
+    edges: Vec<Vec<(T, T, usize)>>,
+    hierarchy: Hierarchy,
+    intra_edges: Vec<Vec<(T, T, usize)>>,
+  ) -> Result<Self, OptimizerError> {
+    let mut hierarchy_optimizer = Self::new(node_layers, edges, hierarchy)?;
+    hierarchy_optimizer.set_intra_edges(intra_edges)?;
+    Ok(hierarchy_optimizer)
+  }
+
+  /// Replaces the edges between nodes in the same layer, one list per layer, e.g. after `from_group_paths`.
+  pub fn set_intra_edges(&mut self, intra_edges: Vec<Vec<(T, T, usize)>>) -> Result<(), OptimizerError> {
+    self.optimizer.set_intra_edges(intra_edges)
   }
 
   pub fn swap_nodes(
@@ -55,98 +152,185 @@ where
     layer_index: usize,
     granularity: Option<usize>,
   ) -> Result<usize, OptimizerError> {
-    let (nodes1, edges1, nodes2, edges2) = self.get_adjacent_layers(layer_index)?;
-    let (groups, borders) = groups_and_borders(&self.hierarchy[layer_index], granularity);
+    let config = OptimizeConfig::builder()
+      .temperature(temperature)
+      .steps(1)
+      .max_iterations(max_iterations)
+      .build()?;
+    self.apply_strategy(&config.annealing(), layer_index, granularity)
+  }
 
-    let (new_indices, new_count) = reduce_crossings(
-      &self.optimizer.node_layers[layer_index],
-      nodes1,
-      edges1,
-      nodes2,
-      edges2,
-      max_iterations,
-      temperature,
-      temperature,
-      1,
-      groups.clone(),
-      borders,
-    );
+  /// Anneals a single layer once, `config.passes()` is not used.
+  pub fn cooldown(
+    &mut self,
+    config: &OptimizeConfig,
+    layer_index: usize,
+    granularity: Option<usize>,
+  ) -> Result<usize, OptimizerError> {
+    self.apply_strategy(&config.annealing(), layer_index, granularity)
+  }
+
+  /// Reorders a single layer with tabu search, which needs no temperature. See `local_search::tabu_search`.
+  pub fn tabu_search(
+    &mut self,
+    tenure: usize,
+    max_iterations: usize,
+    layer_index: usize,
+    granularity: Option<usize>,
+  ) -> Result<usize, OptimizerError> {
+    self.apply_strategy(&Tabu { tenure, max_iterations }, layer_index, granularity)
+  }
+
+  /// Reorders a single layer with the given strategy, returns the crossings of the layer afterwards.
+  ///
+  /// * `granularity` The hierarchy level whose groups are moved as a whole, if None individual nodes are moved within
+  ///   their finest group
+  pub fn apply_strategy(
+    &mut self,
+    strategy: &dyn LayerStrategy,
+    layer_index: usize,
+    granularity: Option<usize>,
+  ) -> Result<usize, OptimizerError> {
+    if layer_index >= self.hierarchy.len() {
+      return Err(OptimizerError::InvalidLayer {
+        layer_index,
+        layer_count: self.hierarchy.len(),
+      });
+    }
+
+    let level_count = self.hierarchy[layer_index].len();
+    if let Some(granularity) = granularity.filter(|g| *g >= level_count) {
+      return Err(OptimizerError::InvalidGranularity {
+        layer_index,
+        granularity,
+        level_count,
+      });
+    }
+
+    let (groups, borders) = groups_and_borders(&self.hierarchy[layer_index], granularity);
+    let problem = self.optimizer.layer_problem(layer_index, groups, borders)?;
+    let new_indices = strategy.reorder(&problem);
+    validate_permutation(&new_indices, problem.unit_count())?;
 
     match granularity {
       None => {
         self.optimizer.node_layers[layer_index] = reorder_nodes(&self.optimizer.node_layers[layer_index], &new_indices)
       }
       Some(granularity) => {
-        self.optimizer.node_layers[layer_index] =
-          reorder_node_groups(&self.optimizer.node_layers[layer_index], &groups.unwrap(), &new_indices);
-        self.hierarchy[layer_index] = reorder_hierarchy(&self.hierarchy[layer_index], granularity, &new_indices);
+        self.reorder_groups(layer_index, granularity, &new_indices);
+        if granularity + 1 == level_count && !self.compound_groups.is_empty() {
+          self.align_compound_groups(layer_index);
+        }
       }
     }
+    self.optimizer.check_interrupt()?;
 
-    Ok(new_count as usize)
+    self.count_layer_crossings(layer_index)
   }
 
-  pub fn cooldown(
-    &mut self,
-    start_temp: f64,
-    end_temp: f64,
-    steps: usize,
-    max_iterations: usize,
-    layer_index: usize,
-    granularity: Option<usize>,
-  ) -> Result<usize, OptimizerError> {
-    let (nodes1, edges1, nodes2, edges2) = self.get_adjacent_layers(layer_index)?;
-    let (groups, borders) = groups_and_borders(&self.hierarchy[layer_index], granularity);
-
-    let (new_indices, new_count) = reduce_crossings(
+  /// Moves the groups at `granularity` of a layer into a new order, along with their nodes, subgroups and names.
+  fn reorder_groups(&mut self, layer_index: usize, granularity: usize, new_indices: &[usize]) {
+    let layer_hierarchy = &self.hierarchy[layer_index];
+    self.optimizer.node_layers[layer_index] = reorder_node_groups(
       &self.optimizer.node_layers[layer_index],
-      nodes1,
-      edges1,
-      nodes2,
-      edges2,
-      max_iterations,
-      start_temp,
-      end_temp,
-      steps,
-      groups.clone(),
-      borders,
+      &layer_hierarchy[granularity],
+      new_indices,
     );
+    let permutation = hierarchy_permutation(layer_hierarchy, granularity, new_indices);
+    self.hierarchy[layer_index] = permute_levels(&self.hierarchy[layer_index], &permutation);
+    self.group_names[layer_index] = permute_levels(&self.group_names[layer_index], &permutation);
+  }
 
-    match granularity {
-      None => {
-        self.optimizer.node_layers[layer_index] = reorder_nodes(&self.optimizer.node_layers[layer_index], &new_indices)
+  /// Groups that span consecutive layers and keep the same relative order in every layer they cover, replacing any
+  /// previously set. Each needs a coarsest group with its name in every layer it covers and the layers have to agree
+  /// on their order, see `compound::validate_compound_groups`. Reordering the coarsest groups of a layer moves the
+  /// compound groups in all other layers along.
+  pub fn set_compound_groups(&mut self, compound_groups: Vec<CompoundGroup>) -> Result<(), OptimizerError> {
+    self.compound_order = validate_compound_groups(&compound_groups, &self.group_names)?;
+    self.compound_groups = compound_groups;
+    Ok(())
+  }
+
+  /// Names of the compound groups from top to bottom.
+  pub fn get_compound_order(&self) -> Vec<String> {
+    self
+      .compound_order
+      .iter()
+      .map(|group| self.compound_groups[*group].name.clone())
+      .collect()
+  }
+
+  /// Takes the global order of the compound groups from `source_layer`, which was just reordered, and puts the compound
+  /// groups of every other layer in that order.
+  fn align_compound_groups(&mut self, source_layer: usize) {
+    self.compound_order = update_compound_order(
+      &self.compound_order,
+      &self.compound_groups,
+      &self.group_names,
+      source_layer,
+    );
+
+    for layer_index in 0..self.hierarchy.len() {
+      if layer_index == source_layer || !self.compound_groups.iter().any(|group| group.covers(layer_index)) {
+        continue;
       }
-      Some(granularity) => {
-        self.optimizer.node_layers[layer_index] =
-          reorder_node_groups(&self.optimizer.node_layers[layer_index], &groups.unwrap(), &new_indices);
-        self.hierarchy[layer_index] = reorder_hierarchy(&self.hierarchy[layer_index], granularity, &new_indices);
+
+      let new_indices = align_layer(
+        &self.compound_order,
+        &self.compound_groups,
+        &self.group_names,
+        layer_index,
+      );
+      if new_indices.iter().enumerate().any(|(i, index)| i != *index) {
+        self.reorder_groups(layer_index, self.hierarchy[layer_index].len() - 1, &new_indices);
       }
     }
+  }
 
-    Ok(new_count as usize)
+  pub fn optimize(&mut self, config: &OptimizeConfig) -> Result<usize, OptimizerError> {
+    let annealing: Box<dyn LayerStrategy> = Box::new(config.annealing());
+    self.optimize_with(&[annealing], config.passes())
   }
 
-  pub fn optimize(
+  /// Each pass sweeps over all layers once for every strategy, in the given order. Within a layer the strategy is
+  /// applied at every level of the hierarchy from fine to coarse, and finally to the individual nodes.
+  pub fn optimize_with(
     &mut self,
-    start_temp: f64,
-    end_temp: f64,
-    steps: usize,
-    max_iterations: usize,
+    strategies: &[Box<dyn LayerStrategy>],
     passes: usize,
   ) -> Result<usize, OptimizerError> {
     for _pass in 0..passes {
+      for strategy in strategies {
+        for layer_index in 0..self.optimizer.node_layers.len() {
+          for granularity in 0..self.hierarchy[layer_index].len() {
+            self.apply_strategy(strategy.as_ref(), layer_index, Some(granularity))?;
+          }
+          self.apply_strategy(strategy.as_ref(), layer_index, None)?;
+        }
+      }
+    }
+
+    Ok(self.count_crossings())
+  }
+
+  /// Deterministic final polish, usually after `optimize`: applies `GreedySwitch` to every layer at every level of the
+  /// hierarchy until a full sweep no longer changes anything. Groups are kept intact.
+  pub fn polish(&mut self, two_opt: bool, block_reversal: bool) -> Result<usize, OptimizerError> {
+    let greedy = GreedySwitch {
+      two_opt,
+      block_reversal,
+    };
+
+    for _sweep in 0..MAX_POLISH_SWEEPS {
+      let node_layers = self.optimizer.node_layers.clone();
       for layer_index in 0..self.optimizer.node_layers.len() {
         for granularity in 0..self.hierarchy[layer_index].len() {
-          self.cooldown(
-            start_temp,
-            end_temp,
-            steps,
-            max_iterations,
-            layer_index,
-            Some(granularity),
-          )?;
+          self.apply_strategy(&greedy, layer_index, Some(granularity))?;
         }
-        self.cooldown(start_temp, end_temp, steps, max_iterations, layer_index, None)?;
+        self.apply_strategy(&greedy, layer_index, None)?;
+      }
+      if self.optimizer.node_layers == node_layers {
+        break;
       }
     }
 
@@ -156,6 +340,283 @@ where
   pub fn get_hierarchy(&self) -> Hierarchy {
     self.hierarchy.clone()
   }
+
+  /// Names of the groups in the same shape as `get_hierarchy`. Groups of a hierarchy given as sizes are numbered from
+  /// the start of the layer at construction.
+  pub fn get_group_names(&self) -> GroupNames {
+    self.group_names.clone()
+  }
+
+  /// Names of the groups at one level of a layer in their current order.
+  ///
+  /// * `granularity` The hierarchy level, 0 being the finest
+  pub fn get_group_order(&self, layer_index: usize, granularity: usize) -> Result<Vec<String>, OptimizerError> {
+    let layer_names = self.group_names.get(layer_index).ok_or(OptimizerError::InvalidLayer {
+      layer_index,
+      layer_count: self.group_names.len(),
+    })?;
+
+    layer_names
+      .get(granularity)
+      .cloned()
+      .ok_or(OptimizerError::InvalidGranularity {
+        layer_index,
+        granularity,
+        level_count: layer_names.len(),
+      })
+  }
+
+  /// The nodes and groups of a layer in their current order, see `from_group_tree`.
+  pub fn get_group_tree(&self, layer_index: usize) -> Result<Vec<GroupTree<T>>, OptimizerError> {
+    if layer_index >= self.hierarchy.len() {
+      return Err(OptimizerError::InvalidLayer {
+        layer_index,
+        layer_count: self.hierarchy.len(),
+      });
+    }
+
+    Ok(layer_to_tree(
+      &self.optimizer.layer_nodes(layer_index),
+      &self.hierarchy[layer_index],
+      &self.group_names[layer_index],
+    ))
+  }
+
+  /// The group of every node of a layer by position at the given level, a layer without a hierarchy counts every node
+  /// as its own group. Fails if the layer has a hierarchy without that level.
+  fn layer_groups(&self, layer_index: usize, granularity: usize) -> Result<Vec<usize>, OptimizerError> {
+    let layer_hierarchy = &self.hierarchy[layer_index];
+    if layer_hierarchy.is_empty() {
+      return Ok((0..self.optimizer.node_layers[layer_index].len()).collect());
+    }
+
+    let group_sizes = layer_hierarchy
+      .get(granularity)
+      .ok_or(OptimizerError::InvalidGranularity {
+        layer_index,
+        granularity,
+        level_count: layer_hierarchy.len(),
+      })?;
+    Ok(
+      group_sizes
+        .iter()
+        .enumerate()
+        .flat_map(|(group_index, size)| std::iter::repeat_n(group_index, *size))
+        .collect(),
+    )
+  }
+
+  /// The number of groups of every layer, see `layer_groups`, and the aggregated edges between the groups of each pair
+  /// of neighbouring layers.
+  fn group_graph(&self, granularity: usize) -> Result<(Vec<usize>, Vec<MappedEdges>), OptimizerError> {
+    let groups = (0..self.hierarchy.len())
+      .map(|layer_index| self.layer_groups(layer_index, granularity))
+      .collect::<Result<Vec<_>, _>>()?;
+    let edges = (0..self.optimizer.edges.len())
+      .map(|i| aggregate_edges(&self.optimizer.mapped_edges(i), &groups[i], &groups[i + 1]))
+      .collect();
+    let group_counts = groups
+      .iter()
+      .map(|layer_groups| layer_groups.last().map_or(0, |group| group + 1))
+      .collect();
+    Ok((group_counts, edges))
+  }
+
+  /// Crossings between the bundles of edges that connect groups, with every bundle counted as a single edge weighted
+  /// by the sum of its weights. Edges within a layer are not counted.
+  ///
+  /// * `granularity` The hierarchy level of the groups, every layer with a hierarchy needs it, see `layer_groups`
+  pub fn count_group_crossings(&self, granularity: usize) -> Result<usize, OptimizerError> {
+    let (group_counts, edges) = self.group_graph(granularity)?;
+    Ok(
+      edges
+        .iter()
+        .enumerate()
+        .map(|(i, layer_edges)| _count_crossings(group_counts[i + 1], layer_edges))
+        .sum(),
+    )
+  }
+
+  /// For every layer the groups in their current order with the bundle crossings their edges are involved in, see
+  /// `count_group_crossings`. A crossing counts towards all four groups it connects. The nodes of a layer without a
+  /// hierarchy are named by their `Display`.
+  pub fn group_crossings(&self, granularity: usize) -> Result<Vec<Vec<(String, usize)>>, OptimizerError>
+  where
+    T: Display,
+  {
+    let (group_counts, edges) = self.group_graph(granularity)?;
+    let mut totals = group_counts.iter().map(|count| vec![0; *count]).collect_vec();
+    for (i, layer_edges) in edges.iter().enumerate() {
+      let (totals1, totals2) = crossings_per_node(group_counts[i], group_counts[i + 1], layer_edges);
+      for (total, count) in totals[i].iter_mut().zip(totals1) {
+        *total += count;
+      }
+      for (total, count) in totals[i + 1].iter_mut().zip(totals2) {
+        *total += count;
+      }
+    }
+
+    let totals = totals
+      .into_iter()
+      .enumerate()
+      .map(|(layer_index, layer_totals)| {
+        let layer_hierarchy = &self.hierarchy[layer_index];
+        let names = if layer_hierarchy.is_empty() {
+          self
+            .optimizer
+            .layer_nodes(layer_index)
+            .iter()
+            .map(|node| node.to_string())
+            .collect()
+        } else {
+          self.group_names[layer_index][granularity].clone()
+        };
+        names.into_iter().zip(layer_totals).collect()
+      })
+      .collect();
+    Ok(totals)
+  }
+
+  /// Adds a node without edges to a layer.
+  ///
+  /// * `group_index` The finest group to append the node to, every coarser group containing it grows along with it.
+  ///   If None the node is appended to the end of the layer in a new group at every level, named after its index like
+  ///   the groups of a hierarchy given as sizes, or the next free number if another group at that level has the name.
+  pub fn add_node(&mut self, layer_index: usize, node: T, group_index: Option<usize>) -> Result<(), OptimizerError> {
+    if layer_index >= self.hierarchy.len() {
+      return Err(OptimizerError::InvalidLayer {
+        layer_index,
+        layer_count: self.hierarchy.len(),
+      });
+    }
+
+    let layer_hierarchy = &mut self.hierarchy[layer_index];
+    match group_index {
+      None => {
+        self.optimizer.add_node(layer_index, node)?;
+        layer_hierarchy.iter_mut().for_each(|groups| groups.push(1));
+        for names in &mut self.group_names[layer_index] {
+          let name = (names.len()..)
+            .map(|index| index.to_string())
+            .find(|name| !names.contains(name))
+            .unwrap();
+          names.push(name);
+        }
+      }
+      Some(group_index) => {
+        let group_count = layer_hierarchy.first().map_or(0, |groups| groups.len());
+        if group_index >= group_count {
+          return Err(OptimizerError::InvalidGroup {
+            layer_index,
+            group_index,
+            group_count,
+          });
+        }
+
+        let position = layer_hierarchy[0][..=group_index].iter().sum();
+        self.optimizer.insert_node(layer_index, position, node)?;
+        grow_group(layer_hierarchy, group_index);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Removes a node along with all of its edges, groups that end up empty are removed from the hierarchy.
+  /// The last node of a compound group in a layer it covers can't be removed.
+  pub fn remove_node(&mut self, layer_index: usize, node: &T) -> Result<(), OptimizerError> {
+    let position = self
+      .optimizer
+      .find_node(layer_index, node)
+      .ok()
+      .map(|(_, position)| position);
+    if let (Some(position), Some(top_groups)) = (position, self.hierarchy[layer_index].last()) {
+      let group_index = group_at(top_groups, position);
+      let name = &self.group_names[layer_index].last().unwrap()[group_index];
+      if top_groups[group_index] == 1
+        && self
+          .compound_groups
+          .iter()
+          .any(|g| g.covers(layer_index) && g.name == *name)
+      {
+        return Err(OptimizerError::MissingCompoundGroup {
+          name: name.clone(),
+          layer_index,
+        });
+      }
+    }
+
+    let position = self.optimizer.remove_node(layer_index, node)?;
+    for (level, group_index) in shrink_groups(&mut self.hierarchy[layer_index], position) {
+      self.group_names[layer_index][level].remove(group_index);
+    }
+    Ok(())
+  }
+
+  /// The nodes in their current order and a compact binary snapshot of everything else, see `snapshot::Snapshot`.
+  pub fn to_snapshot(&self) -> Result<(Vec<Vec<T>>, Vec<u8>), OptimizerError> {
+    let snapshot = Snapshot {
+      hierarchy: self.hierarchy.clone(),
+      group_names: self.group_names.clone(),
+      compound_groups: self.compound_groups.clone(),
+      compound_order: self.compound_order.clone(),
+      ..optimizer_snapshot(&self.optimizer)?
+    };
+    Ok((self.get_nodes(), snapshot.encode()))
+  }
+
+  /// Inverse of `to_snapshot`.
+  pub fn from_snapshot(node_layers: Vec<Vec<T>>, bytes: &[u8]) -> Result<Self, OptimizerError> {
+    let snapshot = Snapshot::decode(bytes)?;
+    let (edges, intra_edges, stability) = restore_parts(&node_layers, &snapshot)?;
+    let mut hierarchy_optimizer = Self::with_intra_edges(node_layers, edges, snapshot.hierarchy, intra_edges)?;
+
+    let shape = |levels: &[Vec<usize>]| levels.iter().map(|sizes| sizes.len()).collect_vec();
+    let names_match = hierarchy_optimizer.hierarchy.len() == snapshot.group_names.len()
+      && hierarchy_optimizer
+        .hierarchy
+        .iter()
+        .zip(&snapshot.group_names)
+        .all(|(levels, names)| shape(levels) == names.iter().map(|n| n.len()).collect_vec());
+    if !names_match {
+      return Err(OptimizerError::InvalidSnapshot {
+        message: "the group names don't match the hierarchy".to_string(),
+      });
+    }
+    hierarchy_optimizer.group_names = snapshot.group_names;
+
+    if !snapshot.compound_groups.is_empty() {
+      let group_count = snapshot.compound_groups.len();
+      hierarchy_optimizer.set_compound_groups(snapshot.compound_groups)?;
+      if !snapshot.compound_order.iter().copied().sorted().eq(0..group_count) {
+        return Err(OptimizerError::InvalidSnapshot {
+          message: "the compound order is not a permutation of the compound groups".to_string(),
+        });
+      }
+      hierarchy_optimizer.compound_order = snapshot.compound_order;
+    }
+
+    hierarchy_optimizer.optimizer.set_stability(stability);
+    hierarchy_optimizer
+      .optimizer
+      .set_edge_length_weight(snapshot.edge_length_weight)?;
+    Ok(hierarchy_optimizer)
+  }
+
+  pub fn to_svg(&self, options: &SvgOptions) -> String
+  where
+    T: Display,
+  {
+    let (edges, intra_edges) = self.optimizer.edge_lists();
+    render_svg(
+      &self.optimizer.node_layers,
+      &edges,
+      &intra_edges,
+      Some(&self.hierarchy),
+      options,
+      |layer_index, id| self.optimizer.node(layer_index, *id).to_string(),
+    )
+  }
 }
 
 #[cfg(test)]
@@ -163,6 +624,7 @@ mod tests {
   use std::collections::{HashMap, HashSet};
 
   use super::*;
+  use crate::strategy::{Annealing, Barycenter};
   use crate::utils::*;
 
   #[test]
@@ -193,9 +655,13 @@ mod tests {
       vec![vec![], vec![]],
     );
     match optimizer {
-      Err(OptimizerError::MissingNode { node_name, layer_index }) => {
-        assert_eq!(node_name, "6".to_string());
-        assert_eq!(layer_index, 1);
+      Err(OptimizerError::MissingNode {
+        edge: None,
+        edge_index,
+        layer_index,
+      }) => {
+        assert_eq!(edge_index, 0);
+        assert_eq!(layer_index, 0);
       }
       Err(other) => panic!("Unexpected error: {}", other),
       Ok(_) => panic!("Expected an error"),
@@ -229,12 +695,11 @@ mod tests {
     );
     match optimizer {
       Err(OptimizerError::DuplicateEdge {
-        node_a,
-        node_b,
+        edge: None,
+        edge_index,
         layer_index,
       }) => {
-        assert_eq!(node_a, "0".to_string());
-        assert_eq!(node_b, "3".to_string());
+        assert_eq!(edge_index, 1);
         assert_eq!(layer_index, 0);
       }
       Err(other) => panic!("Unexpected error: {}", other),
@@ -242,6 +707,7 @@ mod tests {
     }
   }
 
+  #[allow(clippy::needless_range_loop)]
   fn get_clusters(hierarchy: &Hierarchy, layer_index: usize, nodes: &[Vec<i32>]) -> HashMap<usize, HashSet<i32>> {
     let mut clusters = HashMap::<usize, HashSet<i32>>::new();
 
@@ -315,7 +781,10 @@ mod tests {
     let mut start_crossings = optimizer.count_crossings();
 
     for granularity in [None, Some(0_usize), Some(1_usize), Some(2_usize)] {
-      let end_crossings = timeit("Optimize", || optimizer.cooldown(1., 0.1, 5, 200, 1, granularity)).unwrap();
+      let end_crossings = timeit("Optimize", || {
+        optimizer.cooldown(&OptimizeConfig::default(), 1, granularity)
+      })
+      .unwrap();
 
       assert_eq!(
         get_clusters(&optimizer.get_hierarchy(), 1, &optimizer.get_nodes()),
@@ -332,6 +801,353 @@ mod tests {
     }
   }
 
+  #[test]
+  fn test_tabu_search_hierarchy() {
+    let hierarchy: Hierarchy = vec![
+      vec![],
+      vec![vec![10, 13, 7, 3, 2, 14, 20, 15, 16], vec![30, 19, 35, 16]],
+      vec![],
+    ];
+
+    let (nodes, edges) = gen_multi_graph(3, 100).unwrap();
+    let clusters = get_clusters(&hierarchy, 1, &nodes);
+    let mut optimizer = HierarchyOptimizer::new(nodes, edges, hierarchy).unwrap();
+    let mut start_crossings = optimizer.count_layer_crossings(1).unwrap();
+
+    for granularity in [None, Some(0_usize), Some(1_usize)] {
+      let end_crossings = optimizer.tabu_search(5, 200, 1, granularity).unwrap();
+
+      assert_eq!(
+        get_clusters(&optimizer.get_hierarchy(), 1, &optimizer.get_nodes()),
+        clusters
+      );
+      assert!(start_crossings >= end_crossings, "{start_crossings} < {end_crossings}");
+      assert_eq!(end_crossings, optimizer.count_layer_crossings(1).unwrap());
+      start_crossings = end_crossings;
+    }
+  }
+
+  #[test]
+  fn test_edit_hierarchy() {
+    let mut optimizer = HierarchyOptimizer::new(
+      vec![vec![0, 1, 2], vec![3, 4, 5, 6]],
+      vec![vec![(0, 3, 1), (1, 5, 1), (2, 6, 1)]],
+      vec![vec![], vec![vec![1, 2, 1], vec![3, 1]]],
+    )
+    .unwrap();
+
+    optimizer.add_node(1, 7, Some(0)).unwrap();
+    optimizer.add_node(1, 8, None).unwrap();
+    optimizer.add_edge(0, 2, 7, 2).unwrap();
+    assert_eq!(optimizer.get_nodes()[1], vec![3, 7, 4, 5, 6, 8]);
+    assert_eq!(optimizer.get_hierarchy()[1], vec![vec![2, 2, 1, 1], vec![4, 1, 1]]);
+    assert_eq!(optimizer.count_crossings(), 2);
+
+    optimizer.remove_node(1, &6).unwrap();
+    optimizer.remove_node(1, &8).unwrap();
+    assert_eq!(optimizer.get_hierarchy()[1], vec![vec![2, 2], vec![4]]);
+    assert_eq!(optimizer.count_crossings(), 2);
+
+    assert!(matches!(
+      optimizer.add_node(1, 9, Some(2)),
+      Err(OptimizerError::InvalidGroup {
+        layer_index: 1,
+        group_index: 2,
+        group_count: 2
+      })
+    ));
+    assert!(matches!(
+      optimizer.add_node(0, 9, Some(0)),
+      Err(OptimizerError::InvalidGroup { group_count: 0, .. })
+    ));
+
+    optimizer
+      .optimize(&OptimizeConfig::builder().max_iterations(20).passes(2).build().unwrap())
+      .unwrap();
+    assert_eq!(optimizer.count_crossings(), 0);
+
+    // Group "0" disappears, so a new group can't be named after the number of groups
+    optimizer.add_node(1, 8, None).unwrap();
+    optimizer.remove_node(1, &3).unwrap();
+    optimizer.remove_node(1, &7).unwrap();
+    optimizer.add_node(1, 9, None).unwrap();
+    let names = optimizer.get_group_names()[1]
+      .iter()
+      .map(|names| names.iter().sorted().cloned().collect_vec())
+      .collect_vec();
+    assert_eq!(names, vec![vec!["1", "2", "3"], vec!["0", "1", "2"]]);
+  }
+
+  #[test]
+  fn test_group_tree() {
+    let group = |name: &str, children| GroupTree::Group {
+      name: name.to_string(),
+      children,
+    };
+    let nodes = |ids: std::ops::Range<i32>| ids.map(GroupTree::Node).collect_vec();
+    let trees = vec![
+      vec![
+        group("db1", vec![group("a", nodes(0..4)), group("b", nodes(4..10))]),
+        group("db2", vec![group("c", nodes(10..15)), group("a", nodes(15..20))]),
+      ],
+      vec![group("db1", nodes(20..25)), group("db2", nodes(25..40))],
+    ];
+    let edges = vec![(0..20).map(|i| (i, 20 + (i * 7) % 20, 1)).collect_vec()];
+
+    let mut optimizer = HierarchyOptimizer::from_group_tree(trees, edges).unwrap();
+    assert_eq!(optimizer.get_hierarchy()[0], vec![vec![4, 6, 5, 5], vec![10, 10]]);
+    optimizer
+      .optimize(&OptimizeConfig::builder().max_iterations(20).passes(3).build().unwrap())
+      .unwrap();
+
+    // Names move along with their groups
+    for layer_index in 0..2 {
+      let tree = optimizer.get_group_tree(layer_index).unwrap();
+      let layer = layer_from_tree(layer_index, &tree).unwrap();
+      assert_eq!(layer.nodes, optimizer.get_nodes()[layer_index]);
+      assert_eq!(layer.names, optimizer.get_group_names()[layer_index]);
+    }
+    let tree = optimizer.get_group_tree(0).unwrap();
+    let db1 = tree
+      .iter()
+      .find(|g| matches!(g, GroupTree::Group { name, .. } if name == "db1"))
+      .unwrap();
+    let GroupTree::Group { children, .. } = db1 else {
+      unreachable!()
+    };
+    let b = children
+      .iter()
+      .find(|g| matches!(g, GroupTree::Group { name, .. } if name == "b"))
+      .unwrap();
+    let GroupTree::Group { children, .. } = b else {
+      unreachable!()
+    };
+    assert_eq!(
+      children
+        .iter()
+        .cloned()
+        .sorted_by_key(|n| format!("{n:?}"))
+        .collect_vec(),
+      nodes(4..10)
+    );
+
+    let paths = vec![
+      HashMap::from([
+        (0, vec!["x".to_string()]),
+        (1, vec!["y".to_string()]),
+        (2, vec!["x".to_string()]),
+      ]),
+      HashMap::new(),
+    ];
+    let optimizer = HierarchyOptimizer::from_group_paths(vec![vec![0, 1, 2], vec![3]], vec![vec![]], paths.clone());
+    assert!(matches!(
+      optimizer,
+      Err(OptimizerError::MissingGroupPath { layer_index: 1, .. })
+    ));
+
+    let optimizer = HierarchyOptimizer::from_group_paths(vec![vec![0, 1, 2], vec![]], vec![vec![]], paths).unwrap();
+    assert_eq!(optimizer.get_nodes()[0], vec![0, 2, 1]);
+    assert_eq!(optimizer.get_group_names()[0], vec![vec!["x", "y"]]);
+  }
+
+  #[test]
+  fn test_group_order() {
+    let hierarchy: Hierarchy = vec![vec![vec![4, 9, 6, 11], vec![13, 17]], vec![vec![30]], vec![]];
+    let (nodes, edges) = gen_multi_graph(3, 30).unwrap();
+    let mut optimizer = HierarchyOptimizer::new(nodes, edges, hierarchy.clone()).unwrap();
+    assert_eq!(optimizer.get_group_order(0, 1).unwrap(), vec!["0", "1"]);
+
+    // Every group keeps its name while it is moved around
+    optimizer
+      .optimize(&OptimizeConfig::builder().max_iterations(20).passes(3).build().unwrap())
+      .unwrap();
+    let sizes = |granularity: usize| {
+      optimizer
+        .get_group_order(0, granularity)
+        .unwrap()
+        .iter()
+        .map(|name| hierarchy[0][granularity][name.parse::<usize>().unwrap()])
+        .collect_vec()
+    };
+    assert_eq!(sizes(0), optimizer.get_hierarchy()[0][0]);
+    assert_eq!(sizes(1), optimizer.get_hierarchy()[0][1]);
+
+    assert!(matches!(
+      optimizer.get_group_order(2, 0),
+      Err(OptimizerError::InvalidGranularity {
+        layer_index: 2,
+        granularity: 0,
+        level_count: 0
+      })
+    ));
+    assert!(matches!(
+      optimizer.get_group_order(3, 0),
+      Err(OptimizerError::InvalidLayer { .. })
+    ));
+    assert!(matches!(
+      optimizer.swap_nodes(1., 10, 1, Some(1)),
+      Err(OptimizerError::InvalidGranularity { .. })
+    ));
+  }
+
+  #[test]
+  fn test_compound_groups() {
+    let group = |name: &str, ids: std::ops::Range<i32>| GroupTree::Group {
+      name: name.to_string(),
+      children: ids.map(GroupTree::Node).collect(),
+    };
+    let trees = vec![
+      vec![group("p1", 0..5), group("x", 5..10), group("p2", 10..15)],
+      vec![group("p2", 15..20), group("p3", 20..25), group("p1", 25..30)],
+      vec![group("p3", 30..35), group("y", 35..40), group("p1", 40..45)],
+    ];
+    let (_, edges) = generate_multipartite_graph(vec![15, 15, 15]).unwrap();
+    let mut optimizer = HierarchyOptimizer::from_group_tree(trees, edges).unwrap();
+
+    let compound = |name: &str, first_layer, last_layer| CompoundGroup {
+      name: name.to_string(),
+      first_layer,
+      last_layer,
+    };
+    assert!(matches!(
+      optimizer.set_compound_groups(vec![compound("p1", 0, 1), compound("p2", 0, 1)]),
+      Err(OptimizerError::InconsistentCompoundOrder { .. })
+    ));
+
+    let compound_groups = vec![compound("p1", 0, 2), compound("p3", 1, 2)];
+    optimizer.set_compound_groups(compound_groups.clone()).unwrap();
+    assert_eq!(optimizer.get_compound_order(), vec!["p3", "p1"]);
+
+    for _ in 0..5 {
+      optimizer
+        .optimize(&OptimizeConfig::builder().max_iterations(20).passes(1).build().unwrap())
+        .unwrap();
+      let order = optimizer.get_compound_order();
+      for layer_index in 0..3 {
+        let top = optimizer.get_group_order(layer_index, 0).unwrap();
+        let expected = order
+          .iter()
+          .filter(|name| {
+            compound_groups
+              .iter()
+              .any(|g| g.name == **name && g.covers(layer_index))
+          })
+          .collect_vec();
+        assert_eq!(
+          top.iter().filter(|name| expected.contains(name)).collect_vec(),
+          expected
+        );
+      }
+    }
+
+    // The last node of a compound group can't be removed
+    let p3_nodes = optimizer
+      .get_group_tree(1)
+      .unwrap()
+      .into_iter()
+      .find_map(|tree| match tree {
+        GroupTree::Group { name, children } if name == "p3" => Some(children),
+        _ => None,
+      })
+      .unwrap();
+    for (i, node) in p3_nodes.iter().enumerate() {
+      let GroupTree::Node(node) = node else { unreachable!() };
+      let result = optimizer.remove_node(1, node);
+      assert_eq!(result.is_ok(), i < 4);
+    }
+  }
+
+  #[test]
+  fn test_snapshot() {
+    let group = |name: &str, ids: std::ops::Range<i32>| GroupTree::Group {
+      name: name.to_string(),
+      children: ids.map(GroupTree::Node).collect(),
+    };
+    let trees = vec![
+      vec![group("p1", 0..5), group("x", 5..10)],
+      vec![group("y", 10..15), group("p1", 15..20)],
+    ];
+    let (_, edges) = generate_multipartite_graph(vec![10, 10]).unwrap();
+    let mut optimizer = HierarchyOptimizer::from_group_tree(trees, edges).unwrap();
+    optimizer
+      .set_compound_groups(vec![CompoundGroup {
+        name: "p1".to_string(),
+        first_layer: 0,
+        last_layer: 1,
+      }])
+      .unwrap();
+    optimizer
+      .optimize(&OptimizeConfig::builder().max_iterations(20).passes(1).build().unwrap())
+      .unwrap();
+
+    let (nodes, bytes) = optimizer.to_snapshot().unwrap();
+    let restored = HierarchyOptimizer::from_snapshot(nodes.clone(), &bytes).unwrap();
+    assert_eq!(restored.get_nodes(), nodes);
+    assert_eq!(restored.get_hierarchy(), optimizer.get_hierarchy());
+    assert_eq!(restored.get_group_names(), optimizer.get_group_names());
+    assert_eq!(restored.get_compound_order(), optimizer.get_compound_order());
+    assert_eq!(restored.count_crossings(), optimizer.count_crossings());
+  }
+
+  #[test]
+  fn test_group_crossings() {
+    let group = |name: &str, nodes: &[i32]| GroupTree::Group {
+      name: name.to_string(),
+      children: nodes.iter().map(|node| GroupTree::Node(*node)).collect(),
+    };
+    let trees = vec![
+      vec![group("a", &[0, 1]), group("b", &[2])],
+      vec![group("c", &[3]), group("d", &[4, 5])],
+      vec![GroupTree::Node(6), GroupTree::Node(7)],
+    ];
+    let edges = vec![
+      vec![(0, 4, 1), (1, 5, 2), (2, 3, 1)],
+      vec![(3, 7, 1), (4, 6, 1), (5, 6, 1)],
+    ];
+    let optimizer = HierarchyOptimizer::from_group_tree(trees, edges).unwrap();
+
+    // a-d with weight 3 crosses b-c, c-7 crosses d-6 with weight 2, a layer without groups keeps its nodes
+    assert_eq!(optimizer.count_group_crossings(0).unwrap(), 5);
+    assert!(matches!(
+      optimizer.count_group_crossings(3),
+      Err(OptimizerError::InvalidGranularity {
+        layer_index: 0,
+        granularity: 3,
+        level_count: 1
+      })
+    ));
+    assert!(optimizer.group_crossings(1).is_err());
+    assert_eq!(
+      optimizer.group_crossings(0).unwrap(),
+      vec![
+        vec![("a".to_string(), 3), ("b".to_string(), 3)],
+        vec![("c".to_string(), 5), ("d".to_string(), 5)],
+        vec![("6".to_string(), 2), ("7".to_string(), 2)],
+      ]
+    );
+  }
+
+  #[test]
+  fn test_edit_group_names() {
+    let mut optimizer = HierarchyOptimizer::new(
+      vec![vec![0, 1, 2], vec![3, 4, 5, 6]],
+      vec![vec![(0, 3, 1), (1, 5, 1), (2, 6, 1)]],
+      vec![vec![], vec![vec![1, 2, 1], vec![3, 1]]],
+    )
+    .unwrap();
+    assert_eq!(
+      optimizer.get_group_names()[1],
+      vec![vec!["0", "1", "2"], vec!["0", "1"]]
+    );
+
+    optimizer.add_node(1, 8, None).unwrap();
+    optimizer.remove_node(1, &3).unwrap();
+    assert_eq!(
+      optimizer.get_group_names()[1],
+      vec![vec!["1", "2", "3"], vec!["0", "1", "2"]]
+    );
+  }
+
   #[test]
   fn test_optimize_hierarchy() {
     let n = 100;
@@ -351,7 +1167,10 @@ mod tests {
     let mut optimizer = HierarchyOptimizer::new(nodes, edges, hierarchy).unwrap();
     let start_crossings = optimizer.count_crossings();
 
-    let end_crossings = timeit("Optimize", || optimizer.optimize(1., 0.1, 5, 200, 20)).unwrap();
+    let end_crossings = timeit("Optimize", || {
+      optimizer.optimize(&OptimizeConfig::builder().passes(20).build().unwrap())
+    })
+    .unwrap();
 
     assert_eq!(
       get_clusters(&optimizer.get_hierarchy(), 1, &optimizer.get_nodes()),
@@ -362,4 +1181,41 @@ mod tests {
     assert!(start_crossings > end_crossings);
     assert!(end_crossings > 0);
   }
+
+  #[test]
+  fn test_optimize_with() {
+    let hierarchy: Hierarchy = vec![vec![vec![4, 9, 6, 11], vec![13, 17]], vec![vec![30]], vec![]];
+
+    let (nodes, edges) = gen_multi_graph(3, 30).unwrap();
+    let clusters = get_clusters(&hierarchy, 0, &nodes);
+    let mut optimizer = HierarchyOptimizer::new(nodes, edges, hierarchy).unwrap();
+    let start_crossings = optimizer.count_crossings();
+
+    let strategies: Vec<Box<dyn LayerStrategy>> = vec![Box::new(Barycenter), Box::new(Annealing::default())];
+    let end_crossings = optimizer.optimize_with(&strategies, 3).unwrap();
+
+    assert_eq!(
+      get_clusters(&optimizer.get_hierarchy(), 0, &optimizer.get_nodes()),
+      clusters
+    );
+    assert_eq!(end_crossings, optimizer.count_crossings());
+    assert!(end_crossings < start_crossings);
+  }
+
+  #[test]
+  fn test_polish() {
+    let hierarchy: Hierarchy = vec![vec![vec![4, 9, 6, 11], vec![13, 17]], vec![vec![30]], vec![]];
+
+    let (nodes, edges) = gen_multi_graph(3, 30).unwrap();
+    let clusters = get_clusters(&hierarchy, 0, &nodes);
+    let mut optimizer = HierarchyOptimizer::new(nodes, edges, hierarchy).unwrap();
+    let start_crossings = optimizer.count_crossings();
+    let polished = optimizer.polish(true, true).unwrap();
+
+    assert!(polished < start_crossings);
+    assert_eq!(
+      get_clusters(&optimizer.get_hierarchy(), 0, &optimizer.get_nodes()),
+      clusters
+    );
+  }
 }
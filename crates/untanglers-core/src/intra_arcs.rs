@@ -0,0 +1,240 @@
+use std::ops::Range;
+
+/// Node position at which every unit starts, indexed by unit, for an ordering of units with the given sizes.
+pub fn unit_starts(sizes: &[usize], order: &[usize]) -> Vec<usize> {
+  let mut starts = vec![0; sizes.len()];
+  let mut start = 0;
+  for unit in order {
+    starts[*unit] = start;
+    start += sizes[*unit];
+  }
+  starts
+}
+
+/**
+ * Crossings between the edges within a swappable layer, see `count_crossings::_count_intra_crossings`.
+ *
+ * Whether two arcs cross depends on the positions of all four endpoints, so like the edge length this term can't be
+ * expressed as a fixed pairwise matrix. Moving a run of units only changes pairs of arcs of which at least one touches
+ * a moved unit, so a move is evaluated exactly by recounting just those pairs against the current positions.
+ */
+pub struct IntraArcs {
+  sizes: Vec<usize>,
+  /// The unit of every node and its offset within the unit
+  unit_of: Vec<(usize, usize)>,
+  /// As (node, node, weight) with nodes in the order the swappable nodes were given in
+  arcs: Vec<(usize, usize, f64)>,
+  /// Indices of the arcs that touch each unit
+  unit_arcs: Vec<Vec<usize>>,
+}
+
+impl IntraArcs {
+  /// * `mapped_edges` Edges within the layer as (position, position, weight)
+  /// * `groups` Sizes of the swappable units, if None every node is its own unit
+  pub fn new(node_count: usize, mapped_edges: &[(usize, usize, usize)], groups: Option<&[usize]>) -> Self {
+    let sizes = match groups {
+      Some(groups) => groups.to_vec(),
+      None => vec![1; node_count],
+    };
+
+    let mut unit_of = Vec::with_capacity(node_count);
+    for (unit, size) in sizes.iter().enumerate() {
+      for offset in 0..*size {
+        unit_of.push((unit, offset));
+      }
+    }
+
+    let mut unit_arcs = vec![vec![]; sizes.len()];
+    for (arc, (a, b, _)) in mapped_edges.iter().enumerate() {
+      let (unit_a, unit_b) = (unit_of[*a].0, unit_of[*b].0);
+      unit_arcs[unit_a].push(arc);
+      if unit_b != unit_a {
+        unit_arcs[unit_b].push(arc);
+      }
+    }
+
+    Self {
+      sizes,
+      unit_of,
+      arcs: mapped_edges.iter().map(|(a, b, w)| (*a, *b, *w as f64)).collect(),
+      unit_arcs,
+    }
+  }
+
+  pub fn starts(&self, order: &[usize]) -> Vec<usize> {
+    unit_starts(&self.sizes, order)
+  }
+
+  fn position(&self, starts: &[usize], node: usize) -> usize {
+    let (unit, offset) = self.unit_of[node];
+    starts[unit] + offset
+  }
+
+  fn cross(&self, arc1: usize, arc2: usize, position: &impl Fn(usize) -> usize) -> bool {
+    let span = |arc: usize| {
+      let (a, b, _) = self.arcs[arc];
+      let (a, b) = (position(a), position(b));
+      (a.min(b), a.max(b))
+    };
+    let ((start1, end1), (start2, end2)) = (span(arc1), span(arc2));
+    (start1 < start2 && start2 < end1 && end1 < end2) || (start2 < start1 && start1 < end2 && end2 < end1)
+  }
+
+  /// Weighted crossings for an ordering of the units.
+  pub fn cost(&self, order: &[usize]) -> f64 {
+    let starts = self.starts(order);
+    let position = |node| self.position(&starts, node);
+
+    let mut cost = 0.;
+    for arc1 in 0..self.arcs.len() {
+      for arc2 in arc1 + 1..self.arcs.len() {
+        if self.cross(arc1, arc2, &position) {
+          cost += self.arcs[arc1].2 * self.arcs[arc2].2;
+        }
+      }
+    }
+    cost
+  }
+
+  /// Reduction in crossings between the node positions `before` and `after`, where only the nodes of the units for
+  /// which `moved` holds change position.
+  fn delta(
+    &self,
+    units: &[usize],
+    moved: impl Fn(usize) -> bool,
+    before: impl Fn(usize) -> usize,
+    after: impl Fn(usize) -> usize,
+  ) -> f64 {
+    let touches = |arc: usize| {
+      let (a, b, _) = self.arcs[arc];
+      moved(self.unit_of[a].0) || moved(self.unit_of[b].0)
+    };
+
+    let mut reduction = 0.;
+    for (i, unit) in units.iter().enumerate() {
+      for arc1 in &self.unit_arcs[*unit] {
+        // Arcs between two moved units are listed twice
+        let (a, b, weight1) = self.arcs[*arc1];
+        let other = if self.unit_of[a].0 == *unit { b } else { a };
+        if units[..i].contains(&self.unit_of[other].0) {
+          continue;
+        }
+
+        for arc2 in 0..self.arcs.len() {
+          // Pairs of two touching arcs are counted from the one with the lower index
+          if arc2 == *arc1 || (arc2 < *arc1 && touches(arc2)) {
+            continue;
+          }
+          let change = self.cross(*arc1, arc2, &before) as i8 - self.cross(*arc1, arc2, &after) as i8;
+          reduction += change as f64 * weight1 * self.arcs[arc2].2;
+        }
+      }
+    }
+    reduction
+  }
+
+  /// Reduction in crossings when swapping `unit_a` with `unit_b` directly after it.
+  ///
+  /// * `starts` Node position at which every unit starts in the current order, see `starts`
+  pub fn swap_delta(&self, starts: &[usize], unit_a: usize, unit_b: usize) -> f64 {
+    let start = starts[unit_a];
+    let after = |node: usize| {
+      let (unit, offset) = self.unit_of[node];
+      match unit {
+        _ if unit == unit_a => start + self.sizes[unit_b] + offset,
+        _ if unit == unit_b => start + offset,
+        _ => starts[unit] + offset,
+      }
+    };
+
+    self.delta(
+      &[unit_a, unit_b],
+      |unit| unit == unit_a || unit == unit_b,
+      |node| self.position(starts, node),
+      after,
+    )
+  }
+
+  /// Reduction in crossings when the units at positions `range` of `order` are replaced by `new_units`.
+  pub fn range_delta(&self, order: &[usize], range: Range<usize>, new_units: &[usize]) -> f64 {
+    let mut new_order = order.to_vec();
+    new_order.splice(range.clone(), new_units.iter().copied());
+    let (starts_before, starts_after) = (self.starts(order), self.starts(&new_order));
+
+    let mut moved = vec![false; self.sizes.len()];
+    for unit in &order[range.clone()] {
+      moved[*unit] = true;
+    }
+
+    self.delta(
+      &order[range],
+      |unit| moved[unit],
+      |node| self.position(&starts_before, node),
+      |node| self.position(&starts_after, node),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::pairwise::get_intra_pairwise_matrix;
+
+  #[test]
+  fn test_swap_delta() {
+    let edges = vec![(2, 5, 1), (1, 4, 2), (0, 3, 1), (3, 4, 3)];
+    let arcs = IntraArcs::new(6, &edges, None);
+
+    let mut order = (0..6).collect::<Vec<_>>();
+    for (i, j) in [(0, 1), (3, 4), (1, 2), (4, 5), (2, 3)] {
+      let starts = arcs.starts(&order);
+      let (a, b) = (order[i], order[j]);
+      let mut swapped = order.clone();
+      swapped.swap(i, j);
+
+      let reduction = arcs.cost(&order) - arcs.cost(&swapped);
+      assert_eq!(arcs.swap_delta(&starts, a, b), reduction);
+      assert_eq!(arcs.range_delta(&order, i..j + 1, &[b, a]), reduction);
+      order = swapped;
+    }
+  }
+
+  #[test]
+  fn test_sign_change() {
+    // Arcs 1-4 and 2-5 cross, swapping 4 and 5 nests them
+    let edges = vec![(2, 5, 1), (1, 4, 1)];
+    let arcs = IntraArcs::new(6, &edges, None);
+    let linearised = get_intra_pairwise_matrix(6, &edges);
+    let order = (0..6).collect::<Vec<_>>();
+    assert_eq!(arcs.swap_delta(&arcs.starts(&order), 4, 5), 1.);
+    assert_eq!(linearised[4 * 6 + 5], 1.);
+
+    // Once 1 and 2 are swapped the arcs are nested and swapping 4 and 5 makes them cross, which the linearisation
+    // around the starting order still counts as an improvement
+    let order = vec![0, 2, 1, 3, 4, 5];
+    assert_eq!(arcs.cost(&order), 0.);
+    assert_eq!(arcs.swap_delta(&arcs.starts(&order), 4, 5), -1.);
+  }
+
+  #[test]
+  fn test_groups() {
+    // Units [0, 1], [2], [3, 4]
+    let edges = vec![(0, 3, 1), (1, 2, 2), (2, 4, 1)];
+    let arcs = IntraArcs::new(5, &edges, Some(&[2, 1, 2]));
+
+    let order = vec![0, 1, 2];
+    let starts = arcs.starts(&order);
+    assert_eq!(starts, vec![0, 2, 3]);
+    assert_eq!(arcs.cost(&order), 1.);
+    for (i, j) in [(0, 1), (1, 2)] {
+      let mut swapped = order.clone();
+      swapped.swap(i, j);
+      let reduction = arcs.cost(&order) - arcs.cost(&swapped);
+      assert_eq!(arcs.swap_delta(&starts, order[i], order[j]), reduction);
+    }
+    assert_eq!(
+      arcs.range_delta(&order, 0..3, &[2, 1, 0]),
+      arcs.cost(&order) - arcs.cost(&[2, 1, 0])
+    );
+  }
+}
@@ -1,16 +1,27 @@
-use std::fmt::{Debug, Display};
+use std::fmt::Display;
 use std::hash::Hash;
+use std::time::Duration;
 
+use itertools::Itertools;
+
+use crate::config::OptimizeConfig;
 use crate::error::OptimizerError;
+use crate::exact::solve_exact;
+use crate::local_search::MAX_POLISH_SWEEPS;
 use crate::mapping::reorder_nodes;
+use crate::objective::Objective;
 use crate::optimizer::Optimizer;
 use crate::optimizer_ops::{impl_optimizer_ops, OptimizerInternalOps, OptimizerOps};
-use crate::reducer::reduce_crossings;
-use crate::utils::{validate_edge_uniqueness, validate_layers};
+use crate::snapshot::{optimizer_snapshot, restore_parts, Snapshot};
+use crate::stability::StabilityMetric;
+use crate::strategy::{GreedySwitch, LayerStrategy, Tabu};
+use crate::svg::{render_svg, SvgOptions};
+use crate::utils::{validate_edge_uniqueness, validate_layers, validate_permutation};
 
+#[derive(Clone)]
 pub struct LayoutOptimizer<T>
 where
-  T: Eq + Hash + Clone + Display + Debug,
+  T: Eq + Hash + Clone,
 {
   optimizer: Optimizer<T>,
 }
@@ -19,7 +30,7 @@ impl_optimizer_ops!(LayoutOptimizer<T>);
 
 impl<T> LayoutOptimizer<T>
 where
-  T: Eq + Hash + Clone + Display + Debug,
+  T: Eq + Hash + Clone,
 {
   pub fn new(node_layers: Vec<Vec<T>>, edges: Vec<Vec<(T, T, usize)>>) -> Result<Self, OptimizerError> {
     validate_layers(&node_layers, &edges)?;
@@ -29,83 +40,186 @@ where
     Ok(Self { optimizer })
   }
 
+  /// Like `new`, with additional edges between nodes in the same layer, one list per layer.
+  pub fn with_intra_edges(
+    node_layers: Vec<Vec<T>>,
+    edges: Vec<Vec<(T, T, usize)>>,
+    intra_edges: Vec<Vec<(T, T, usize)>>,
+  ) -> Result<Self, OptimizerError> {
+    let mut layout_optimizer = Self::new(node_layers, edges)?;
+    layout_optimizer.set_intra_edges(intra_edges)?;
+    Ok(layout_optimizer)
+  }
+
+  /// Replaces the edges between nodes in the same layer, one list per layer.
+  pub fn set_intra_edges(&mut self, intra_edges: Vec<Vec<(T, T, usize)>>) -> Result<(), OptimizerError> {
+    self.optimizer.set_intra_edges(intra_edges)
+  }
+
   pub fn swap_nodes(
     &mut self,
     temperature: f64,
     max_iterations: usize,
     layer_index: usize,
   ) -> Result<usize, OptimizerError> {
-    let (nodes1, edges1, nodes2, edges2) = self.get_adjacent_layers(layer_index)?;
-
-    let (new_indices, new_count) = reduce_crossings(
-      &self.optimizer.node_layers[layer_index],
-      nodes1,
-      edges1,
-      nodes2,
-      edges2,
-      max_iterations,
-      temperature,
-      temperature,
-      1,
-      None,
-      None,
-    );
-
-    self.optimizer.node_layers[layer_index] = reorder_nodes(&self.optimizer.node_layers[layer_index], &new_indices);
+    let config = OptimizeConfig::builder()
+      .temperature(temperature)
+      .steps(1)
+      .max_iterations(max_iterations)
+      .build()?;
+    self.apply_strategy(&config.annealing(), layer_index)
+  }
 
-    Ok(new_count as usize)
+  /// Anneals a single layer once, `config.passes()` is not used.
+  pub fn cooldown(&mut self, config: &OptimizeConfig, layer_index: usize) -> Result<usize, OptimizerError> {
+    self.apply_strategy(&config.annealing(), layer_index)
   }
 
-  pub fn cooldown(
+  /// Reorders a single layer with tabu search, which needs no temperature. See `local_search::tabu_search`.
+  pub fn tabu_search(
     &mut self,
-    start_temp: f64,
-    end_temp: f64,
-    steps: usize,
+    tenure: usize,
     max_iterations: usize,
     layer_index: usize,
   ) -> Result<usize, OptimizerError> {
-    let (nodes1, edges1, nodes2, edges2) = self.get_adjacent_layers(layer_index)?;
-
-    let (new_indices, new_count) = reduce_crossings(
-      &self.optimizer.node_layers[layer_index],
-      nodes1,
-      edges1,
-      nodes2,
-      edges2,
-      max_iterations,
-      start_temp,
-      end_temp,
-      steps,
-      None,
-      None,
-    );
+    self.apply_strategy(&Tabu { tenure, max_iterations }, layer_index)
+  }
 
+  /// Reorders a single layer with the given strategy, returns the crossings of the layer afterwards.
+  pub fn apply_strategy(&mut self, strategy: &dyn LayerStrategy, layer_index: usize) -> Result<usize, OptimizerError> {
+    let problem = self.optimizer.layer_problem(layer_index, None, None)?;
+    let new_indices = strategy.reorder(&problem);
+    validate_permutation(&new_indices, problem.unit_count())?;
     self.optimizer.node_layers[layer_index] = reorder_nodes(&self.optimizer.node_layers[layer_index], &new_indices);
+    self.optimizer.check_interrupt()?;
 
-    Ok(new_count as usize)
+    self.count_layer_crossings(layer_index)
   }
 
-  pub fn optimize(
+  pub fn optimize(&mut self, config: &OptimizeConfig) -> Result<usize, OptimizerError> {
+    let annealing: Box<dyn LayerStrategy> = Box::new(config.annealing());
+    self.optimize_with(&[annealing], config.passes())
+  }
+
+  /// Each pass sweeps over all layers once for every strategy, in the given order.
+  pub fn optimize_with(
     &mut self,
-    start_temp: f64,
-    end_temp: f64,
-    steps: usize,
-    max_iterations: usize,
+    strategies: &[Box<dyn LayerStrategy>],
     passes: usize,
   ) -> Result<usize, OptimizerError> {
     for _pass in 0..passes {
-      for i in 0..self.optimizer.node_layers.len() {
-        self.cooldown(start_temp, end_temp, steps, max_iterations, i)?;
+      for strategy in strategies {
+        for i in 0..self.optimizer.node_layers.len() {
+          self.apply_strategy(strategy.as_ref(), i)?;
+        }
       }
     }
 
     Ok(self.count_crossings())
   }
+
+  /// Deterministic final polish, usually after `optimize`: applies `GreedySwitch` to every layer until a full sweep no
+  /// longer changes anything.
+  pub fn polish(&mut self, two_opt: bool, block_reversal: bool) -> Result<usize, OptimizerError> {
+    let greedy = GreedySwitch {
+      two_opt,
+      block_reversal,
+    };
+
+    for _sweep in 0..MAX_POLISH_SWEEPS {
+      let node_layers = self.optimizer.node_layers.clone();
+      for layer_index in 0..self.optimizer.node_layers.len() {
+        self.apply_strategy(&greedy, layer_index)?;
+      }
+      if self.optimizer.node_layers == node_layers {
+        break;
+      }
+    }
+
+    Ok(self.count_crossings())
+  }
+
+  /// Replaces the layout with one that has the fewest possible crossings, see `exact::solve_exact`. Only the crossings
+  /// between layers are minimised, other terms of the cost are ignored. Returns the crossings and whether the layout is
+  /// proven optimal, which is not the case if the time limit was hit.
+  pub fn solve_exact(
+    &mut self,
+    max_nodes: usize,
+    time_limit: Option<Duration>,
+  ) -> Result<(usize, bool), OptimizerError> {
+    let layer_sizes = self.optimizer.node_layers.iter().map(|layer| layer.len()).collect_vec();
+    let edges = (0..self.optimizer.edges.len())
+      .map(|i| self.optimizer.mapped_edges(i))
+      .collect_vec();
+
+    let solution = solve_exact(&layer_sizes, &edges, max_nodes, time_limit)?;
+    for (layer, order) in self.optimizer.node_layers.iter_mut().zip(&solution.orders) {
+      *layer = reorder_nodes(layer, order);
+    }
+
+    Ok((solution.crossings, solution.optimal))
+  }
+
+  /// Adds a node without edges at the end of its layer.
+  pub fn add_node(&mut self, layer_index: usize, node: T) -> Result<(), OptimizerError> {
+    self.optimizer.add_node(layer_index, node)
+  }
+
+  /// Removes a node along with all of its edges.
+  pub fn remove_node(&mut self, layer_index: usize, node: &T) -> Result<(), OptimizerError> {
+    self.optimizer.remove_node(layer_index, node)?;
+    Ok(())
+  }
+
+  /// The nodes in their current order and a compact binary snapshot of everything else, see `snapshot::Snapshot`.
+  pub fn to_snapshot(&self) -> Result<(Vec<Vec<T>>, Vec<u8>), OptimizerError> {
+    let snapshot = optimizer_snapshot(&self.optimizer)?;
+    Ok((self.get_nodes(), snapshot.encode()))
+  }
+
+  /// Inverse of `to_snapshot`.
+  pub fn from_snapshot(node_layers: Vec<Vec<T>>, bytes: &[u8]) -> Result<Self, OptimizerError> {
+    let snapshot = Snapshot::decode(bytes)?;
+    if !snapshot.hierarchy.is_empty() {
+      return Err(OptimizerError::InvalidSnapshot {
+        message: "the snapshot has a hierarchy".to_string(),
+      });
+    }
+
+    let (edges, intra_edges, stability) = restore_parts(&node_layers, &snapshot)?;
+    let mut layout_optimizer = Self::with_intra_edges(node_layers, edges, intra_edges)?;
+    layout_optimizer.optimizer.set_stability(stability);
+    layout_optimizer
+      .optimizer
+      .set_edge_length_weight(snapshot.edge_length_weight)?;
+    Ok(layout_optimizer)
+  }
+
+  pub fn to_svg(&self, options: &SvgOptions) -> String
+  where
+    T: Display,
+  {
+    let (edges, intra_edges) = self.optimizer.edge_lists();
+    render_svg(
+      &self.optimizer.node_layers,
+      &edges,
+      &intra_edges,
+      None,
+      options,
+      |layer_index, id| self.optimizer.node(layer_index, *id).to_string(),
+    )
+  }
 }
 
 #[cfg(test)]
 mod tests {
+  use std::collections::HashMap;
+  use std::sync::atomic::{AtomicBool, Ordering};
+  use std::sync::Arc;
+
   use super::*;
+  use crate::objective::GroupCohesion;
+  use crate::strategy::{Annealing, Barycenter, LayerProblem};
   use crate::utils::*;
 
   #[test]
@@ -131,7 +245,7 @@ mod tests {
     let (nodes, edges) = gen_multi_graph(7, n).unwrap();
     let mut optimizer = LayoutOptimizer::new(nodes, edges).unwrap();
     let start_crossings = optimizer.count_crossings();
-    let end_crossings = timeit("Optimize", || optimizer.cooldown(1., 0.1, 5, 200, 3)).unwrap();
+    let end_crossings = timeit("Optimize", || optimizer.cooldown(&OptimizeConfig::default(), 3)).unwrap();
 
     println!("Improved from {} to {}", start_crossings, end_crossings);
     assert!(start_crossings > end_crossings);
@@ -141,6 +255,17 @@ mod tests {
     assert_eq!(end_crossings, real_crossings);
   }
 
+  #[test]
+  fn test_tabu_search() {
+    let (nodes, edges) = gen_multi_graph(3, 100).unwrap();
+    let mut optimizer = LayoutOptimizer::new(nodes, edges).unwrap();
+    let start_crossings = optimizer.count_layer_crossings(1).unwrap();
+    let end_crossings = timeit("Tabu", || optimizer.tabu_search(7, 500, 1)).unwrap();
+
+    assert!(start_crossings > end_crossings);
+    assert_eq!(end_crossings, optimizer.count_layer_crossings(1).unwrap());
+  }
+
   #[test]
   fn test_optimize() {
     let n = 200;
@@ -148,10 +273,277 @@ mod tests {
     let (nodes, edges) = gen_multi_graph(7, n).unwrap();
     let mut optimizer = LayoutOptimizer::new(nodes, edges).unwrap();
     let start_crossings = optimizer.count_crossings();
-    let end_crossings = timeit("Optimize", || optimizer.optimize(1., 0.1, 5, 200, 20)).unwrap();
+    let end_crossings = timeit("Optimize", || {
+      optimizer.optimize(&OptimizeConfig::builder().passes(20).build().unwrap())
+    })
+    .unwrap();
 
     println!("Improved from {} to {}", start_crossings, end_crossings);
     assert!(start_crossings > end_crossings);
     assert!(end_crossings > 0);
   }
+
+  #[test]
+  fn test_struct_nodes() {
+    // Node types only need Eq + Hash + Clone, errors point at nodes by position
+    #[derive(Clone, PartialEq, Eq, Hash)]
+    struct Column {
+      table: &'static str,
+      name: &'static str,
+    }
+    let column = |table, name| Column { table, name };
+
+    let nodes = vec![
+      vec![column("orders", "id"), column("orders", "user_id")],
+      vec![column("users", "id"), column("items", "order_id")],
+    ];
+    let edges = vec![vec![
+      (column("orders", "id"), column("items", "order_id"), 1),
+      (column("orders", "user_id"), column("users", "id"), 1),
+    ]];
+    let mut optimizer = LayoutOptimizer::new(nodes.clone(), edges).unwrap();
+    assert_eq!(optimizer.count_crossings(), 1);
+    assert_eq!(optimizer.optimize(&OptimizeConfig::default()).unwrap(), 0);
+    for (layer, original) in optimizer.get_nodes().iter().zip(&nodes) {
+      assert!(original.iter().all(|node| layer.contains(node)));
+    }
+
+    assert!(matches!(
+      optimizer.add_edge(0, column("orders", "id"), column("users", "name"), 1),
+      Err(OptimizerError::MissingNode {
+        edge: None,
+        edge_index: 2,
+        layer_index: 0
+      })
+    ));
+  }
+
+  #[test]
+  fn test_intra_edges() {
+    let n = 50;
+
+    let (nodes, edges) = gen_multi_graph(3, n).unwrap();
+    let intra_edges = vec![
+      vec![],
+      (0..n / 2).map(|i| (nodes[1][i], nodes[1][n - i - 1], 1)).collect(),
+      vec![],
+    ];
+    let mut optimizer = LayoutOptimizer::with_intra_edges(nodes, edges, intra_edges).unwrap();
+    let start_crossings = optimizer.count_crossings();
+    let end_crossings = optimizer
+      .cooldown(&OptimizeConfig::builder().max_iterations(100).build().unwrap(), 1)
+      .unwrap();
+
+    assert!(start_crossings > end_crossings);
+    assert_eq!(end_crossings, optimizer.count_layer_crossings(1).unwrap());
+  }
+
+  #[test]
+  fn test_stability() {
+    let n = 50;
+
+    let (nodes, edges) = gen_multi_graph(3, n).unwrap();
+    let mut optimizer = LayoutOptimizer::new(nodes.clone(), edges).unwrap();
+    optimizer
+      .set_reference(&nodes, StabilityMetric::KendallTau, 1e6)
+      .unwrap();
+    let crossings = optimizer
+      .optimize(&OptimizeConfig::builder().max_iterations(50).passes(2).build().unwrap())
+      .unwrap();
+    assert_eq!(optimizer.get_nodes(), nodes);
+    assert_eq!(crossings, optimizer.count_crossings());
+
+    optimizer.clear_reference();
+    let new_crossings = optimizer
+      .optimize(&OptimizeConfig::builder().max_iterations(50).passes(2).build().unwrap())
+      .unwrap();
+    assert_ne!(optimizer.get_nodes(), nodes);
+    assert!(new_crossings < crossings);
+
+    assert!(matches!(
+      optimizer.set_reference(&nodes[1..], StabilityMetric::Displacement, 1.),
+      Err(OptimizerError::ReferenceMismatch {
+        reference: 2,
+        layers: 3
+      })
+    ));
+  }
+
+  #[test]
+  fn test_edge_length() {
+    let n = 50;
+
+    let (nodes, edges) = gen_multi_graph(3, n).unwrap();
+    let mut optimizer = LayoutOptimizer::new(nodes, edges).unwrap();
+    optimizer
+      .optimize(&OptimizeConfig::builder().max_iterations(50).passes(2).build().unwrap())
+      .unwrap();
+    let edge_length = optimizer.edge_length();
+
+    optimizer.set_edge_length_weight(10.).unwrap();
+    let crossings = optimizer
+      .optimize(&OptimizeConfig::builder().max_iterations(50).passes(2).build().unwrap())
+      .unwrap();
+    assert_eq!(crossings, optimizer.count_crossings());
+    assert!(optimizer.edge_length() < edge_length);
+
+    assert!(matches!(
+      optimizer.set_edge_length_weight(-1.),
+      Err(OptimizerError::InvalidWeight { .. })
+    ));
+    assert!(optimizer.set_edge_length_weight(f64::NAN).is_err());
+    assert_eq!(optimizer.optimizer.edge_length_weight, 10.);
+  }
+
+  #[test]
+  fn test_objectives() {
+    let (nodes, edges) = gen_multi_graph(3, 20).unwrap();
+    let labels = nodes
+      .iter()
+      .map(|layer| layer.iter().map(|node| (*node, (*node % 2) as usize)).collect())
+      .collect();
+    let cohesion = GroupCohesion::new(labels);
+
+    let mut optimizer = LayoutOptimizer::new(nodes, edges).unwrap();
+    let cohesion_cost = cohesion.cost(&optimizer.optimizer).unwrap();
+    assert_eq!(optimizer.cost().unwrap(), optimizer.count_crossings() as f64);
+
+    optimizer.add_objective(Box::new(GroupCohesion::new(cohesion.labels.clone())), 100.);
+    let crossings = optimizer
+      .optimize(&OptimizeConfig::builder().max_iterations(50).passes(2).build().unwrap())
+      .unwrap();
+    assert_eq!(crossings, optimizer.count_crossings());
+    assert!(cohesion.cost(&optimizer.optimizer).unwrap() < cohesion_cost);
+    assert_eq!(
+      optimizer.cost().unwrap(),
+      crossings as f64 + 100. * cohesion.cost(&optimizer.optimizer).unwrap()
+    );
+
+    optimizer.clear_objectives();
+    assert_eq!(optimizer.cost().unwrap(), crossings as f64);
+  }
+
+  #[test]
+  fn test_snapshot() {
+    let (nodes, edges) = gen_multi_graph(3, 20).unwrap();
+    let intra_edges = vec![vec![(nodes[0][0], nodes[0][5], 2)], vec![], vec![]];
+    let mut optimizer = LayoutOptimizer::with_intra_edges(nodes.clone(), edges, intra_edges).unwrap();
+    optimizer
+      .set_reference(&nodes, StabilityMetric::Displacement, 0.5)
+      .unwrap();
+    optimizer.set_edge_length_weight(0.1).unwrap();
+    optimizer
+      .optimize(&OptimizeConfig::builder().max_iterations(20).passes(1).build().unwrap())
+      .unwrap();
+
+    let (nodes, bytes) = optimizer.to_snapshot().unwrap();
+    let mut restored = LayoutOptimizer::from_snapshot(nodes.clone(), &bytes).unwrap();
+    assert_eq!(restored.get_nodes(), nodes);
+    assert_eq!(restored.cost().unwrap(), optimizer.cost().unwrap());
+
+    // A copy is independent of the original
+    let mut copy = optimizer.clone();
+    copy.clear_reference();
+    assert!(copy.optimizer.stability.is_none() && optimizer.optimizer.stability.is_some());
+
+    assert!(LayoutOptimizer::from_snapshot(nodes[1..].to_vec(), &bytes).is_err());
+    restored.add_objective(Box::new(GroupCohesion::new(vec![HashMap::new(); 3])), 1.);
+    assert!(matches!(
+      restored.to_snapshot(),
+      Err(OptimizerError::ObjectivesNotSerializable)
+    ));
+  }
+
+  #[test]
+  fn test_interrupt() {
+    let (nodes, edges) = gen_multi_graph(3, 20).unwrap();
+    let mut optimizer = LayoutOptimizer::new(nodes.clone(), edges).unwrap();
+    let interrupt = Arc::new(AtomicBool::new(true));
+    optimizer.set_interrupt(Some(Arc::clone(&interrupt)));
+    assert!(matches!(
+      optimizer.optimize(&OptimizeConfig::builder().max_iterations(20).build().unwrap()),
+      Err(OptimizerError::Interrupted)
+    ));
+    assert_eq!(optimizer.get_nodes(), nodes);
+
+    interrupt.store(false, Ordering::Relaxed);
+    assert!(optimizer
+      .optimize(&OptimizeConfig::builder().max_iterations(20).passes(1).build().unwrap())
+      .is_ok());
+    optimizer.set_interrupt(None);
+    assert!(optimizer.tabu_search(5, 20, 1).is_ok());
+  }
+
+  #[test]
+  fn test_optimize_with() {
+    let (nodes, edges) = gen_multi_graph(4, 50).unwrap();
+    let mut optimizer = LayoutOptimizer::new(nodes, edges).unwrap();
+    let start_crossings = optimizer.count_crossings();
+
+    let barycenter_crossings = optimizer.optimize_with(&[Box::new(Barycenter)], 2).unwrap();
+    assert!(barycenter_crossings < start_crossings);
+
+    let strategies: Vec<Box<dyn LayerStrategy>> = vec![Box::new(Barycenter), Box::new(Annealing::default())];
+    let end_crossings = optimizer.optimize_with(&strategies, 2).unwrap();
+    assert_eq!(end_crossings, optimizer.count_crossings());
+    assert!(end_crossings < barycenter_crossings);
+
+    // Orders that aren't a permutation would drop or duplicate nodes
+    struct Broken;
+    impl LayerStrategy for Broken {
+      fn reorder(&self, problem: &LayerProblem) -> Vec<usize> {
+        vec![0; problem.unit_count()]
+      }
+    }
+    let nodes = optimizer.get_nodes();
+    assert!(matches!(
+      optimizer.apply_strategy(&Broken, 1),
+      Err(OptimizerError::InvalidStrategyOrder {
+        size: 50,
+        unit_count: 50
+      })
+    ));
+    assert_eq!(optimizer.get_nodes(), nodes);
+  }
+
+  #[test]
+  fn test_polish() {
+    let (nodes, edges) = gen_multi_graph(3, 50).unwrap();
+    let mut optimizer = LayoutOptimizer::new(nodes, edges).unwrap();
+    let annealed = optimizer
+      .optimize(
+        &OptimizeConfig::builder()
+          .end_temp(0.5)
+          .steps(2)
+          .max_iterations(10)
+          .passes(1)
+          .build()
+          .unwrap(),
+      )
+      .unwrap();
+    let polished = optimizer.polish(true, true).unwrap();
+    assert!(polished <= annealed);
+
+    // A second polish finds nothing left to improve
+    let nodes = optimizer.get_nodes();
+    assert_eq!(optimizer.polish(false, false).unwrap(), polished);
+    assert_eq!(optimizer.get_nodes(), nodes);
+  }
+  #[test]
+  fn test_solve_exact() {
+    let (nodes, edges) = generate_multipartite_graph(vec![5, 6, 5]).unwrap();
+    let mut annealed = LayoutOptimizer::new(nodes.clone(), edges.clone()).unwrap();
+    let annealed_crossings = annealed
+      .optimize(&OptimizeConfig::builder().max_iterations(100).passes(3).build().unwrap())
+      .unwrap();
+
+    // The exact solution is ground truth for the annealer
+    let mut optimizer = LayoutOptimizer::new(nodes, edges).unwrap();
+    let (crossings, optimal) = optimizer.solve_exact(16, None).unwrap();
+    assert!(optimal);
+    assert_eq!(crossings, optimizer.count_crossings());
+    assert!(crossings <= annealed_crossings);
+
+    assert!(optimizer.solve_exact(15, None).is_err());
+  }
 }
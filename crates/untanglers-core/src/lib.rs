@@ -1,12 +1,27 @@
+pub mod adjacency;
 pub mod aggregation;
+pub mod compound;
+pub mod config;
 pub mod count_crossings;
+pub mod edge_length;
 pub mod error;
+pub mod exact;
+pub mod genetic_optimizer;
+pub mod group_tree;
 pub mod hierarchy;
 pub mod hierarchy_optimizer;
+pub mod intra_arcs;
 pub mod layout_optimizer;
+pub mod local_search;
 pub mod mapping;
+pub mod node_index;
+pub mod objective;
 pub mod optimizer;
 pub mod optimizer_ops;
 pub mod pairwise;
 pub mod reducer;
+pub mod snapshot;
+pub mod stability;
+pub mod strategy;
+pub mod svg;
 pub mod utils;
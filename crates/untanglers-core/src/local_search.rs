@@ -0,0 +1,333 @@
+use std::ops::Range;
+
+use itertools::Itertools;
+
+use crate::strategy::{EnergyTerms, LayerProblem};
+
+/// Upper bound on the sweeps over all layers when polishing a whole graph. Each sweep recomputes the linearised terms,
+/// so unlike the search within a layer the sweeps aren't guaranteed to settle.
+pub const MAX_POLISH_SWEEPS: usize = 100;
+
+/// Moves have to improve the energy by at least this much, so rounding errors can't make the search cycle.
+const MIN_IMPROVEMENT: f64 = 1e-9;
+
+struct Search {
+  order: Vec<usize>,
+  terms: EnergyTerms,
+}
+
+impl Search {
+  fn pair(&self, a: usize, b: usize) -> f64 {
+    self.terms.pair(a, b)
+  }
+
+  /// Reduction in the terms that depend on absolute positions when the units at positions `range` are replaced by
+  /// `new_units`.
+  fn position_delta(&self, range: Range<usize>, new_units: &[usize]) -> f64 {
+    match self.terms.has_position_terms() {
+      true => self.terms.range_delta(&self.order, range, new_units),
+      false => 0.,
+    }
+  }
+
+  /// Swaps neighbouring units as long as that strictly improves the energy.
+  fn greedy_switch(&mut self, segment: &Range<usize>) -> bool {
+    let mut improved = false;
+
+    loop {
+      let mut swapped = false;
+      let mut starts = match self.terms.has_position_terms() {
+        true => self.terms.starts(&self.order),
+        false => vec![],
+      };
+      for i in segment.start..segment.end.saturating_sub(1) {
+        let (a, b) = (self.order[i], self.order[i + 1]);
+        let reduction = match self.terms.has_position_terms() {
+          true => self.terms.swap_delta(&starts, a, b),
+          false => self.pair(a, b),
+        };
+
+        if reduction > MIN_IMPROVEMENT {
+          self.order.swap(i, i + 1);
+          if self.terms.has_position_terms() {
+            self.terms.swap_starts(&mut starts, a, b);
+          }
+          swapped = true;
+        }
+      }
+
+      if !swapped {
+        return improved;
+      }
+      improved = true;
+    }
+  }
+
+  /// Exchanges two units that aren't neighbours, every unit in between changes order with both of them.
+  fn two_opt(&mut self, segment: &Range<usize>) -> bool {
+    let mut improved = false;
+
+    for i in segment.clone() {
+      for j in i + 2..segment.end {
+        let (a, b) = (self.order[i], self.order[j]);
+        let mut reduction = self.pair(a, b);
+        for m in &self.order[i + 1..j] {
+          reduction += self.pair(a, *m) + self.pair(*m, b);
+        }
+
+        if self.terms.has_position_terms() {
+          let mut new_units = self.order[i..=j].to_vec();
+          new_units.swap(0, j - i);
+          reduction += self.position_delta(i..j + 1, &new_units);
+        }
+
+        if reduction > MIN_IMPROVEMENT {
+          self.order.swap(i, j);
+          improved = true;
+        }
+      }
+    }
+
+    improved
+  }
+
+  /// Reverses a run of at least three units, which inverts every pair inside it.
+  fn block_reversal(&mut self, segment: &Range<usize>) -> bool {
+    let mut improved = false;
+
+    for i in segment.clone() {
+      let mut reduction = 0.;
+      let mut j = i + 1;
+      while j < segment.end {
+        let b = self.order[j];
+        reduction += self.order[i..j].iter().map(|a| self.pair(*a, b)).sum::<f64>();
+
+        let mut total = reduction;
+        if j >= i + 2 && self.terms.has_position_terms() {
+          let new_units = self.order[i..=j].iter().rev().copied().collect_vec();
+          total += self.position_delta(i..j + 1, &new_units);
+        }
+
+        if j >= i + 2 && total > MIN_IMPROVEMENT {
+          self.order[i..=j].reverse();
+          improved = true;
+          reduction = 0.;
+          j = i + 1;
+          continue;
+        }
+        j += 1;
+      }
+    }
+
+    improved
+  }
+}
+
+/**
+ * Deterministic local search over the units of a layer. Neighbouring units are swapped while that strictly improves
+ * the energy, optionally followed by exchanging any two units (2-opt) and reversing runs of units, repeated until no
+ * improving move remains. Moves never cross the borders of the problem.
+ *
+ * The pairwise matrix is computed once, so for terms that are linearised around the starting order (e.g. group
+ * cohesion) the search minimises that linearisation. Returns the new order as indices into the units.
+ */
+pub fn local_search(problem: &LayerProblem, two_opt: bool, block_reversal: bool) -> Vec<usize> {
+  let mut search = Search {
+    order: (0..problem.unit_count()).collect(),
+    terms: problem.energy_terms(),
+  };
+
+  for segment in problem.segments() {
+    loop {
+      let mut improved = search.greedy_switch(&segment);
+      if two_opt {
+        improved |= search.two_opt(&segment);
+      }
+      if block_reversal {
+        improved |= search.block_reversal(&segment);
+      }
+
+      // The greedy pass runs until it is stuck, so only the larger moves can open up new improvements
+      if !(two_opt || block_reversal) || !improved {
+        break;
+      }
+    }
+  }
+
+  search.order
+}
+
+/**
+ * Tabu search over swaps of neighbouring units, which needs no temperature. Every iteration performs the best swap
+ * that isn't tabu, even if it makes the layout worse, so the search can walk out of local minima. A pair of units that
+ * was just swapped stays tabu for `tenure` iterations so the search doesn't immediately undo the move, unless swapping
+ * it again would give a new best layout (aspiration). Moves never cross the borders of the problem.
+ *
+ * Returns the best order found as indices into the units and its energy.
+ */
+pub fn tabu_search(problem: &LayerProblem, tenure: usize, max_iterations: usize) -> (Vec<usize>, f64) {
+  let unit_count = problem.unit_count();
+  let mut search = Search {
+    order: (0..unit_count).collect(),
+    terms: problem.energy_terms(),
+  };
+  let mut energy = search.terms.energy;
+
+  let positions = problem
+    .segments()
+    .into_iter()
+    .flat_map(|segment| segment.start..segment.end.saturating_sub(1))
+    .collect_vec();
+  let mut tabu_until = vec![0; unit_count * unit_count];
+  let mut starts = match search.terms.has_position_terms() {
+    true => search.terms.starts(&search.order),
+    false => vec![],
+  };
+  let (mut best_order, mut best_energy) = (search.order.clone(), energy);
+
+  for iteration in 1..=max_iterations {
+    if problem.interrupted() {
+      break;
+    }
+    let mut best_move: Option<(usize, f64)> = None;
+
+    for i in &positions {
+      let (a, b) = (search.order[*i], search.order[*i + 1]);
+      let reduction = match search.terms.has_position_terms() {
+        true => search.terms.swap_delta(&starts, a, b),
+        false => search.pair(a, b),
+      };
+
+      let tabu = tabu_until[a * unit_count + b] >= iteration;
+      let aspiration = energy - reduction < best_energy - MIN_IMPROVEMENT;
+      if (!tabu || aspiration) && best_move.is_none_or(|(_, best)| reduction > best) {
+        best_move = Some((*i, reduction));
+      }
+    }
+
+    let Some((i, reduction)) = best_move else { break };
+    let (a, b) = (search.order[i], search.order[i + 1]);
+    search.order.swap(i, i + 1);
+    if search.terms.has_position_terms() {
+      search.terms.swap_starts(&mut starts, a, b);
+    }
+    energy -= reduction;
+    tabu_until[a * unit_count + b] = iteration + tenure;
+    tabu_until[b * unit_count + a] = iteration + tenure;
+
+    if energy < best_energy - MIN_IMPROVEMENT {
+      best_order.clone_from(&search.order);
+      best_energy = energy;
+    }
+  }
+
+  (best_order, best_energy)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::count_crossings::count_crossings;
+  use crate::intra_arcs::IntraArcs;
+  use crate::mapping::reorder_nodes;
+  use crate::utils::generate_bipartite_graph;
+
+  #[test]
+  fn test_greedy_switch() {
+    // Swapping 1 and 2 is the only improving move
+    let problem = LayerProblem::new(&[1, 2, 3], &[4, 5, 6], &[(1, 5, 1), (2, 4, 1), (3, 6, 1)]);
+    assert_eq!(local_search(&problem, false, false), vec![1, 0, 2]);
+
+    let problem = LayerProblem {
+      borders: Some(vec![0]),
+      ..problem
+    };
+    assert_eq!(local_search(&problem, false, false), vec![0, 1, 2]);
+  }
+
+  #[test]
+  fn test_larger_moves() {
+    // No single swap of neighbours improves, but moving 2 in front of 0 does
+    #[rustfmt::skip]
+    let matrix = vec![
+      0., -1., 5.,
+      1., 0., -1.,
+      -5., 1., 0.,
+    ];
+    let problem = LayerProblem {
+      penalty: Some((matrix, 0.)),
+      ..LayerProblem::new::<i32>(&[1, 2, 3], &[], &[])
+    };
+    assert_eq!(local_search(&problem, false, false), vec![0, 1, 2]);
+    assert_eq!(local_search(&problem, true, false), vec![1, 2, 0]);
+    assert_eq!(local_search(&problem, false, true), vec![1, 2, 0]);
+
+    // With edge length the result is still a local optimum for swaps of neighbours
+    let (nodes_left, nodes_right, edges) = generate_bipartite_graph(40);
+    let problem = LayerProblem {
+      edge_length_weight: 0.5,
+      ..LayerProblem::new(&nodes_left, &nodes_right, &edges)
+    };
+    let edge_length = problem.energy_terms().edge_length.unwrap();
+    let energy = |order: &[usize]| {
+      count_crossings(&reorder_nodes(&nodes_left, order), &nodes_right, &edges) as f64 + edge_length.cost(order)
+    };
+
+    for (two_opt, block_reversal) in [(false, false), (true, true)] {
+      let order = local_search(&problem, two_opt, block_reversal);
+      assert!(energy(&order) < energy(&(0..nodes_left.len()).collect::<Vec<_>>()));
+      for i in 0..order.len() - 1 {
+        let mut swapped = order.clone();
+        swapped.swap(i, i + 1);
+        assert!(energy(&swapped) >= energy(&order) - 1e-9);
+      }
+    }
+  }
+
+  #[test]
+  fn test_intra_edges() {
+    // Swapping 1 and 2 uncrosses the arcs, after which swapping 4 and 5 would cross them again
+    let nodes: Vec<u8> = vec![0, 1, 2, 3, 4, 5];
+    let mut problem = LayerProblem::new(&nodes, &[], &[]);
+    problem.intra_edges = vec![(2, 5, 1), (1, 4, 1)];
+    let intra_arcs = IntraArcs::new(6, &problem.intra_edges, None);
+
+    let order = local_search(&problem, true, true);
+    assert_eq!(intra_arcs.cost(&order), 0.);
+
+    let (order, energy) = tabu_search(&problem, 2, 20);
+    assert_eq!(energy, 0.);
+    assert_eq!(intra_arcs.cost(&order), 0.);
+  }
+
+  #[test]
+  fn test_tabu_search() {
+    // Greedy swaps get stuck on the matrix from test_larger_moves, tabu search walks through the worse layouts
+    #[rustfmt::skip]
+    let matrix = vec![
+      0., -1., 5.,
+      1., 0., -1.,
+      -5., 1., 0.,
+    ];
+    let problem = LayerProblem {
+      penalty: Some((matrix, 4.)),
+      ..LayerProblem::new::<i32>(&[1, 2, 3], &[], &[])
+    };
+    let (order, energy) = tabu_search(&problem, 2, 10);
+    assert_eq!(order, vec![1, 2, 0]);
+    assert_eq!(energy, 0.);
+
+    let (nodes_left, nodes_right, edges) = generate_bipartite_graph(40);
+    let problem = LayerProblem::new(&nodes_left, &nodes_right, &edges);
+    let (order, energy) = tabu_search(&problem, 7, 500);
+    let crossings = count_crossings(&reorder_nodes(&nodes_left, &order), &nodes_right, &edges);
+    assert_eq!(energy, crossings as f64);
+    assert!(crossings < count_crossings(&nodes_left, &nodes_right, &edges));
+
+    let problem = LayerProblem {
+      borders: Some((0..40).collect()),
+      ..problem
+    };
+    assert_eq!(tabu_search(&problem, 7, 500).0, (0..40).collect::<Vec<_>>());
+  }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/**
+ * Interns the nodes of a layer to dense ids, so the optimizers work on indices and only need `Eq + Hash + Clone` from
+ * the node type. Ids are never reused: a node keeps its id after it is removed from the layer and gets it back when it
+ * is added again.
+ */
+#[derive(Clone, Debug)]
+pub struct NodeIndex<T>
+where
+  T: Eq + Hash + Clone,
+{
+  nodes: Vec<T>,
+  ids: HashMap<T, usize>,
+}
+
+impl<T> Default for NodeIndex<T>
+where
+  T: Eq + Hash + Clone,
+{
+  fn default() -> Self {
+    Self {
+      nodes: vec![],
+      ids: HashMap::new(),
+    }
+  }
+}
+
+impl<T> NodeIndex<T>
+where
+  T: Eq + Hash + Clone,
+{
+  pub fn id(&self, node: &T) -> Option<usize> {
+    self.ids.get(node).copied()
+  }
+
+  /// The id of the node, which is added if it wasn't interned before.
+  pub fn intern(&mut self, node: &T) -> usize {
+    if let Some(id) = self.id(node) {
+      return id;
+    }
+    self.nodes.push(node.clone());
+    self.ids.insert(node.clone(), self.nodes.len() - 1);
+    self.nodes.len() - 1
+  }
+
+  pub fn node(&self, id: usize) -> &T {
+    &self.nodes[id]
+  }
+
+  /// The number of interned nodes, including those that are no longer part of the layer.
+  pub fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_intern() {
+    let mut index = NodeIndex::default();
+    assert_eq!(index.intern(&"b"), 0);
+    assert_eq!(index.intern(&"a"), 1);
+    assert_eq!(index.intern(&"b"), 0);
+
+    assert_eq!(index.id(&"a"), Some(1));
+    assert_eq!(index.id(&"c"), None);
+    assert_eq!(*index.node(0), "b");
+    assert_eq!(index.len(), 2);
+  }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::count_crossings::{_count_crossings, _count_intra_crossings};
+use crate::error::OptimizerError;
+use crate::optimizer::Optimizer;
+use crate::pairwise::get_intra_pairwise_matrix;
+use crate::reducer::matrix_and_count;
+
+/**
+ * A term of the cost that is minimised when reordering layers.
+ *
+ * For a layer an objective provides a pairwise matrix in the same form as `pairwise::get_pairwise_matrix`, i.e. entry
+ * [A, B] is the reduction in cost when swapping A and B given that A comes first, together with the cost of the
+ * current order. Terms that can't be decomposed into pairs exactly may return a linearisation around the current
+ * order, the optimizers recount the crossings afterwards so the reported counts stay exact.
+ *
+ * The optimizers minimise the crossings plus a weighted sum of objectives, see `OptimizerOps::add_objective`.
+ */
+pub trait Objective<T>
+where
+  T: Eq + Hash + Clone,
+{
+  fn layer_matrix_and_cost(
+    &self,
+    optimizer: &Optimizer<T>,
+    layer_index: usize,
+  ) -> Result<(Vec<f64>, f64), OptimizerError>;
+
+  /// Total cost of the current layout. The default sums the layer costs, which is only correct for terms that don't
+  /// span multiple layers.
+  fn cost(&self, optimizer: &Optimizer<T>) -> Result<f64, OptimizerError> {
+    (0..optimizer.node_layers.len())
+      .map(|layer_index| Ok(self.layer_matrix_and_cost(optimizer, layer_index)?.1))
+      .sum()
+  }
+
+  /// Whether the matrix is exact for swaps of neighbours in any order, not just around the current one. Only then the
+  /// optimizers can treat the energy as exact, see `strategy::EnergyTerms::exact`.
+  fn exact(&self) -> bool {
+    false
+  }
+}
+
+/// Weighted crossings between a layer and its neighbours. These are always part of the objective, adding this term
+/// again only increases their weight.
+pub struct Crossings;
+
+impl<T> Objective<T> for Crossings
+where
+  T: Eq + Hash + Clone,
+{
+  fn layer_matrix_and_cost(
+    &self,
+    optimizer: &Optimizer<T>,
+    layer_index: usize,
+  ) -> Result<(Vec<f64>, f64), OptimizerError> {
+    let static_layers = optimizer.get_adjacent_layers(layer_index)?;
+    let (count, matrix) = matrix_and_count(optimizer.node_layers[layer_index].len(), &static_layers);
+    Ok((matrix, count as f64))
+  }
+
+  fn cost(&self, optimizer: &Optimizer<T>) -> Result<f64, OptimizerError> {
+    Ok(
+      (0..optimizer.edges.len())
+        .map(|i| _count_crossings(optimizer.node_layers[i + 1].len(), &optimizer.mapped_edges(i)))
+        .sum::<usize>() as f64,
+    )
+  }
+
+  fn exact(&self) -> bool {
+    true
+  }
+}
+
+/// Crossings between the intra-layer edges of a layer, linearised around the current order. The optimizers already
+/// evaluate these exactly per move, see `intra_arcs::IntraArcs`, so this is mostly useful to weigh them more.
+pub struct IntraCrossings;
+
+impl<T> Objective<T> for IntraCrossings
+where
+  T: Eq + Hash + Clone,
+{
+  fn layer_matrix_and_cost(
+    &self,
+    optimizer: &Optimizer<T>,
+    layer_index: usize,
+  ) -> Result<(Vec<f64>, f64), OptimizerError> {
+    let mapped_edges = optimizer.mapped_intra_edges(layer_index);
+
+    Ok((
+      get_intra_pairwise_matrix(optimizer.node_layers[layer_index].len(), &mapped_edges),
+      _count_intra_crossings(&mapped_edges) as f64,
+    ))
+  }
+}
+
+/**
+ * Keeps nodes with the same label together. The cost of a layer is the summed distance between all pairs of nodes with
+ * the same label, minus the smallest possible sum, so it is 0 exactly when every label forms a contiguous block.
+ *
+ * The matrix is linearised around the current order and is exact for neighbouring nodes: moving A one step towards
+ * the end brings it closer to the nodes of its label after it and further from those before it. Nodes without a label
+ * are free to move.
+ */
+pub struct GroupCohesion<T>
+where
+  T: Eq + Hash + Clone,
+{
+  pub labels: Vec<HashMap<T, usize>>,
+}
+
+impl<T> GroupCohesion<T>
+where
+  T: Eq + Hash + Clone,
+{
+  /// * `labels` For each layer a mapping from node to an arbitrary group label
+  pub fn new(labels: Vec<HashMap<T, usize>>) -> Self {
+    Self { labels }
+  }
+}
+
+impl<T> Objective<T> for GroupCohesion<T>
+where
+  T: Eq + Hash + Clone,
+{
+  fn layer_matrix_and_cost(
+    &self,
+    optimizer: &Optimizer<T>,
+    layer_index: usize,
+  ) -> Result<(Vec<f64>, f64), OptimizerError> {
+    let nodes = &optimizer.node_layers[layer_index];
+    let node_count = nodes.len();
+    let labels = nodes
+      .iter()
+      .map(|id| {
+        let node = optimizer.node(layer_index, *id);
+        self.labels.get(layer_index).and_then(|l| l.get(node)).copied()
+      })
+      .collect::<Vec<_>>();
+
+    // For each node the number of nodes with the same label before it, and the positions of each label
+    let mut positions = HashMap::<usize, Vec<usize>>::new();
+    let mut before = vec![0; node_count];
+    for (i, label) in labels.iter().enumerate() {
+      if let Some(label) = label {
+        let label_positions = positions.entry(*label).or_default();
+        before[i] = label_positions.len();
+        label_positions.push(i);
+      }
+    }
+
+    let mut cost = 0.;
+    for label_positions in positions.values() {
+      let size = label_positions.len();
+      for (k, position) in label_positions.iter().enumerate() {
+        // Every position is added once for each member before it and subtracted once for each member after it
+        cost += (2 * k) as f64 * *position as f64 - (size - 1) as f64 * *position as f64;
+      }
+      cost -= ((size - 1) * size * (size + 1)) as f64 / 6.;
+    }
+
+    // Gain of moving a node one step towards the end: it gets closer to the members after it
+    let gain = |i: usize| match labels[i] {
+      Some(label) => positions[&label].len() as f64 - 1. - 2. * before[i] as f64,
+      None => 0.,
+    };
+    let gains = (0..node_count).map(gain).collect::<Vec<_>>();
+
+    let mut matrix = vec![0.; node_count * node_count];
+    for a in 0..node_count {
+      for b in 0..node_count {
+        if a != b && (labels[a].is_none() || labels[a] != labels[b]) {
+          matrix[a * node_count + b] = gains[a] - gains[b];
+        }
+      }
+    }
+
+    Ok((matrix, cost))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mapping::reorder_nodes;
+  use crate::utils::validate_layers;
+
+  fn cohesion_cost(objective: &GroupCohesion<i32>, nodes: Vec<i32>) -> (Vec<f64>, f64) {
+    let optimizer = Optimizer::new(vec![nodes], vec![]);
+    objective.layer_matrix_and_cost(&optimizer, 0).unwrap()
+  }
+
+  #[test]
+  fn test_crossings() {
+    let optimizer = Optimizer::new(
+      vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8]],
+      vec![vec![(1, 6, 1), (3, 4, 2)], vec![(4, 8, 1), (6, 7, 1)]],
+    );
+    validate_layers(&optimizer.node_layers, &optimizer.edge_lists().0).unwrap();
+
+    let (matrix, count) = Crossings.layer_matrix_and_cost(&optimizer, 1).unwrap();
+    assert_eq!(count, 3.);
+    assert_eq!(matrix[2], 3.);
+    assert_eq!(Objective::<i32>::cost(&Crossings, &optimizer).unwrap(), 3.);
+  }
+
+  #[test]
+  fn test_group_cohesion() {
+    let labels = HashMap::from([(0, 0), (1, 1), (2, 0), (3, 1), (4, 0)]);
+    let objective = GroupCohesion::new(vec![labels]);
+
+    assert_eq!(cohesion_cost(&objective, vec![0, 2, 4, 1, 3, 5]).1, 0.);
+    assert_eq!(cohesion_cost(&objective, vec![0, 2, 5, 4, 1, 3]).1, 2.);
+
+    // Exact for neighbouring swaps
+    let nodes = vec![0, 1, 5, 2, 3, 4];
+    let (matrix, cost) = cohesion_cost(&objective, nodes.clone());
+    assert_eq!(cost, 6. + 2.);
+    for i in 0..nodes.len() - 1 {
+      let mut indices = (0..nodes.len()).collect::<Vec<_>>();
+      indices.swap(i, i + 1);
+      let (_, new_cost) = cohesion_cost(&objective, reorder_nodes(&nodes, &indices));
+      assert_eq!(matrix[i * nodes.len() + i + 1], cost - new_cost, "swap {i}");
+    }
+  }
+}
@@ -1,99 +1,471 @@
-use std::fmt::{Debug, Display};
 use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use itertools::Itertools;
 
-use crate::count_crossings::count_crossings;
+use crate::adjacency::Adjacency;
+use crate::count_crossings::{_count_crossings, _count_intra_crossings};
+use crate::edge_length::centred_position;
 use crate::error::OptimizerError;
-use crate::mapping::swap_edges;
+use crate::node_index::NodeIndex;
+use crate::objective::Objective;
+use crate::stability::{Stability, StabilityMetric};
+use crate::strategy::{LayerProblem, MappedEdges};
+use crate::utils::{add_matrix, validate_intra_edges, validate_weight};
 
+/// Edges as (id in the first layer, id in the second layer, weight), see `NodeIndex`
+pub type IdEdges = Vec<(usize, usize, usize)>;
+
+/**
+ * The layers and edges that all optimizers share. Nodes are interned per layer once and everything is stored by node
+ * id, with the edges as `Adjacency`. The node type only appears at the boundary: when the graph is built or edited and
+ * in `get_nodes`.
+ */
+#[derive(Clone)]
 pub struct Optimizer<T>
 where
-  T: Eq + Hash + Clone + Display + Debug,
+  T: Eq + Hash + Clone,
 {
-  pub node_layers: Vec<Vec<T>>,
-  pub edges: Vec<Vec<(T, T, usize)>>,
-  pub inverted_edges: Vec<Vec<(T, T, usize)>>,
+  /// The ids of the nodes of each layer
+  pub nodes: Vec<NodeIndex<T>>,
+  /// Node ids of each layer in their current order
+  pub node_layers: Vec<Vec<usize>>,
+  /// Edges from each layer to the next one
+  pub edges: Vec<Adjacency>,
+  /// The same edges from each layer to the previous one
+  pub inverted_edges: Vec<Adjacency>,
+  /// Edges between nodes of the same layer, one adjacency per layer
+  pub intra_edges: Vec<Adjacency>,
+  pub stability: Option<Stability<usize>>,
+  /// Weight of the total vertical edge length relative to the crossings, 0 disables it
+  pub edge_length_weight: f64,
+  /// Extra terms that are minimised along with the crossings, with their weights
+  /// Objectives never change once added, so copies of the optimizer share them
+  pub objectives: Vec<(Arc<dyn Objective<T> + Send + Sync>, f64)>,
+  /// Stops running optimizations once set, see `set_interrupt`
+  pub interrupt: Option<Arc<AtomicBool>>,
 }
 
 impl<T> Optimizer<T>
 where
-  T: Eq + Hash + Clone + Display + Debug,
+  T: Eq + Hash + Clone,
 {
+  /// Interns the nodes of every layer. The layers and edges have to be valid, see `utils::validate_layers`.
   pub fn new(node_layers: Vec<Vec<T>>, edges: Vec<Vec<(T, T, usize)>>) -> Self {
-    let inverted_edges = edges.iter().map(|e| swap_edges(e)).collect_vec();
+    let mut nodes = vec![NodeIndex::default(); node_layers.len()];
+    let node_layers = node_layers
+      .iter()
+      .zip(&mut nodes)
+      .map(|(layer, index)| layer.iter().map(|node| index.intern(node)).collect_vec())
+      .collect_vec();
+    let intra_edges = nodes.iter().map(|index| Adjacency::new(index.len(), &[])).collect();
 
-    Self {
+    let mut optimizer = Self {
+      nodes,
       node_layers,
-      edges,
-      inverted_edges,
+      edges: vec![],
+      inverted_edges: vec![],
+      intra_edges,
+      stability: None,
+      edge_length_weight: 0.,
+      objectives: vec![],
+      interrupt: None,
+    };
+    optimizer.edges = (0..edges.len())
+      .map(|i| optimizer.adjacency(i, i + 1, &edges[i]))
+      .collect();
+    optimizer.inverted_edges = (0..edges.len())
+      .map(|i| optimizer.edges[i].inverted(optimizer.nodes[i + 1].len()))
+      .collect();
+    optimizer
+  }
+
+  /// The node with the given id in a layer.
+  pub fn node(&self, layer_index: usize, id: usize) -> &T {
+    self.nodes[layer_index].node(id)
+  }
+
+  /// Replaces the nodes of validated edges by their ids.
+  fn adjacency(&self, layer_a: usize, layer_b: usize, edges: &[(T, T, usize)]) -> Adjacency {
+    let (index_a, index_b) = (&self.nodes[layer_a], &self.nodes[layer_b]);
+    let edges = edges
+      .iter()
+      .map(|(a, b, w)| (index_a.id(a).unwrap(), index_b.id(b).unwrap(), *w))
+      .collect_vec();
+    Adjacency::new(index_a.len(), &edges)
+  }
+
+  /// The position of every node id of a layer, ids that are not part of the layer map to `usize::MAX`.
+  pub fn positions(&self, layer_index: usize) -> Vec<usize> {
+    let mut positions = vec![usize::MAX; self.nodes[layer_index].len()];
+    for (position, id) in self.node_layers[layer_index].iter().enumerate() {
+      positions[*id] = position;
     }
+    positions
+  }
+
+  /// The edges from a layer to the next one, with both ends replaced by their position.
+  pub fn mapped_edges(&self, layer_index: usize) -> MappedEdges {
+    self.edges[layer_index].map(&self.node_layers[layer_index], &self.positions(layer_index + 1))
+  }
+
+  /// The edges within a layer, with both ends replaced by their position.
+  pub fn mapped_intra_edges(&self, layer_index: usize) -> MappedEdges {
+    self.intra_edges[layer_index].map(&self.node_layers[layer_index], &self.positions(layer_index))
+  }
+
+  /// Replaces the edges between nodes in the same layer, one list per layer.
+  pub fn set_intra_edges(&mut self, intra_edges: Vec<Vec<(T, T, usize)>>) -> Result<(), OptimizerError> {
+    validate_intra_edges(&self.get_nodes(), &intra_edges)?;
+    self.intra_edges = intra_edges
+      .iter()
+      .enumerate()
+      .map(|(i, layer_edges)| self.adjacency(i, i, layer_edges))
+      .collect();
+    Ok(())
   }
 
   pub fn count_layer_crossings(&self, layer_index: usize) -> Result<usize, OptimizerError> {
+    self.validate_layer_index(layer_index, self.node_layers.len())?;
+
     let mut crossing_count = 0;
     if layer_index < self.edges.len() {
-      crossing_count += count_crossings(
-        &self.node_layers[layer_index],
-        &self.node_layers[layer_index + 1],
-        &self.edges[layer_index],
-      );
-
-      if layer_index > 0 {
-        crossing_count += count_crossings(
-          &self.node_layers[layer_index - 1],
-          &self.node_layers[layer_index],
-          &self.edges[layer_index - 1],
-        );
-      }
+      crossing_count += _count_crossings(self.node_layers[layer_index + 1].len(), &self.mapped_edges(layer_index));
     }
 
+    if layer_index > 0 {
+      crossing_count += _count_crossings(self.node_layers[layer_index].len(), &self.mapped_edges(layer_index - 1));
+    }
+
+    crossing_count += _count_intra_crossings(&self.mapped_intra_edges(layer_index));
+
     Ok(crossing_count)
   }
 
   pub fn count_crossings(&self) -> usize {
     let mut total_count = 0;
 
-    for i in 0..self.node_layers.len() - 1 {
-      total_count += count_crossings(&self.node_layers[i], &self.node_layers[i + 1], &self.edges[i])
+    for i in 0..self.edges.len() {
+      total_count += _count_crossings(self.node_layers[i + 1].len(), &self.mapped_edges(i));
+    }
+
+    for i in 0..self.node_layers.len() {
+      total_count += _count_intra_crossings(&self.mapped_intra_edges(i));
     }
 
     total_count
   }
 
-  #[allow(clippy::type_complexity)]
-  pub fn get_adjacent_layers(
+  /// For the previous and the next layer, if they exist, the node count and the edges from the given layer to it, with
+  /// the nodes of both layers replaced by their position.
+  pub fn get_adjacent_layers(&self, layer_index: usize) -> Result<Vec<(usize, MappedEdges)>, OptimizerError> {
+    self.validate_layer_index(layer_index, self.node_layers.len())?;
+
+    let order = &self.node_layers[layer_index];
+    let mut adjacent_layers = vec![];
+    if layer_index > 0 {
+      let edges = self.inverted_edges[layer_index - 1].map(order, &self.positions(layer_index - 1));
+      adjacent_layers.push((self.node_layers[layer_index - 1].len(), edges));
+    }
+    if layer_index < self.edges.len() {
+      let edges = self.edges[layer_index].map(order, &self.positions(layer_index + 1));
+      adjacent_layers.push((self.node_layers[layer_index + 1].len(), edges));
+    }
+    Ok(adjacent_layers)
+  }
+
+  /// The edges between neighbouring layers and within each layer as plain lists.
+  pub fn edge_lists(&self) -> (Vec<IdEdges>, Vec<IdEdges>) {
+    let lists = |adjacencies: &[Adjacency]| adjacencies.iter().map(|a| a.edges().collect()).collect();
+    (lists(&self.edges), lists(&self.intra_edges))
+  }
+
+  /// The nodes of a layer in their current order.
+  pub fn layer_nodes(&self, layer_index: usize) -> Vec<T> {
+    self.node_layers[layer_index]
+      .iter()
+      .map(|id| self.node(layer_index, *id).clone())
+      .collect()
+  }
+
+  pub fn get_nodes(&self) -> Vec<Vec<T>> {
+    (0..self.node_layers.len()).map(|i| self.layer_nodes(i)).collect()
+  }
+
+  /// Penalises displacement from `reference` in all subsequent optimisation steps.
+  pub fn set_reference(
+    &mut self,
+    reference: &[Vec<T>],
+    metric: StabilityMetric,
+    weight: f64,
+  ) -> Result<(), OptimizerError> {
+    if reference.len() != self.node_layers.len() {
+      return Err(OptimizerError::ReferenceMismatch {
+        reference: reference.len(),
+        layers: self.node_layers.len(),
+      });
+    }
+
+    self.set_stability(Some(Stability::new(reference, metric, weight)?));
+    Ok(())
+  }
+
+  /// Sets the stability term with its reference in terms of nodes. Nodes of the reference that are not part of the
+  /// graph are interned as well, so they are ranked once they are added.
+  pub(crate) fn set_stability(&mut self, stability: Option<Stability<T>>) {
+    self.stability = stability.map(|stability| Stability {
+      reference: stability
+        .reference
+        .iter()
+        .zip(&mut self.nodes)
+        .map(|(ranks, index)| ranks.iter().map(|(node, rank)| (index.intern(node), *rank)).collect())
+        .collect(),
+      metric: stability.metric,
+      weight: stability.weight,
+    });
+  }
+
+  pub fn clear_reference(&mut self) {
+    self.stability = None;
+  }
+
+  /// Also penalises long edges in all subsequent optimisation steps, see `edge_length::EdgeLength`.
+  pub fn set_edge_length_weight(&mut self, weight: f64) -> Result<(), OptimizerError> {
+    validate_weight("edge length", weight)?;
+    self.edge_length_weight = weight;
+    Ok(())
+  }
+
+  /// Total weighted vertical edge length Σ w * |y_a - y_b|, with layers centred on each other and unit node spacing.
+  pub fn edge_length(&self) -> f64 {
+    let mut total = 0.;
+
+    for i in 0..self.edges.len() {
+      let (count1, count2) = (self.node_layers[i].len(), self.node_layers[i + 1].len());
+      for (a, b, w) in self.mapped_edges(i) {
+        total += w as f64 * (centred_position(a, count1) - centred_position(b, count2)).abs();
+      }
+    }
+
+    total
+  }
+
+  /// Adds `weight * objective` to the cost that is minimised in all subsequent optimisation steps.
+  pub fn add_objective(&mut self, objective: Box<dyn Objective<T> + Send + Sync>, weight: f64) {
+    self.objectives.push((objective.into(), weight));
+  }
+
+  pub fn clear_objectives(&mut self) {
+    self.objectives.clear();
+  }
+
+  /// The full cost that is minimised: crossings, the stability penalty, the edge length and the added objectives.
+  pub fn cost(&self) -> Result<f64, OptimizerError> {
+    let mut cost = self.count_crossings() as f64 + self.edge_length_weight * self.edge_length();
+    if let Some(stability) = &self.stability {
+      cost += stability.cost(self)?;
+    }
+    for (objective, weight) in &self.objectives {
+      cost += weight * objective.cost(self)?;
+    }
+    Ok(cost)
+  }
+
+  /// All terms besides the crossings with the adjacent layers that should be folded into the pairwise matrix when
+  /// reordering a layer, i.e. the stability penalty and the added objectives. The intra-layer crossings depend on
+  /// absolute positions, see `LayerProblem::intra_edges`.
+  pub fn layer_penalty(&self, layer_index: usize) -> Result<Option<(Vec<f64>, f64)>, OptimizerError> {
+    let node_count = self.node_layers[layer_index].len();
+    let mut terms: Vec<(Vec<f64>, f64)> = vec![];
+
+    if let Some(stability) = &self.stability {
+      terms.push(stability.layer_matrix_and_cost(self, layer_index)?);
+    }
+    for (objective, weight) in &self.objectives {
+      let (matrix, cost) = objective.layer_matrix_and_cost(self, layer_index)?;
+      if matrix.len() != node_count * node_count {
+        return Err(OptimizerError::ObjectiveSizeMismatch {
+          layer_index,
+          size: matrix.len(),
+          node_count,
+        });
+      }
+      terms.push((matrix.into_iter().map(|x| weight * x).collect(), weight * cost));
+    }
+
+    Ok(
+      terms
+        .into_iter()
+        .reduce(|(matrix1, cost1), (matrix2, cost2)| (add_matrix(&matrix1, &matrix2), cost1 + cost2)),
+    )
+  }
+
+  /**
+   * Sets a flag that another thread, e.g. a signal handler, can set to stop running optimizations. The layer that is
+   * being reordered keeps the best order found so far and the optimization returns `OptimizerError::Interrupted`, so
+   * the layout stays valid. Copies of the optimizer share the flag.
+   */
+  pub fn set_interrupt(&mut self, interrupt: Option<Arc<AtomicBool>>) {
+    self.interrupt = interrupt;
+  }
+
+  pub fn check_interrupt(&self) -> Result<(), OptimizerError> {
+    match &self.interrupt {
+      Some(interrupt) if interrupt.load(Ordering::Relaxed) => Err(OptimizerError::Interrupted),
+      _ => Ok(()),
+    }
+  }
+
+  /// Collects everything a `LayerStrategy` needs to reorder a layer.
+  pub fn layer_problem(
     &self,
     layer_index: usize,
-  ) -> Result<(&[T], &[(T, T, usize)], Option<&Vec<T>>, Option<&Vec<(T, T, usize)>>), OptimizerError> {
-    if layer_index >= self.node_layers.len() {
+    groups: Option<Vec<usize>>,
+    borders: Option<Vec<usize>>,
+  ) -> Result<LayerProblem, OptimizerError> {
+    Ok(LayerProblem {
+      node_count: self.node_layers[layer_index].len(),
+      static_layers: self.get_adjacent_layers(layer_index)?,
+      groups,
+      borders,
+      edge_length_weight: self.edge_length_weight,
+      penalty: self.layer_penalty(layer_index)?,
+      penalty_exact: self.objectives.iter().all(|(objective, _)| objective.exact()),
+      intra_edges: self.mapped_intra_edges(layer_index),
+      interrupt: self.interrupt.clone(),
+    })
+  }
+
+  fn validate_layer_index(&self, layer_index: usize, layer_count: usize) -> Result<(), OptimizerError> {
+    if layer_index >= layer_count {
       return Err(OptimizerError::InvalidLayer {
         layer_index,
-        layer_count: self.node_layers.len(),
+        layer_count,
       });
     }
+    Ok(())
+  }
 
-    Ok(if layer_index == 0 {
-      (&self.node_layers[layer_index + 1], &self.edges[layer_index], None, None)
-    } else if layer_index == self.node_layers.len() - 1 {
-      (
-        &self.node_layers[layer_index - 1],
-        &self.inverted_edges[layer_index - 1],
-        None,
-        None,
-      )
-    } else {
-      (
-        &self.node_layers[layer_index - 1],
-        &self.inverted_edges[layer_index - 1],
-        Some(&self.node_layers[layer_index + 1]),
-        Some(&self.edges[layer_index]),
-      )
-    })
+  /// The id of a node and its position in its layer.
+  pub(crate) fn find_node(&self, layer_index: usize, node: &T) -> Result<(usize, usize), OptimizerError> {
+    self.validate_layer_index(layer_index, self.node_layers.len())?;
+    self.nodes[layer_index]
+      .id(node)
+      .and_then(|id| Some((id, self.node_layers[layer_index].iter().position(|n| *n == id)?)))
+      .ok_or(OptimizerError::UnknownNode {
+        node: None,
+        layer_index,
+      })
   }
 
-  pub fn get_nodes(&self) -> Vec<Vec<T>> {
-    self.node_layers.clone()
+  /// The ids of the ends of an edge from layer `layer_index` to the next one.
+  fn find_edge(&self, layer_index: usize, node_a: &T, node_b: &T) -> Result<(usize, usize), OptimizerError> {
+    self.validate_layer_index(layer_index, self.edges.len())?;
+    self.nodes[layer_index]
+      .id(node_a)
+      .zip(self.nodes[layer_index + 1].id(node_b))
+      .filter(|(id_a, id_b)| self.edges[layer_index].neighbours(*id_a).iter().any(|(b, _)| b == id_b))
+      .ok_or(OptimizerError::UnknownEdge {
+        edge: None,
+        layer_index,
+      })
+  }
+
+  /// Adds a node without any edges at the end of its layer.
+  pub fn add_node(&mut self, layer_index: usize, node: T) -> Result<(), OptimizerError> {
+    self.validate_layer_index(layer_index, self.node_layers.len())?;
+    self.insert_node(layer_index, self.node_layers[layer_index].len(), node)
+  }
+
+  /// Inserts a node without any edges at the given position in its layer.
+  pub fn insert_node(&mut self, layer_index: usize, position: usize, node: T) -> Result<(), OptimizerError> {
+    self.validate_layer_index(layer_index, self.node_layers.len())?;
+    let node_count = self.node_layers[layer_index].len();
+    if position > node_count {
+      return Err(OptimizerError::InvalidPosition {
+        position,
+        node_count,
+        layer_index,
+      });
+    }
+    if let Ok((_, existing)) = self.find_node(layer_index, &node) {
+      return Err(OptimizerError::DuplicateNode {
+        position: existing,
+        node: None,
+        layer_index,
+      });
+    }
+
+    let id = self.nodes[layer_index].intern(&node);
+    self.node_layers[layer_index].insert(position, id);
+    Ok(())
+  }
+
+  /// Removes a node along with all of its edges, returns the position the node had in its layer.
+  pub fn remove_node(&mut self, layer_index: usize, node: &T) -> Result<usize, OptimizerError> {
+    let (id, position) = self.find_node(layer_index, node)?;
+    self.node_layers[layer_index].remove(position);
+
+    if layer_index > 0 {
+      self.edges[layer_index - 1].retain(|_, b, _| b != id);
+      self.inverted_edges[layer_index - 1].retain(|b, _, _| b != id);
+    }
+    if layer_index < self.edges.len() {
+      self.edges[layer_index].retain(|a, _, _| a != id);
+      self.inverted_edges[layer_index].retain(|_, a, _| a != id);
+    }
+    self.intra_edges[layer_index].retain(|a, b, _| a != id && b != id);
+
+    Ok(position)
+  }
+
+  /// Adds an edge between `node_a` in layer `layer_index` and `node_b` in layer `layer_index + 1`.
+  pub fn add_edge(&mut self, layer_index: usize, node_a: T, node_b: T, weight: usize) -> Result<(), OptimizerError> {
+    self.validate_layer_index(layer_index, self.edges.len())?;
+
+    let edge_index = self.edges[layer_index].len();
+    let (Ok((id_a, _)), Ok((id_b, _))) = (
+      self.find_node(layer_index, &node_a),
+      self.find_node(layer_index + 1, &node_b),
+    ) else {
+      return Err(OptimizerError::MissingNode {
+        edge_index,
+        edge: None,
+        layer_index,
+      });
+    };
+    if self.find_edge(layer_index, &node_a, &node_b).is_ok() {
+      return Err(OptimizerError::DuplicateEdge {
+        edge_index,
+        edge: None,
+        layer_index,
+      });
+    }
+
+    self.edges[layer_index].insert(id_a, id_b, weight);
+    self.inverted_edges[layer_index].insert(id_b, id_a, weight);
+    Ok(())
+  }
+
+  /// Removes an edge, returns its weight.
+  pub fn remove_edge(&mut self, layer_index: usize, node_a: &T, node_b: &T) -> Result<usize, OptimizerError> {
+    let (id_a, id_b) = self.find_edge(layer_index, node_a, node_b)?;
+    self.inverted_edges[layer_index].remove(id_b, id_a);
+    Ok(self.edges[layer_index].remove(id_a, id_b).unwrap())
+  }
+
+  pub fn set_weight(
+    &mut self,
+    layer_index: usize,
+    node_a: &T,
+    node_b: &T,
+    weight: usize,
+  ) -> Result<(), OptimizerError> {
+    let (id_a, id_b) = self.find_edge(layer_index, node_a, node_b)?;
+    *self.edges[layer_index].weight_mut(id_a, id_b).unwrap() = weight;
+    *self.inverted_edges[layer_index].weight_mut(id_b, id_a).unwrap() = weight;
+    Ok(())
   }
 }
 
@@ -101,29 +473,212 @@ where
 mod tests {
   use super::*;
 
+  /// The edges of every layer in terms of nodes
+  fn node_edges(optimizer: &Optimizer<i32>, edges: &[Adjacency], offset: usize) -> Vec<Vec<(i32, i32, usize)>> {
+    edges
+      .iter()
+      .enumerate()
+      .map(|(i, layer_edges)| {
+        layer_edges
+          .edges()
+          .map(|(a, b, w)| (*optimizer.node(i, a), *optimizer.node(i + offset, b), w))
+          .collect()
+      })
+      .collect()
+  }
+
   #[test]
   fn test_get_adjacent_layers() {
+    let mut optimizer = Optimizer::new(
+      vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]],
+      vec![vec![(1, 4, 2), (1, 5, 1)], vec![(4, 8, 3), (6, 7, 4)]],
+    );
+
+    assert_eq!(
+      optimizer.get_adjacent_layers(0).unwrap(),
+      vec![(3, vec![(0, 0, 2), (0, 1, 1)])]
+    );
+    assert_eq!(
+      optimizer.get_adjacent_layers(1).unwrap(),
+      vec![(3, vec![(0, 0, 2), (1, 0, 1)]), (3, vec![(0, 1, 3), (2, 0, 4)])]
+    );
+    assert_eq!(
+      optimizer.get_adjacent_layers(2).unwrap(),
+      vec![(3, vec![(0, 2, 4), (1, 0, 3)])]
+    );
+
+    // Edges follow the current order of both layers
+    optimizer.node_layers[1] = vec![2, 0, 1];
+    assert_eq!(optimizer.positions(1), vec![1, 2, 0]);
+    assert_eq!(
+      optimizer.get_adjacent_layers(1).unwrap(),
+      vec![(3, vec![(1, 0, 2), (2, 0, 1)]), (3, vec![(0, 0, 4), (1, 1, 3)])]
+    );
+    assert!(matches!(
+      optimizer.get_adjacent_layers(3),
+      Err(OptimizerError::InvalidLayer { layer_index: 3, .. })
+    ));
+  }
+
+  #[test]
+  fn test_intra_edges() {
+    let mut optimizer = Optimizer::new(vec![vec![1, 2, 3], vec![4, 5, 6, 7]], vec![vec![(1, 4, 2), (3, 5, 1)]]);
+    optimizer
+      .set_intra_edges(vec![vec![], vec![(4, 6, 1), (5, 7, 3)]])
+      .unwrap();
+
+    assert_eq!(optimizer.count_layer_crossings(0).unwrap(), 0);
+    assert_eq!(optimizer.count_layer_crossings(1).unwrap(), 3);
+    assert_eq!(optimizer.count_crossings(), 3);
+
+    // The intra-layer crossings are evaluated per move instead of being folded into the penalty
+    assert!(optimizer.layer_penalty(1).unwrap().is_none());
+    let problem = optimizer.layer_problem(1, None, None).unwrap();
+    assert_eq!(problem.intra_edges, vec![(0, 2, 1), (1, 3, 3)]);
+    assert_eq!(problem.energy_terms().energy, 3.);
+
+    optimizer.remove_node(1, &6).unwrap();
+    assert_eq!(node_edges(&optimizer, &optimizer.intra_edges, 0)[1], vec![(5, 7, 3)]);
+    assert_eq!(optimizer.count_crossings(), 0);
+
+    assert!(matches!(
+      optimizer.set_intra_edges(vec![vec![], vec![(4, 6, 1)]]),
+      Err(OptimizerError::MissingNode {
+        edge: None,
+        edge_index: 0,
+        layer_index: 1
+      })
+    ));
+  }
+
+  #[test]
+  fn test_edge_length() {
     let optimizer = Optimizer::new(
+      vec![vec![1, 2, 3], vec![4, 5], vec![6]],
+      vec![vec![(1, 5, 2), (3, 4, 1)], vec![(4, 6, 3)]],
+    );
+
+    // Positions are -1, 0, 1 / -0.5, 0.5 / 0
+    assert_eq!(optimizer.edge_length(), 2. * 1.5 + 1. * 1.5 + 3. * 0.5);
+  }
+
+  #[test]
+  fn test_reference() {
+    let mut optimizer = Optimizer::new(vec![vec!["a", "b"], vec!["c"]], vec![vec![]]);
+    optimizer
+      .set_reference(&[vec!["b", "x", "a"], vec![]], StabilityMetric::KendallTau, 1.)
+      .unwrap();
+    assert_eq!(optimizer.cost().unwrap(), 1.);
+
+    // Nodes of the reference keep their rank once they are added
+    optimizer.insert_node(0, 0, "x").unwrap();
+    assert_eq!(optimizer.cost().unwrap(), 2.);
+
+    assert!(matches!(
+      optimizer.set_reference(&[vec!["a"], vec![]], StabilityMetric::KendallTau, f64::NAN),
+      Err(OptimizerError::InvalidWeight { .. })
+    ));
+    assert_eq!(optimizer.cost().unwrap(), 2.);
+  }
+
+  #[test]
+  fn test_edit_graph() {
+    let mut optimizer = Optimizer::new(
       vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]],
       vec![vec![(1, 4, 2), (1, 5, 1)], vec![(4, 8, 3), (6, 7, 4)]],
     );
 
-    let (nodes1, edges1, nodes2, edges2) = optimizer.get_adjacent_layers(0).unwrap();
-    assert_eq!(nodes1, vec![4, 5, 6]);
-    assert_eq!(nodes2, None);
-    assert_eq!(edges1, vec![(1, 4, 2), (1, 5, 1)]);
-    assert_eq!(edges2, None);
-
-    let (nodes1, edges1, nodes2, edges2) = optimizer.get_adjacent_layers(1).unwrap();
-    assert_eq!(nodes1, vec![1, 2, 3]);
-    assert_eq!(nodes2, Some(&vec![7, 8, 9]));
-    assert_eq!(edges1, vec![(4, 1, 2), (5, 1, 1)]);
-    assert_eq!(edges2, Some(&vec![(4, 8, 3), (6, 7, 4)]));
-
-    let (nodes1, edges1, nodes2, edges2) = optimizer.get_adjacent_layers(2).unwrap();
-    assert_eq!(nodes1, vec![4, 5, 6]);
-    assert_eq!(nodes2, None);
-    assert_eq!(edges1, vec![(8, 4, 3), (7, 6, 4)]);
-    assert_eq!(edges2, None);
+    optimizer.insert_node(1, 3, 10).unwrap();
+    optimizer.add_edge(0, 3, 4, 5).unwrap();
+    optimizer.add_edge(1, 10, 9, 1).unwrap();
+    assert_eq!(optimizer.get_nodes()[1], vec![4, 5, 6, 10]);
+    assert_eq!(optimizer.count_crossings(), 17);
+
+    optimizer.set_weight(0, &3, &4, 1).unwrap();
+    assert_eq!(optimizer.count_crossings(), 13);
+    assert_eq!(
+      node_edges(&optimizer, &optimizer.edges, 1)[0],
+      vec![(1, 4, 2), (1, 5, 1), (3, 4, 1)]
+    );
+    assert_eq!(
+      optimizer.inverted_edges[0].edges().collect_vec(),
+      optimizer.edges[0].inverted(4).edges().collect_vec()
+    );
+
+    assert_eq!(optimizer.remove_edge(0, &1, &5).unwrap(), 1);
+    assert_eq!(
+      node_edges(&optimizer, &optimizer.edges, 1)[0],
+      vec![(1, 4, 2), (3, 4, 1)]
+    );
+
+    assert_eq!(optimizer.remove_node(1, &4).unwrap(), 0);
+    assert_eq!(optimizer.get_nodes()[1], vec![5, 6, 10]);
+    assert_eq!(
+      node_edges(&optimizer, &optimizer.edges, 1),
+      vec![vec![], vec![(6, 7, 4), (10, 9, 1)]]
+    );
+    assert_eq!(
+      optimizer.inverted_edges[1].edges().collect_vec(),
+      optimizer.edges[1].inverted(3).edges().collect_vec()
+    );
+
+    // A node that is added again gets its old id back
+    optimizer.insert_node(1, 0, 4).unwrap();
+    assert_eq!(optimizer.node_layers[1], vec![0, 1, 2, 3]);
+
+    assert!(matches!(
+      optimizer.insert_node(1, 5, 12),
+      Err(OptimizerError::InvalidPosition {
+        position: 5,
+        node_count: 4,
+        layer_index: 1
+      })
+    ));
+    assert!(optimizer.nodes[1].id(&12).is_none());
+    optimizer.add_node(1, 12).unwrap();
+    assert_eq!(optimizer.get_nodes()[1], vec![4, 5, 6, 10, 12]);
+
+    assert!(matches!(
+      optimizer.insert_node(2, 0, 8),
+      Err(OptimizerError::DuplicateNode {
+        node: None,
+        position: 1,
+        layer_index: 2
+      })
+    ));
+    assert!(matches!(
+      optimizer.add_edge(1, 10, 9, 1),
+      Err(OptimizerError::DuplicateEdge {
+        edge: None,
+        edge_index: 2,
+        layer_index: 1
+      })
+    ));
+    assert!(matches!(
+      optimizer.add_edge(0, 2, 11, 1),
+      Err(OptimizerError::MissingNode {
+        edge: None,
+        edge_index: 0,
+        layer_index: 0
+      })
+    ));
+    assert!(matches!(
+      optimizer.remove_edge(1, &5, &8),
+      Err(OptimizerError::UnknownEdge {
+        edge: None,
+        layer_index: 1
+      })
+    ));
+    assert!(matches!(
+      optimizer.remove_node(0, &4),
+      Err(OptimizerError::UnknownNode {
+        node: None,
+        layer_index: 0
+      })
+    ));
+    assert!(matches!(
+      optimizer.add_edge(2, 7, 8, 1),
+      Err(OptimizerError::InvalidLayer { layer_index: 2, .. })
+    ));
   }
 }
@@ -1,52 +1,113 @@
-use std::fmt::{Debug, Display};
 use std::hash::Hash;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::objective::Objective;
+use crate::stability::StabilityMetric;
+use crate::strategy::MappedEdges;
 
 pub trait OptimizerOps<T>
 where
-  T: Eq + Hash + Clone + Display + Debug,
+  T: Eq + Hash + Clone,
 {
   fn count_layer_crossings(&self, layer_index: usize) -> Result<usize, OptimizerError>;
   fn count_crossings(&self) -> usize;
   fn get_nodes(&self) -> Vec<Vec<T>>;
+  fn add_edge(&mut self, layer_index: usize, node_a: T, node_b: T, weight: usize) -> Result<(), OptimizerError>;
+  fn remove_edge(&mut self, layer_index: usize, node_a: &T, node_b: &T) -> Result<usize, OptimizerError>;
+  fn set_weight(&mut self, layer_index: usize, node_a: &T, node_b: &T, weight: usize) -> Result<(), OptimizerError>;
+  fn set_reference(&mut self, reference: &[Vec<T>], metric: StabilityMetric, weight: f64)
+    -> Result<(), OptimizerError>;
+  fn clear_reference(&mut self);
+  fn set_edge_length_weight(&mut self, weight: f64) -> Result<(), OptimizerError>;
+  fn edge_length(&self) -> f64;
+  fn add_objective(&mut self, objective: Box<dyn Objective<T> + Send + Sync>, weight: f64);
+  fn clear_objectives(&mut self);
+  fn cost(&self) -> Result<f64, OptimizerError>;
+  fn set_interrupt(&mut self, interrupt: Option<Arc<AtomicBool>>);
 }
 
 pub trait OptimizerInternalOps<T>
 where
-  T: Eq + Hash + Clone + Display + Debug,
+  T: Eq + Hash + Clone,
 {
-  #[allow(clippy::type_complexity)]
-  fn get_adjacent_layers(
-    &self,
-    layer_index: usize,
-  ) -> Result<(&[T], &[(T, T, usize)], Option<&Vec<T>>, Option<&Vec<(T, T, usize)>>), OptimizerError>;
+  fn get_adjacent_layers(&self, layer_index: usize) -> Result<Vec<(usize, MappedEdges)>, OptimizerError>;
 }
 
+/// Implements the traits by delegating to the `Optimizer` at `self.optimizer`, or at the given field path.
 macro_rules! impl_optimizer_ops {
   ($className:ty) => {
+    impl_optimizer_ops!($className, optimizer);
+  };
+  ($className:ty, $($field:ident).+) => {
     impl<T> OptimizerOps<T> for $className
     where
-      T: Eq + Hash + Clone + Display + Debug,
+      T: Eq + Hash + Clone,
     {
       fn count_layer_crossings(&self, layer_index: usize) -> Result<usize, OptimizerError> {
-        self.optimizer.count_layer_crossings(layer_index)
+        self.$($field).+.count_layer_crossings(layer_index)
       }
       fn count_crossings(&self) -> usize {
-        self.optimizer.count_crossings()
+        self.$($field).+.count_crossings()
       }
       fn get_nodes(&self) -> Vec<Vec<T>> {
-        self.optimizer.get_nodes()
+        self.$($field).+.get_nodes()
+      }
+      fn add_edge(&mut self, layer_index: usize, node_a: T, node_b: T, weight: usize) -> Result<(), OptimizerError> {
+        self.$($field).+.add_edge(layer_index, node_a, node_b, weight)
+      }
+      fn remove_edge(&mut self, layer_index: usize, node_a: &T, node_b: &T) -> Result<usize, OptimizerError> {
+        self.$($field).+.remove_edge(layer_index, node_a, node_b)
+      }
+      fn set_weight(
+        &mut self,
+        layer_index: usize,
+        node_a: &T,
+        node_b: &T,
+        weight: usize,
+      ) -> Result<(), OptimizerError> {
+        self.$($field).+.set_weight(layer_index, node_a, node_b, weight)
+      }
+      fn set_reference(
+        &mut self,
+        reference: &[Vec<T>],
+        metric: StabilityMetric,
+        weight: f64,
+      ) -> Result<(), OptimizerError> {
+        self.$($field).+.set_reference(reference, metric, weight)
+      }
+      fn clear_reference(&mut self) {
+        self.$($field).+.clear_reference()
+      }
+      fn set_edge_length_weight(&mut self, weight: f64) -> Result<(), OptimizerError> {
+        self.$($field).+.set_edge_length_weight(weight)
+      }
+      fn edge_length(&self) -> f64 {
+        self.$($field).+.edge_length()
+      }
+      fn add_objective(&mut self, objective: Box<dyn Objective<T> + Send + Sync>, weight: f64) {
+        self.$($field).+.add_objective(objective, weight)
+      }
+      fn clear_objectives(&mut self) {
+        self.$($field).+.clear_objectives()
+      }
+      fn cost(&self) -> Result<f64, OptimizerError> {
+        self.$($field).+.cost()
+      }
+      fn set_interrupt(&mut self, interrupt: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>) {
+        self.$($field).+.set_interrupt(interrupt)
       }
     }
 
     impl<T> OptimizerInternalOps<T> for $className
     where
-      T: Eq + Hash + Clone + Display + Debug,
+      T: Eq + Hash + Clone,
     {
       fn get_adjacent_layers(
         &self,
         layer_index: usize,
-      ) -> Result<(&[T], &[(T, T, usize)], Option<&Vec<T>>, Option<&Vec<(T, T, usize)>>), OptimizerError> {
-        self.optimizer.get_adjacent_layers(layer_index)
+      ) -> Result<Vec<(usize, $crate::strategy::MappedEdges)>, OptimizerError> {
+        self.$($field).+.get_adjacent_layers(layer_index)
       }
     }
   };
@@ -70,3 +70,94 @@ pub fn get_pairwise_matrix(
 
   pair_crossings
 }
+
+/**
+ * Pairwise matrix for edges within the swappable layer, see `count_crossings::count_intra_crossings`. Unlike edges to
+ * a static layer, whether two arcs cross depends on the positions of all four endpoints, so swapping A and B does not
+ * have a fixed contribution. Instead the matrix is linearised around the current order: the partners of A and B are
+ * classified as lying before or after the pair (relative to the midpoint between A and B) and for adjacent A and B:
+ *  1. If both partners are on the same side, the arcs cross iff the partner of A is closer than the partner of B,
+ *     swapping flips this
+ *  2. If the partner of A lies before and the partner of B after the pair, swapping introduces a crossing
+ *  3. If the partner of A lies after and the partner of B before the pair, swapping removes a crossing
+ *
+ * This is exact for pairs that are adjacent in the current order. Arcs between A and B themselves never change.
+ */
+pub fn get_intra_pairwise_matrix(swappable_count: usize, edges: &[(usize, usize, usize)]) -> Vec<f64> {
+  let mut arcs: Vec<Vec<(usize, f64)>> = vec![vec![]; swappable_count];
+  for (a, b, weight) in edges {
+    arcs[*a].push((*b, *weight as f64));
+    arcs[*b].push((*a, *weight as f64));
+  }
+
+  let mut pair_crossings: Vec<f64> = vec![0.; swappable_count * swappable_count];
+  for node_a in 0..swappable_count {
+    for node_b in 0..swappable_count {
+      if node_a == node_b || arcs[node_a].is_empty() || arcs[node_b].is_empty() {
+        continue;
+      }
+
+      let midpoint = (node_a + node_b) as f64 / 2.;
+      let mut contribution = 0.;
+      for (partner_a, weight_a) in &arcs[node_a] {
+        for (partner_b, weight_b) in &arcs[node_b] {
+          if *partner_a == node_b || *partner_b == node_a || partner_a == partner_b {
+            continue;
+          }
+
+          let sign = match (*partner_a as f64 > midpoint, *partner_b as f64 > midpoint) {
+            (false, true) => -1.,
+            (true, false) => 1.,
+            _ if partner_a < partner_b => 1.,
+            _ => -1.,
+          };
+          contribution += sign * weight_a * weight_b;
+        }
+      }
+
+      pair_crossings[node_a * swappable_count + node_b] = contribution;
+    }
+  }
+
+  pair_crossings
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::count_crossings::count_intra_crossings;
+
+  #[test]
+  fn test_intra_pairwise_matrix() {
+    let edges = vec![
+      (0, 3, 1),
+      (1, 5, 2),
+      (2, 4, 1),
+      (3, 5, 3),
+      (0, 6, 2),
+      (2, 6, 1),
+      (4, 1, 1),
+    ];
+    let nodes = (0..7).collect::<Vec<usize>>();
+    let matrix = get_intra_pairwise_matrix(nodes.len(), &edges);
+
+    for node_a in 0..nodes.len() {
+      for node_b in 0..nodes.len() {
+        assert_eq!(matrix[node_a * 7 + node_b], -matrix[node_b * 7 + node_a]);
+      }
+    }
+
+    let crossings = count_intra_crossings(&nodes, &edges) as f64;
+    for j in 0..nodes.len() - 1 {
+      let mut swapped = nodes.clone();
+      swapped.swap(j, j + 1);
+      let new_crossings = count_intra_crossings(&swapped, &edges) as f64;
+      assert_eq!(
+        matrix[j * 7 + j + 1],
+        crossings - new_crossings,
+        "swapping {j} <-> {}",
+        j + 1
+      );
+    }
+  }
+}
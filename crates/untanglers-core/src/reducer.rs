@@ -1,116 +1,109 @@
-#![allow(clippy::too_many_arguments)]
 use itertools::Itertools;
 use rand::random;
-use std::fmt::{Debug, Display};
-use std::hash::Hash;
 
-use crate::aggregation::aggregate_pairwise_matrix;
+use crate::config::OptimizeConfig;
 use crate::count_crossings::_count_crossings;
-use crate::mapping::map_edges;
 use crate::pairwise::get_pairwise_matrix;
+use crate::strategy::{EnergyTerms, LayerProblem, MappedEdges};
 use crate::utils::add_matrix;
 
+/// Tolerance for rounding errors when checking that an exact energy didn't turn negative.
+const ENERGY_TOLERANCE: f64 = 1e-6;
+
+/// Sweeps over `nodes` up to `max_iterations` times, swapping neighbouring units that aren't separated by a border if
+/// that lowers the energy, or by chance depending on the temperature. `nodes` is an ordering of the units of `terms`.
 pub fn swap_nodes(
-  swappable_count: usize,
-  pairwise_matrix: &[f64],
+  terms: &EnergyTerms,
   max_iterations: usize,
   temperature: f64,
-  mut crossing_count: i64,
+  mut energy: f64,
   nodes: Vec<usize>,
   borders: &Option<Vec<usize>>,
-) -> (Vec<usize>, i64) {
+) -> (Vec<usize>, f64) {
+  let swappable_count = nodes.len();
   let mut new_nodes = nodes.clone();
 
   if swappable_count == 0 {
-    return (new_nodes, crossing_count);
+    return (new_nodes, energy);
   }
 
   let indices = match borders {
     None => (0..swappable_count - 1).collect_vec(),
     Some(b) => (0..swappable_count - 1).filter(|i| !b.contains(i)).collect_vec(),
   };
+  // Start position (in nodes) of every unit, only needed for position dependent costs
+  let mut starts = match terms.has_position_terms() {
+    true => terms.starts(&new_nodes),
+    false => vec![],
+  };
 
-  if crossing_count > 0 {
+  if energy > 0. || !terms.exact {
     for _ in 0..max_iterations {
       for j in &indices {
         let (node_a, node_b) = (new_nodes[*j], new_nodes[*j + 1]);
-        let contribution = pairwise_matrix[node_a * swappable_count + node_b];
+        let contribution = match terms.has_position_terms() {
+          true => terms.swap_delta(&starts, node_a, node_b),
+          false => terms.pair(node_a, node_b),
+        };
+
         if contribution > 0. || ((contribution - 1.) / temperature).exp() > random::<f64>() {
           new_nodes[*j] = node_b;
           new_nodes[*j + 1] = node_a;
-          crossing_count -= contribution as i64;
+          energy -= contribution;
+          if terms.has_position_terms() {
+            terms.swap_starts(&mut starts, node_a, node_b);
+          }
         }
 
-        if crossing_count < 0 {
-          println!(
-            "[ERROR] Swapped nodes {} <-> {} with contrib {} new count = {}",
-            node_a, node_b, contribution, crossing_count
-          );
-          panic!("Crossing count turned negative: {}", crossing_count);
+        if terms.exact && energy < -ENERGY_TOLERANCE {
+          panic!("Energy turned negative after swapping {node_a} <-> {node_b}: {energy}");
         }
       }
 
-      if crossing_count == 0 {
+      // Only an exact energy of 0 means no swap can improve the order
+      if terms.exact && energy <= 0. {
         break;
       }
     }
   }
 
-  (new_nodes, crossing_count)
+  (new_nodes, energy)
 }
 
-fn matrix_and_count<T>(
-  swappable_nodes: &[T],
-  static_nodes1: &[T],
-  edges1: &[(T, T, usize)],
-  static_nodes2: Option<&Vec<T>>,
-  edges2: Option<&Vec<(T, T, usize)>>,
-) -> (i64, Vec<f64>)
-where
-  T: Eq + Hash + Clone + Display + Debug,
-{
-  let mapped_edges1 = map_edges(swappable_nodes, static_nodes1, edges1);
-  let mut crossing_count = _count_crossings(static_nodes1.len(), &mapped_edges1) as i64;
-  let mut pairwise_matrix = get_pairwise_matrix(swappable_nodes.len(), static_nodes1.len(), &mapped_edges1);
-
-  if let (Some(static_nodes2), Some(edges2)) = (static_nodes2, edges2) {
-    let mapped_edges2 = map_edges(swappable_nodes, static_nodes2, edges2);
-    crossing_count += _count_crossings(static_nodes2.len(), &mapped_edges2) as i64;
+/// The crossings of a layer with its adjacent layers and the pairwise matrix over its nodes.
+///
+/// * `static_layers` For each adjacent layer its node count and the edges to it, see `LayerProblem::static_layers`
+pub(crate) fn matrix_and_count(node_count: usize, static_layers: &[(usize, MappedEdges)]) -> (i64, Vec<f64>) {
+  let mut crossing_count = 0;
+  let mut pairwise_matrix = vec![0.; node_count * node_count];
+
+  for (static_count, mapped_edges) in static_layers {
+    crossing_count += _count_crossings(*static_count, mapped_edges) as i64;
     pairwise_matrix = add_matrix(
       &pairwise_matrix,
-      &get_pairwise_matrix(swappable_nodes.len(), static_nodes2.len(), &mapped_edges2),
+      &get_pairwise_matrix(node_count, *static_count, mapped_edges),
     );
-  };
+  }
 
   (crossing_count, pairwise_matrix)
 }
 
-pub fn reduce_crossings<T>(
-  swappable_nodes: &[T],
-  static_nodes1: &[T],
-  edges1: &[(T, T, usize)],
-  static_nodes2: Option<&Vec<T>>,
-  edges2: Option<&Vec<(T, T, usize)>>,
+/// Reorders the units of a layer to minimise its energy using simulated annealing over swaps of neighbouring units.
+/// Returns the new order as indices into the units and the final energy.
+///
+/// If the penalty matrix is only a linearisation (e.g. group cohesion) the returned energy is an estimate, and may even
+/// turn negative.
+pub fn anneal(
+  problem: &LayerProblem,
   max_iterations: usize,
   start_temp: f64,
   end_temp: f64,
   temp_steps: usize,
-  groups: Option<Vec<usize>>,
-  borders: Option<Vec<usize>>,
-) -> (Vec<usize>, i64)
-where
-  T: Eq + Hash + Clone + Display + Debug,
-{
-  let (mut crossing_count, mut pairwise_matrix) =
-    matrix_and_count(swappable_nodes, static_nodes1, edges1, static_nodes2, edges2);
-
-  let swappable_count = match groups {
-    Some(groups) => {
-      pairwise_matrix = aggregate_pairwise_matrix(&pairwise_matrix, &groups);
-      groups.len()
-    }
-    None => swappable_nodes.len(),
-  };
+) -> (Vec<usize>, f64) {
+  let terms = problem.energy_terms();
+  let mut energy = terms.energy;
+  let swappable_count = problem.unit_count();
+  let mut new_indices = (0..swappable_count).collect_vec();
 
   let mut temperature = start_temp;
   let delta_t: f64 = if temp_steps == 0 {
@@ -118,59 +111,97 @@ where
   } else {
     (end_temp / start_temp).powf(1. / (temp_steps as f64 - 1.))
   };
-  let mut new_indices = (0..swappable_count).collect_vec();
 
   for _ in 0..temp_steps {
-    (new_indices, crossing_count) = swap_nodes(
-      swappable_count,
-      &pairwise_matrix,
+    if problem.interrupted() {
+      break;
+    }
+    (new_indices, energy) = swap_nodes(
+      &terms,
       max_iterations,
       temperature,
-      crossing_count,
+      energy,
       new_indices,
-      &borders,
+      &problem.borders,
     );
     temperature *= delta_t;
   }
 
-  (new_indices, crossing_count)
+  (new_indices, energy)
+}
+
+/// Reorders the units of a layer to minimise its energy with the annealing settings of `config`, see `anneal`.
+/// `config.passes()` is not used.
+pub fn reduce_crossings(problem: &LayerProblem, config: &OptimizeConfig) -> (Vec<usize>, f64) {
+  anneal(
+    problem,
+    config.max_iterations(),
+    config.start_temp(),
+    config.end_temp(),
+    config.steps(),
+  )
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
   use crate::{
+    config::OptimizeConfig,
     count_crossings::count_crossings,
-    mapping::{reorder_nodes, swap_edges},
+    edge_length::EdgeLength,
+    intra_arcs::IntraArcs,
+    mapping::{map_edges, reorder_nodes, swap_edges},
+    stability::{Stability, StabilityMetric},
     utils::generate_bipartite_graph,
   };
 
+  fn config(max_iterations: usize, start_temp: f64, end_temp: f64, steps: usize) -> OptimizeConfig {
+    OptimizeConfig::builder()
+      .max_iterations(max_iterations)
+      .start_temp(start_temp)
+      .end_temp(end_temp)
+      .steps(steps)
+      .build()
+      .unwrap()
+  }
+
   #[test]
   fn test_middle_layer() {
-    let (crossing_count, pairwise_matrix) = matrix_and_count(
-      &[4, 5, 6],
-      &[1, 2, 3],
-      &[(4, 1, 2), (5, 1, 1), (4, 2, 1), (6, 3, 10)],
-      Some(&vec![7, 8, 9]),
-      Some(&vec![(4, 8, 3), (5, 7, 2), (6, 9, 5)]),
-    );
+    let static_layers = |swappable_nodes: &[i32]| {
+      vec![
+        (
+          3,
+          map_edges(
+            swappable_nodes,
+            &[1, 2, 3],
+            &[(4, 1, 2), (5, 1, 1), (4, 2, 1), (6, 3, 10)],
+          ),
+        ),
+        (
+          3,
+          map_edges(swappable_nodes, &[7, 8, 9], &[(4, 8, 3), (5, 7, 2), (6, 9, 5)]),
+        ),
+      ]
+    };
+    let (crossing_count, pairwise_matrix) = matrix_and_count(3, &static_layers(&[4, 5, 6]));
 
     assert_eq!(crossing_count, 7);
 
     let expected_matrix = vec![0., 7., -45., -7., 0., -20., 45., 20., 0.];
     assert_eq!(pairwise_matrix, expected_matrix);
 
-    let (new_nodes, new_count) = swap_nodes(3, &pairwise_matrix, 1, 1e-5, crossing_count, vec![0, 1, 2], &None);
-    assert_eq!(new_count, 0);
+    let (new_nodes, new_count) = swap_nodes(
+      &EnergyTerms::pairwise(pairwise_matrix, crossing_count as f64),
+      1,
+      1e-5,
+      crossing_count as f64,
+      vec![0, 1, 2],
+      &None,
+    );
+    assert_eq!(new_count, 0.);
     assert_eq!(new_nodes, vec![1, 0, 2]);
 
-    let (crossing_count, pairwise_matrix) = matrix_and_count(
-      &[5, 4, 6],
-      &[1, 2, 3],
-      &[(4, 1, 2), (5, 1, 1), (4, 2, 1), (6, 3, 10)],
-      Some(&vec![7, 8, 9]),
-      Some(&vec![(4, 8, 3), (5, 7, 2), (6, 9, 5)]),
-    );
+    let (crossing_count, pairwise_matrix) = matrix_and_count(3, &static_layers(&[5, 4, 6]));
 
     assert_eq!(crossing_count, 0);
 
@@ -193,13 +224,15 @@ mod tests {
     );
     assert_eq!(count_crossings(&nodes_left, &nodes_right, &edges), 9);
 
-    let (new_indices, expected_count) =
-      reduce_crossings(&nodes_left, &nodes_right, &edges, None, None, 10, 0., 0., 1, None, None);
+    let (new_indices, expected_count) = reduce_crossings(
+      &LayerProblem::new(&nodes_left, &nodes_right, &edges),
+      &config(10, 0., 0., 1),
+    );
 
     let new_nodes = reorder_nodes(&nodes_left, &new_indices);
-    let actual_count = count_crossings(&new_nodes, &nodes_right, &edges) as i64;
+    let actual_count = count_crossings(&new_nodes, &nodes_right, &edges) as f64;
     assert_eq!(expected_count, actual_count);
-    assert_eq!(actual_count, 0);
+    assert_eq!(actual_count, 0.);
 
     // Test counting right side
     let inv_edges = swap_edges(&edges);
@@ -212,22 +245,13 @@ mod tests {
     assert_eq!(count_crossings(&nodes_right, &nodes_left, &inv_edges), 9);
 
     let (new_indices, expected_count) = reduce_crossings(
-      &nodes_right,
-      &nodes_left,
-      &inv_edges,
-      None,
-      None,
-      10,
-      0.,
-      0.,
-      1,
-      None,
-      None,
+      &LayerProblem::new(&nodes_right, &nodes_left, &inv_edges),
+      &config(10, 0., 0., 1),
     );
     let new_nodes = reorder_nodes(&nodes_right, &new_indices);
-    let actual_count: i64 = count_crossings(&nodes_left, &new_nodes, &edges) as i64;
+    let actual_count: f64 = count_crossings(&nodes_left, &new_nodes, &edges) as f64;
     assert_eq!(expected_count, actual_count);
-    assert_eq!(actual_count, 0);
+    assert_eq!(actual_count, 0.);
   }
 
   #[test]
@@ -238,56 +262,102 @@ mod tests {
 
     let (nodes_left, nodes_right, edges) = generate_bipartite_graph(n);
     let swapped_edges = swap_edges(&edges);
-    let start_crossings = count_crossings(&nodes_left, &nodes_right, &edges) as i64;
+    let start_crossings = count_crossings(&nodes_left, &nodes_right, &edges) as f64;
 
     assert_eq!(
       start_crossings,
-      count_crossings(&nodes_right, &nodes_left, &swapped_edges) as i64
+      count_crossings(&nodes_right, &nodes_left, &swapped_edges) as f64
     );
 
     let (new_indices, mid_crossings) = reduce_crossings(
-      &nodes_left,
-      &nodes_right,
-      &edges,
-      None,
-      None,
-      iterations,
-      temperature,
-      temperature,
-      1,
-      None,
-      None,
+      &LayerProblem::new(&nodes_left, &nodes_right, &edges),
+      &config(iterations, temperature, temperature, 1),
     );
 
     let new_nodes_left = reorder_nodes(&nodes_left, &new_indices);
     assert_eq!(
       mid_crossings,
-      count_crossings(&new_nodes_left, &nodes_right, &edges) as i64
+      count_crossings(&new_nodes_left, &nodes_right, &edges) as f64
     );
 
     let (new_indices, end_crossings) = reduce_crossings(
-      &nodes_right,
-      &new_nodes_left,
-      &swapped_edges,
-      None,
-      None,
-      iterations,
-      temperature,
-      temperature / 10.,
-      2,
-      None,
-      None,
+      &LayerProblem::new(&nodes_right, &new_nodes_left, &swapped_edges),
+      &config(iterations, temperature, temperature / 10., 2),
     );
 
     let new_nodes_right = reorder_nodes(&nodes_right, &new_indices);
 
     assert!(mid_crossings < start_crossings, "{mid_crossings} !< {start_crossings}");
-    assert!(mid_crossings > 0, "{mid_crossings} < 0");
+    assert!(mid_crossings > 0., "{mid_crossings} < 0");
     assert!(end_crossings < mid_crossings, "{end_crossings} !< {mid_crossings}");
-    assert!(end_crossings > 0, "{end_crossings} < 0");
+    assert!(end_crossings > 0., "{end_crossings} < 0");
     assert_eq!(
       end_crossings,
-      count_crossings(&new_nodes_left, &new_nodes_right, &edges) as i64
+      count_crossings(&new_nodes_left, &new_nodes_right, &edges) as f64
+    );
+  }
+
+  #[test]
+  fn test_penalty() {
+    let (nodes_left, nodes_right, edges) = generate_bipartite_graph(30);
+    let mut reference = nodes_left.clone();
+    reference.reverse();
+    let stability = Stability::new(&[reference], StabilityMetric::Displacement, 0.25).unwrap();
+
+    let (new_indices, energy) = reduce_crossings(
+      &LayerProblem {
+        penalty: Some(stability.matrix_and_cost(0, &nodes_left)),
+        ..LayerProblem::new(&nodes_left, &nodes_right, &edges)
+      },
+      &config(100, 1., 0.1, 3),
+    );
+
+    let new_nodes = reorder_nodes(&nodes_left, &new_indices);
+    let (_, cost) = stability.matrix_and_cost(0, &new_nodes);
+    let crossings = count_crossings(&new_nodes, &nodes_right, &edges) as f64;
+    assert!(
+      (energy - crossings - cost).abs() < 1e-6,
+      "{energy} != {crossings} + {cost}"
+    );
+  }
+
+  #[test]
+  fn test_intra_edges() {
+    // Swapping either 1 and 2 or 4 and 5 uncrosses the arcs, but after one of them the other crosses them again
+    let nodes: Vec<u8> = vec![0, 1, 2, 3, 4, 5];
+    let mut problem = LayerProblem::new(&nodes, &[], &[]);
+    problem.intra_edges = vec![(2, 5, 1), (1, 4, 1)];
+    let intra_arcs = IntraArcs::new(6, &problem.intra_edges, None);
+
+    let terms = problem.energy_terms();
+    assert_eq!(terms.energy, 1.);
+    assert!(terms.exact);
+
+    let (new_indices, energy) = reduce_crossings(&problem, &config(10, 1e-5, 1e-5, 1));
+    assert_eq!(energy, 0.);
+    assert_eq!(intra_arcs.cost(&new_indices), 0.);
+  }
+
+  #[test]
+  fn test_edge_length() {
+    let (nodes_left, nodes_right, edges) = generate_bipartite_graph(30);
+    let mapped_edges = map_edges(&nodes_left, &nodes_right, &edges);
+    let edge_length = EdgeLength::new(0.5, nodes_left.len(), &[(nodes_right.len(), &mapped_edges)], None);
+
+    let (new_indices, energy) = reduce_crossings(
+      &LayerProblem {
+        edge_length_weight: 0.5,
+        ..LayerProblem::new(&nodes_left, &nodes_right, &edges)
+      },
+      &config(100, 1., 0.1, 3),
+    );
+
+    let new_nodes = reorder_nodes(&nodes_left, &new_indices);
+    let crossings = count_crossings(&new_nodes, &nodes_right, &edges) as f64;
+    let cost = edge_length.cost(&new_indices);
+    assert!(
+      (energy - crossings - cost).abs() < 1e-6,
+      "{energy} != {crossings} + {cost}"
     );
   }
 
@@ -299,14 +369,18 @@ mod tests {
 
     assert_eq!(count_crossings(&nodes_left, &nodes_right, &edges), 0);
 
-    let (_, expected_count) =
-      reduce_crossings(&nodes_left, &nodes_right, &edges, None, None, 10, 0., 0., 1, None, None);
+    let (_, expected_count) = reduce_crossings(
+      &LayerProblem::new(&nodes_left, &nodes_right, &edges),
+      &config(10, 0., 0., 1),
+    );
 
-    assert_eq!(expected_count, 0);
+    assert_eq!(expected_count, 0.);
 
-    let (_, expected_count) =
-      reduce_crossings(&nodes_right, &nodes_left, &edges, None, None, 10, 0., 0., 1, None, None);
+    let (_, expected_count) = reduce_crossings(
+      &LayerProblem::new(&nodes_right, &nodes_left, &edges),
+      &config(10, 0., 0., 1),
+    );
 
-    assert_eq!(expected_count, 0);
+    assert_eq!(expected_count, 0.);
   }
 }
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use itertools::Itertools;
+
+use crate::compound::CompoundGroup;
+use crate::error::OptimizerError;
+use crate::group_tree::GroupNames;
+use crate::hierarchy_optimizer::Hierarchy;
+use crate::optimizer::Optimizer;
+use crate::stability::{Stability, StabilityMetric};
+use crate::strategy::MappedEdges;
+use crate::utils::validate_weight;
+
+const MAGIC: &[u8] = b"UNTG";
+const VERSION: usize = 1;
+
+/// A stability reference with, for each layer, the position of every node that is part of the reference along with
+/// its rank in the reference.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotReference {
+  pub ranks: Vec<Vec<(usize, usize)>>,
+  pub metric: StabilityMetric,
+  pub weight: f64,
+}
+
+/**
+ * The problem and current order of an optimizer, with every node replaced by its position in the current order of its
+ * layer. The nodes themselves are stored separately, by whatever means suits their type. Objectives are arbitrary
+ * code, so optimizers with objectives can't be captured.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+  pub layer_sizes: Vec<usize>,
+  pub edges: Vec<MappedEdges>,
+  pub intra_edges: Vec<MappedEdges>,
+  pub reference: Option<SnapshotReference>,
+  pub edge_length_weight: f64,
+  /// Empty for optimizers without a hierarchy
+  pub hierarchy: Hierarchy,
+  pub group_names: GroupNames,
+  pub compound_groups: Vec<CompoundGroup>,
+  pub compound_order: Vec<usize>,
+}
+
+fn invalid(message: impl Into<String>) -> OptimizerError {
+  OptimizerError::InvalidSnapshot {
+    message: message.into(),
+  }
+}
+
+/// Integers are written as LEB128 varints, since nearly all of them are small indices.
+struct Writer {
+  bytes: Vec<u8>,
+}
+
+impl Writer {
+  fn usize(&mut self, mut value: usize) {
+    while value >= 0x80 {
+      self.bytes.push((value as u8 & 0x7f) | 0x80);
+      value >>= 7;
+    }
+    self.bytes.push(value as u8);
+  }
+
+  fn f64(&mut self, value: f64) {
+    self.bytes.extend(value.to_le_bytes());
+  }
+
+  fn string(&mut self, value: &str) {
+    self.usize(value.len());
+    self.bytes.extend(value.as_bytes());
+  }
+
+  fn seq<I>(&mut self, items: &[I], mut write: impl FnMut(&mut Self, &I)) {
+    self.usize(items.len());
+    for item in items {
+      write(self, item);
+    }
+  }
+
+  fn edges(&mut self, edges: &[MappedEdges]) {
+    self.seq(edges, |w, layer_edges| {
+      w.seq(layer_edges, |w, (a, b, weight)| {
+        w.usize(*a);
+        w.usize(*b);
+        w.usize(*weight);
+      })
+    });
+  }
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  position: usize,
+}
+
+impl Reader<'_> {
+  fn take(&mut self, count: usize) -> Result<&[u8], OptimizerError> {
+    let end = self.position.checked_add(count).filter(|end| *end <= self.bytes.len());
+    let end = end.ok_or_else(|| invalid("unexpected end of data"))?;
+    let bytes = &self.bytes[self.position..end];
+    self.position = end;
+    Ok(bytes)
+  }
+
+  fn usize(&mut self) -> Result<usize, OptimizerError> {
+    let mut value = 0_usize;
+    for shift in (0..usize::BITS).step_by(7) {
+      let byte = self.take(1)?[0];
+      value |= ((byte & 0x7f) as usize)
+        .checked_shl(shift)
+        .ok_or_else(|| invalid("integer out of range"))?;
+      if byte < 0x80 {
+        return Ok(value);
+      }
+    }
+    Err(invalid("integer out of range"))
+  }
+
+  fn f64(&mut self) -> Result<f64, OptimizerError> {
+    Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+  }
+
+  fn string(&mut self) -> Result<String, OptimizerError> {
+    let length = self.usize()?;
+    String::from_utf8(self.take(length)?.to_vec()).map_err(|_| invalid("invalid utf-8 in a name"))
+  }
+
+  fn seq<I>(&mut self, mut read: impl FnMut(&mut Self) -> Result<I, OptimizerError>) -> Result<Vec<I>, OptimizerError> {
+    let length = self.usize()?;
+    // Every item takes at least a byte, this guards against huge allocations from corrupt lengths
+    if length > self.bytes.len() - self.position {
+      return Err(invalid("unexpected end of data"));
+    }
+    (0..length).map(|_| read(self)).collect()
+  }
+
+  fn edges(&mut self) -> Result<Vec<MappedEdges>, OptimizerError> {
+    self.seq(|r| r.seq(|r| Ok((r.usize()?, r.usize()?, r.usize()?))))
+  }
+}
+
+impl Snapshot {
+  pub fn encode(&self) -> Vec<u8> {
+    let mut w = Writer { bytes: MAGIC.to_vec() };
+    w.usize(VERSION);
+    w.seq(&self.layer_sizes, |w, size| w.usize(*size));
+    w.edges(&self.edges);
+    w.edges(&self.intra_edges);
+
+    match &self.reference {
+      None => w.usize(0),
+      Some(reference) => {
+        w.usize(match reference.metric {
+          StabilityMetric::KendallTau => 1,
+          StabilityMetric::Displacement => 2,
+        });
+        w.f64(reference.weight);
+        w.seq(&reference.ranks, |w, layer_ranks| {
+          w.seq(layer_ranks, |w, (position, rank)| {
+            w.usize(*position);
+            w.usize(*rank);
+          })
+        });
+      }
+    }
+    w.f64(self.edge_length_weight);
+
+    w.seq(&self.hierarchy, |w, levels| {
+      w.seq(levels, |w, sizes| w.seq(sizes, |w, size| w.usize(*size)))
+    });
+    w.seq(&self.group_names, |w, levels| {
+      w.seq(levels, |w, names| w.seq(names, |w, name| w.string(name)))
+    });
+    w.seq(&self.compound_groups, |w, group| {
+      w.string(&group.name);
+      w.usize(group.first_layer);
+      w.usize(group.last_layer);
+    });
+    w.seq(&self.compound_order, |w, index| w.usize(*index));
+    w.bytes
+  }
+
+  pub fn decode(bytes: &[u8]) -> Result<Self, OptimizerError> {
+    if !bytes.starts_with(MAGIC) {
+      return Err(invalid("not a snapshot"));
+    }
+    let mut r = Reader {
+      bytes,
+      position: MAGIC.len(),
+    };
+    let version = r.usize()?;
+    if version != VERSION {
+      return Err(invalid(format!("unsupported version {version}")));
+    }
+
+    let layer_sizes = r.seq(|r| r.usize())?;
+    let edges = r.edges()?;
+    let intra_edges = r.edges()?;
+    let reference = match r.usize()? {
+      0 => None,
+      metric => Some(SnapshotReference {
+        metric: match metric {
+          1 => StabilityMetric::KendallTau,
+          2 => StabilityMetric::Displacement,
+          _ => return Err(invalid(format!("unknown stability metric {metric}"))),
+        },
+        weight: r.f64()?,
+        ranks: r.seq(|r| r.seq(|r| Ok((r.usize()?, r.usize()?))))?,
+      }),
+    };
+    let edge_length_weight = r.f64()?;
+
+    let hierarchy = r.seq(|r| r.seq(|r| r.seq(|r| r.usize())))?;
+    let group_names = r.seq(|r| r.seq(|r| r.seq(|r| r.string())))?;
+    let compound_groups = r.seq(|r| {
+      Ok(CompoundGroup {
+        name: r.string()?,
+        first_layer: r.usize()?,
+        last_layer: r.usize()?,
+      })
+    })?;
+    let compound_order = r.seq(|r| r.usize())?;
+
+    if r.position != bytes.len() {
+      return Err(invalid("trailing data"));
+    }
+    Ok(Self {
+      layer_sizes,
+      edges,
+      intra_edges,
+      reference,
+      edge_length_weight,
+      hierarchy,
+      group_names,
+      compound_groups,
+      compound_order,
+    })
+  }
+}
+
+/// Captures the parts of a snapshot that every optimizer has, the hierarchy is left empty.
+pub(crate) fn optimizer_snapshot<T>(optimizer: &Optimizer<T>) -> Result<Snapshot, OptimizerError>
+where
+  T: Eq + Hash + Clone,
+{
+  if !optimizer.objectives.is_empty() {
+    return Err(OptimizerError::ObjectivesNotSerializable);
+  }
+
+  let node_layers = &optimizer.node_layers;
+  let edges = (0..optimizer.edges.len()).map(|i| optimizer.mapped_edges(i)).collect();
+  let intra_edges = (0..node_layers.len())
+    .map(|i| optimizer.mapped_intra_edges(i))
+    .collect();
+  let reference = optimizer.stability.as_ref().map(|stability| SnapshotReference {
+    ranks: node_layers
+      .iter()
+      .zip(&stability.reference)
+      .map(|(nodes, ranks)| {
+        nodes
+          .iter()
+          .enumerate()
+          .filter_map(|(position, node)| ranks.get(node).map(|rank| (position, *rank)))
+          .collect()
+      })
+      .collect(),
+    metric: stability.metric,
+    weight: stability.weight,
+  });
+
+  Ok(Snapshot {
+    layer_sizes: node_layers.iter().map(|nodes| nodes.len()).collect(),
+    edges,
+    intra_edges,
+    reference,
+    edge_length_weight: optimizer.edge_length_weight,
+    hierarchy: vec![],
+    group_names: vec![],
+    compound_groups: vec![],
+    compound_order: vec![],
+  })
+}
+
+/// Edges, intra-layer edges and stability reference of a snapshot in terms of the given nodes.
+pub(crate) type RestoredParts<T> = (Vec<Vec<(T, T, usize)>>, Vec<Vec<(T, T, usize)>>, Option<Stability<T>>);
+
+/// Replaces the positions in a snapshot by the nodes at those positions in `node_layers`.
+pub(crate) fn restore_parts<T>(node_layers: &[Vec<T>], snapshot: &Snapshot) -> Result<RestoredParts<T>, OptimizerError>
+where
+  T: Eq + Hash + Clone,
+{
+  let layer_sizes = node_layers.iter().map(|nodes| nodes.len()).collect_vec();
+  if layer_sizes != snapshot.layer_sizes {
+    return Err(invalid(format!(
+      "expected layers of sizes {:?}, got {layer_sizes:?}",
+      snapshot.layer_sizes
+    )));
+  }
+  let node = |layer_index: usize, position: usize| {
+    node_layers
+      .get(layer_index)
+      .and_then(|nodes| nodes.get(position))
+      .cloned()
+      .ok_or_else(|| invalid(format!("position {position} out of range in layer {layer_index}")))
+  };
+  let unmap = |edges: &[MappedEdges], offset: usize| {
+    edges
+      .iter()
+      .enumerate()
+      .map(|(i, layer_edges)| {
+        layer_edges
+          .iter()
+          .map(|(a, b, weight)| Ok((node(i, *a)?, node(i + offset, *b)?, *weight)))
+          .collect()
+      })
+      .collect::<Result<Vec<_>, _>>()
+  };
+
+  if let Some(reference) = &snapshot.reference {
+    validate_weight("stability penalty", reference.weight)?;
+  }
+  let stability = match &snapshot.reference {
+    None => None,
+    Some(reference) => Some(Stability {
+      reference: reference
+        .ranks
+        .iter()
+        .enumerate()
+        .map(|(i, ranks)| {
+          ranks
+            .iter()
+            .map(|(position, rank)| Ok((node(i, *position)?, *rank)))
+            .collect::<Result<HashMap<_, _>, _>>()
+        })
+        .collect::<Result<_, _>>()?,
+      metric: reference.metric,
+      weight: reference.weight,
+    }),
+  };
+
+  Ok((unmap(&snapshot.edges, 1)?, unmap(&snapshot.intra_edges, 0)?, stability))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_round_trip() {
+    let snapshot = Snapshot {
+      layer_sizes: vec![2, 300],
+      edges: vec![vec![(0, 299, 1), (1, 0, 1000)]],
+      intra_edges: vec![vec![], vec![(0, 1, 2)]],
+      reference: Some(SnapshotReference {
+        ranks: vec![vec![(0, 1), (1, 0)], vec![]],
+        metric: StabilityMetric::Displacement,
+        weight: 0.5,
+      }),
+      edge_length_weight: 0.25,
+      hierarchy: vec![vec![vec![1, 1]], vec![]],
+      group_names: vec![vec![vec!["a".to_string(), "ß".to_string()]], vec![]],
+      compound_groups: vec![CompoundGroup {
+        name: "a".to_string(),
+        first_layer: 0,
+        last_layer: 0,
+      }],
+      compound_order: vec![0],
+    };
+    let bytes = snapshot.encode();
+    assert_eq!(Snapshot::decode(&bytes).unwrap(), snapshot);
+
+    assert!(matches!(
+      Snapshot::decode(&bytes[..bytes.len() - 1]),
+      Err(OptimizerError::InvalidSnapshot { .. })
+    ));
+    assert!(matches!(
+      Snapshot::decode(b"nope"),
+      Err(OptimizerError::InvalidSnapshot { .. })
+    ));
+  }
+}
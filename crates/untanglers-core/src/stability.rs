@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::error::OptimizerError;
+use crate::objective::Objective;
+use crate::optimizer::Optimizer;
+use crate::utils::validate_weight;
+
+/// How displacement from the reference ordering is measured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StabilityMetric {
+  /// Every pair of nodes that is ordered differently from the reference costs 1 (Kendall tau distance)
+  KendallTau,
+  /// Every inverted pair costs the distance between the two nodes in the reference, which is a pairwise proxy for how
+  /// far nodes moved from their reference position
+  Displacement,
+}
+
+/// Penalises moving nodes away from a reference ordering, so re-optimising a slightly changed graph keeps the layout
+/// recognisable. Nodes that are not part of the reference are free to move.
+#[derive(Clone)]
+pub struct Stability<T>
+where
+  T: Eq + Hash + Clone,
+{
+  pub reference: Vec<HashMap<T, usize>>,
+  pub metric: StabilityMetric,
+  pub weight: f64,
+}
+
+impl<T> Stability<T>
+where
+  T: Eq + Hash + Clone,
+{
+  pub fn new(reference: &[Vec<T>], metric: StabilityMetric, weight: f64) -> Result<Self, OptimizerError> {
+    validate_weight("stability penalty", weight)?;
+    let reference = reference
+      .iter()
+      .map(|nodes| nodes.iter().enumerate().map(|(i, node)| (node.clone(), i)).collect())
+      .collect();
+
+    Ok(Self {
+      reference,
+      metric,
+      weight,
+    })
+  }
+
+  fn pair_cost(&self, rank_a: usize, rank_b: usize) -> f64 {
+    match self.metric {
+      StabilityMetric::KendallTau => self.weight,
+      StabilityMetric::Displacement => self.weight * rank_a.abs_diff(rank_b) as f64,
+    }
+  }
+
+  /**
+   * Computes the stability term for a layer in the same form as `pairwise::get_pairwise_matrix`, i.e. entry [A, B]
+   * is the reduction in cost when swapping A and B given that A comes first, together with the cost of the current
+   * ordering. Because both metrics are sums over inverted pairs the matrix is exact.
+   */
+  pub fn matrix_and_cost(&self, layer_index: usize, nodes: &[T]) -> (Vec<f64>, f64) {
+    let node_count = nodes.len();
+    let reference = &self.reference[layer_index];
+    let ranks = nodes
+      .iter()
+      .map(|node| reference.get(node).copied())
+      .collect::<Vec<_>>();
+
+    let mut matrix = vec![0.; node_count * node_count];
+    let mut cost = 0.;
+
+    for a in 0..node_count {
+      let Some(rank_a) = ranks[a] else { continue };
+      for b in a + 1..node_count {
+        let Some(rank_b) = ranks[b] else { continue };
+
+        let pair_cost = self.pair_cost(rank_a, rank_b);
+        if rank_a > rank_b {
+          cost += pair_cost;
+          matrix[a * node_count + b] = pair_cost;
+          matrix[b * node_count + a] = -pair_cost;
+        } else {
+          matrix[a * node_count + b] = -pair_cost;
+          matrix[b * node_count + a] = pair_cost;
+        }
+      }
+    }
+
+    (matrix, cost)
+  }
+}
+
+impl<T> Objective<T> for Stability<usize>
+where
+  T: Eq + Hash + Clone,
+{
+  fn layer_matrix_and_cost(
+    &self,
+    optimizer: &Optimizer<T>,
+    layer_index: usize,
+  ) -> Result<(Vec<f64>, f64), OptimizerError> {
+    Ok(self.matrix_and_cost(layer_index, &optimizer.node_layers[layer_index]))
+  }
+
+  fn exact(&self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_kendall_tau() {
+    let stability = Stability::new(&[vec!["a", "b", "c", "d"]], StabilityMetric::KendallTau, 2.).unwrap();
+
+    let (matrix, cost) = stability.matrix_and_cost(0, &["b", "a", "x", "c"]);
+    assert_eq!(cost, 2.);
+    #[rustfmt::skip]
+    assert_eq!(matrix, vec![
+      0., 2., 0., -2.,
+      -2., 0., 0., -2.,
+      0., 0., 0., 0.,
+      2., 2., 0., 0.,
+    ]);
+
+    let (_, cost) = stability.matrix_and_cost(0, &["d", "c", "b", "a"]);
+    assert_eq!(cost, 12.);
+  }
+
+  #[test]
+  fn test_displacement() {
+    let stability = Stability::new(&[vec!["a", "b", "c", "d"]], StabilityMetric::Displacement, 1.).unwrap();
+
+    let (matrix, cost) = stability.matrix_and_cost(0, &["d", "b", "c", "a"]);
+    // Inverted pairs: (d, b), (d, c), (d, a), (b, a), (c, a)
+    assert_eq!(cost, 2. + 1. + 3. + 1. + 2.);
+    assert_eq!(matrix[1], 2.);
+    assert_eq!(matrix[4 + 2], -1.);
+  }
+
+  #[test]
+  fn test_invalid_weight() {
+    for weight in [-1., f64::NAN, f64::INFINITY] {
+      assert!(matches!(
+        Stability::new(&[vec!["a"]], StabilityMetric::KendallTau, weight),
+        Err(OptimizerError::InvalidWeight { .. })
+      ));
+    }
+    assert!(Stability::new(&[vec!["a"]], StabilityMetric::KendallTau, 0.).is_ok());
+  }
+}
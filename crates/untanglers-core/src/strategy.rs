@@ -0,0 +1,503 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use crate::aggregation::aggregate_pairwise_matrix;
+use crate::config::OptimizeConfig;
+use crate::count_crossings::_count_crossings;
+use crate::edge_length::{centred_position, EdgeLength};
+use crate::error::OptimizerError;
+use crate::intra_arcs::{unit_starts, IntraArcs};
+use crate::local_search::{local_search, tabu_search};
+use crate::pairwise::get_pairwise_matrix;
+use crate::reducer::anneal;
+use crate::utils::add_matrix;
+
+/// Edges as (swappable position, static position, weight)
+pub type MappedEdges = Vec<(usize, usize, usize)>;
+
+/// A layer together with everything needed to reorder it, with all nodes mapped to their position in their layer.
+pub struct LayerProblem {
+  pub node_count: usize,
+  /// For each adjacent layer its node count and the edges to it
+  pub static_layers: Vec<(usize, MappedEdges)>,
+  /// Sizes of the units that are moved as a whole, if None every node is its own unit
+  pub groups: Option<Vec<usize>>,
+  /// Positions of units that may not be swapped with the unit after them
+  pub borders: Option<Vec<usize>>,
+  /// Weight of the total vertical edge length, see `edge_length::EdgeLength`
+  pub edge_length_weight: f64,
+  /// An extra pairwise term over the nodes and its cost for the current order, see `OptimizerOps::add_objective`
+  pub penalty: Option<(Vec<f64>, f64)>,
+  /// Whether the penalty is exact for swaps of neighbours in any order, see `Objective::exact`
+  pub penalty_exact: bool,
+  /// Edges within the layer as (position, position, weight), see `intra_arcs::IntraArcs`
+  pub intra_edges: MappedEdges,
+  /// Once set, strategies stop early and return the best order found so far, see `Optimizer::set_interrupt`
+  pub interrupt: Option<Arc<AtomicBool>>,
+}
+
+impl LayerProblem {
+  /// A layer with a single adjacent layer and no other terms, for tests.
+  #[cfg(test)]
+  pub fn new<T>(swappable_nodes: &[T], static_nodes: &[T], edges: &[(T, T, usize)]) -> Self
+  where
+    T: Eq + std::hash::Hash + Clone,
+  {
+    Self {
+      node_count: swappable_nodes.len(),
+      static_layers: vec![(
+        static_nodes.len(),
+        crate::mapping::map_edges(swappable_nodes, static_nodes, edges),
+      )],
+      groups: None,
+      borders: None,
+      edge_length_weight: 0.,
+      penalty: None,
+      penalty_exact: true,
+      intra_edges: vec![],
+      interrupt: None,
+    }
+  }
+
+  pub fn interrupted(&self) -> bool {
+    self
+      .interrupt
+      .as_ref()
+      .is_some_and(|interrupt| interrupt.load(Ordering::Relaxed))
+  }
+
+  /// Number of units that are being ordered.
+  pub fn unit_count(&self) -> usize {
+    self.groups.as_ref().map_or(self.node_count, |groups| groups.len())
+  }
+
+  /// Number of nodes in each unit.
+  pub fn unit_sizes(&self) -> Vec<usize> {
+    self.groups.clone().unwrap_or_else(|| vec![1; self.node_count])
+  }
+
+  /// Maximal runs of unit positions that may be reordered freely, i.e. the stretches between borders.
+  pub fn segments(&self) -> Vec<Range<usize>> {
+    let mut segments = vec![];
+    let mut start = 0;
+    if let Some(borders) = &self.borders {
+      for border in borders.iter().sorted() {
+        if *border + 1 < self.unit_count() {
+          segments.push(start..border + 1);
+          start = border + 1;
+        }
+      }
+    }
+    segments.push(start..self.unit_count());
+    segments
+  }
+
+  /**
+   * The pairwise matrix over the units in the form of `pairwise::get_pairwise_matrix`, the energy of the current order
+   * (crossings + penalty + intra-layer crossings + edge length) and the terms that depend on absolute positions, which
+   * have to be evaluated separately for every move.
+   */
+  pub fn energy_terms(&self) -> EnergyTerms {
+    let mut pairwise_matrix = vec![0.; self.node_count * self.node_count];
+    let mut energy = 0.;
+
+    for (static_count, mapped_edges) in &self.static_layers {
+      energy += _count_crossings(*static_count, mapped_edges) as f64;
+      pairwise_matrix = add_matrix(
+        &pairwise_matrix,
+        &get_pairwise_matrix(self.node_count, *static_count, mapped_edges),
+      );
+    }
+
+    if let Some((penalty_matrix, penalty_cost)) = &self.penalty {
+      pairwise_matrix = add_matrix(&pairwise_matrix, penalty_matrix);
+      energy += penalty_cost;
+    }
+
+    if let Some(groups) = &self.groups {
+      pairwise_matrix = aggregate_pairwise_matrix(&pairwise_matrix, groups);
+    }
+
+    let edge_length = (self.edge_length_weight != 0.).then(|| {
+      let static_layers = self
+        .static_layers
+        .iter()
+        .map(|(static_count, mapped_edges)| (*static_count, mapped_edges.as_slice()))
+        .collect_vec();
+      EdgeLength::new(
+        self.edge_length_weight,
+        self.node_count,
+        &static_layers,
+        self.groups.as_deref(),
+      )
+    });
+    let intra_arcs = (!self.intra_edges.is_empty())
+      .then(|| IntraArcs::new(self.node_count, &self.intra_edges, self.groups.as_deref()));
+
+    let identity = (0..self.unit_count()).collect_vec();
+    if let Some(edge_length) = &edge_length {
+      energy += edge_length.cost(&identity);
+    }
+    if let Some(intra_arcs) = &intra_arcs {
+      energy += intra_arcs.cost(&identity);
+    }
+
+    EnergyTerms {
+      pairwise_matrix,
+      energy,
+      edge_length,
+      intra_arcs,
+      exact: self.penalty.is_none() || self.penalty_exact,
+      sizes: self.unit_sizes(),
+    }
+  }
+}
+
+/// The energy of a layer, see `LayerProblem::energy_terms`.
+pub struct EnergyTerms {
+  /// Reduction in energy when swapping two units in the form of `pairwise::get_pairwise_matrix`
+  pub pairwise_matrix: Vec<f64>,
+  /// The energy of the starting order
+  pub energy: f64,
+  pub edge_length: Option<EdgeLength>,
+  pub intra_arcs: Option<IntraArcs>,
+  /// Whether the energy and all deltas are exact. Only then the energy can't drop below 0 and reaching 0 means the
+  /// order is optimal, with linearised objectives it is an estimate.
+  pub exact: bool,
+  /// Number of nodes in each unit
+  pub sizes: Vec<usize>,
+}
+
+impl EnergyTerms {
+  /// Terms for single nodes that only consist of a pairwise matrix, e.g. the crossings with a static layer.
+  pub fn pairwise(pairwise_matrix: Vec<f64>, energy: f64) -> Self {
+    let node_count = (pairwise_matrix.len() as f64).sqrt() as usize;
+    Self {
+      pairwise_matrix,
+      energy,
+      edge_length: None,
+      intra_arcs: None,
+      exact: true,
+      sizes: vec![1; node_count],
+    }
+  }
+
+  pub fn unit_count(&self) -> usize {
+    self.sizes.len()
+  }
+
+  pub fn pair(&self, unit_a: usize, unit_b: usize) -> f64 {
+    self.pairwise_matrix[unit_a * self.unit_count() + unit_b]
+  }
+
+  /// Whether some terms depend on absolute positions, so moves can't be evaluated with the pairwise matrix alone.
+  pub fn has_position_terms(&self) -> bool {
+    self.edge_length.is_some() || self.intra_arcs.is_some()
+  }
+
+  /// Node position at which every unit starts, indexed by unit, for an ordering of the units.
+  pub fn starts(&self, order: &[usize]) -> Vec<usize> {
+    unit_starts(&self.sizes, order)
+  }
+
+  /// Reduction in energy when swapping `unit_a` with `unit_b` directly after it, `starts` as returned by `starts`.
+  pub fn swap_delta(&self, starts: &[usize], unit_a: usize, unit_b: usize) -> f64 {
+    let mut reduction = self.pair(unit_a, unit_b);
+    if let Some(edge_length) = &self.edge_length {
+      reduction += edge_length.swap_delta(unit_a, unit_b, starts[unit_a]);
+    }
+    if let Some(intra_arcs) = &self.intra_arcs {
+      reduction += intra_arcs.swap_delta(starts, unit_a, unit_b);
+    }
+    reduction
+  }
+
+  /// Reduction in the terms that depend on absolute positions when the units at positions `range` of `order` are
+  /// replaced by `new_units`.
+  pub fn range_delta(&self, order: &[usize], range: Range<usize>, new_units: &[usize]) -> f64 {
+    let mut reduction = 0.;
+    if let Some(edge_length) = &self.edge_length {
+      let start = order[..range.start].iter().map(|unit| self.sizes[*unit]).sum();
+      reduction += edge_length.range_cost(&order[range.clone()], start) - edge_length.range_cost(new_units, start);
+    }
+    if let Some(intra_arcs) = &self.intra_arcs {
+      reduction += intra_arcs.range_delta(order, range, new_units);
+    }
+    reduction
+  }
+
+  /// Swaps `unit_a` with `unit_b` directly after it in `starts`.
+  pub fn swap_starts(&self, starts: &mut [usize], unit_a: usize, unit_b: usize) {
+    starts[unit_b] = starts[unit_a];
+    starts[unit_a] += self.sizes[unit_b];
+  }
+}
+
+/// An algorithm that reorders a single layer while the adjacent layers stay fixed.
+pub trait LayerStrategy: Send {
+  /// Returns the new order as indices into the units of the layer, containing every unit exactly once. Units must not
+  /// cross the borders of the problem.
+  fn reorder(&self, problem: &LayerProblem) -> Vec<usize>;
+}
+
+/// Simulated annealing over swaps of neighbouring units, see `reducer::anneal`. The settings come from an
+/// `OptimizeConfig`, so they are validated by its builder, see `OptimizeConfig::annealing`.
+#[derive(Clone, Debug, Default)]
+pub struct Annealing {
+  config: OptimizeConfig,
+}
+
+impl Annealing {
+  pub fn config(&self) -> &OptimizeConfig {
+    &self.config
+  }
+}
+
+impl From<OptimizeConfig> for Annealing {
+  fn from(config: OptimizeConfig) -> Self {
+    Self { config }
+  }
+}
+
+impl LayerStrategy for Annealing {
+  fn reorder(&self, problem: &LayerProblem) -> Vec<usize> {
+    let config = &self.config;
+    anneal(
+      problem,
+      config.max_iterations(),
+      config.start_temp(),
+      config.end_temp(),
+      config.steps(),
+    )
+    .0
+  }
+}
+
+/// Deterministic polish: swaps neighbouring units while that strictly improves the energy, optionally combined with
+/// exchanging any two units and reversing runs of units, see `local_search::local_search`.
+#[derive(Clone, Debug, Default)]
+pub struct GreedySwitch {
+  pub two_opt: bool,
+  pub block_reversal: bool,
+}
+
+impl LayerStrategy for GreedySwitch {
+  fn reorder(&self, problem: &LayerProblem) -> Vec<usize> {
+    local_search(problem, self.two_opt, self.block_reversal)
+  }
+}
+
+/// Tabu search over swaps of neighbouring units, an alternative to annealing without a temperature, see
+/// `local_search::tabu_search`.
+#[derive(Clone, Debug)]
+pub struct Tabu {
+  /// Number of iterations a swapped pair stays tabu
+  pub tenure: usize,
+  pub max_iterations: usize,
+}
+
+impl Default for Tabu {
+  fn default() -> Self {
+    Self {
+      tenure: 7,
+      max_iterations: 1000,
+    }
+  }
+}
+
+impl LayerStrategy for Tabu {
+  fn reorder(&self, problem: &LayerProblem) -> Vec<usize> {
+    tabu_search(problem, self.tenure, self.max_iterations).0
+  }
+}
+
+/**
+ * The barycenter heuristic: sorts units by the weighted average position of their neighbours in the adjacent layers.
+ * Units without edges keep their own position as barycenter so they stay roughly where they are. This only looks at
+ * the edges, it's meant as a quick initial ordering before a strategy that minimises the full objective.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct Barycenter;
+
+impl LayerStrategy for Barycenter {
+  fn reorder(&self, problem: &LayerProblem) -> Vec<usize> {
+    let mut sums = vec![(0., 0.); problem.node_count];
+    for (static_count, mapped_edges) in &problem.static_layers {
+      for (swappable_id, static_id, weight) in mapped_edges {
+        let weight = *weight as f64;
+        sums[*swappable_id].0 += weight * centred_position(*static_id, *static_count);
+        sums[*swappable_id].1 += weight;
+      }
+    }
+
+    let mut start = 0;
+    let barycenters = problem
+      .unit_sizes()
+      .iter()
+      .map(|size| {
+        let (sum, total) = sums[start..start + size]
+          .iter()
+          .fold((0., 0.), |(sum, total), (s, t)| (sum + s, total + t));
+        let own_position = centred_position(start, problem.node_count) + (*size as f64 - 1.) / 2.;
+        start += size;
+        if total > 0. {
+          sum / total
+        } else {
+          own_position
+        }
+      })
+      .collect_vec();
+
+    problem
+      .segments()
+      .into_iter()
+      .flat_map(|segment| segment.sorted_by(|a, b| barycenters[*a].total_cmp(&barycenters[*b])))
+      .collect()
+  }
+}
+
+/// Builds one of the built-in strategies by name, settings that aren't given in `params` keep their default.
+pub fn strategy_from_name(name: &str, params: &HashMap<String, f64>) -> Result<Box<dyn LayerStrategy>, OptimizerError> {
+  let unknown_parameter = |parameter: &str| OptimizerError::UnknownParameter {
+    strategy: name.to_string(),
+    parameter: parameter.to_string(),
+  };
+  // Counts are passed as floats, so they are only accepted if they hold a whole number
+  let count = |parameter: &str, value: f64| {
+    if value.is_finite() && value >= 0. && value.fract() == 0. && value <= usize::MAX as f64 {
+      Ok(value as usize)
+    } else {
+      Err(OptimizerError::InvalidParameter {
+        strategy: name.to_string(),
+        parameter: parameter.to_string(),
+        value,
+      })
+    }
+  };
+
+  match name {
+    "annealing" => {
+      // Built like the config of `optimize`, so the temperatures and counts are validated the same way
+      let mut builder = OptimizeConfig::builder();
+      for (parameter, value) in params {
+        builder = match parameter.as_str() {
+          "start_temp" => builder.start_temp(*value),
+          "end_temp" => builder.end_temp(*value),
+          "steps" => builder.steps(count(parameter, *value)?),
+          "max_iterations" => builder.max_iterations(count(parameter, *value)?),
+          _ => return Err(unknown_parameter(parameter)),
+        };
+      }
+      Ok(Box::new(builder.build()?.annealing()))
+    }
+    "greedy" => {
+      let mut greedy = GreedySwitch::default();
+      for (parameter, value) in params {
+        match parameter.as_str() {
+          "two_opt" => greedy.two_opt = *value != 0.,
+          "block_reversal" => greedy.block_reversal = *value != 0.,
+          _ => return Err(unknown_parameter(parameter)),
+        }
+      }
+      Ok(Box::new(greedy))
+    }
+    "tabu" => {
+      let mut tabu = Tabu::default();
+      for (parameter, value) in params {
+        match parameter.as_str() {
+          "tenure" => tabu.tenure = count(parameter, *value)?,
+          "max_iterations" => tabu.max_iterations = count(parameter, *value)?,
+          _ => return Err(unknown_parameter(parameter)),
+        }
+      }
+      Ok(Box::new(tabu))
+    }
+    "barycenter" => match params.keys().next() {
+      Some(parameter) => Err(unknown_parameter(parameter)),
+      None => Ok(Box::new(Barycenter)),
+    },
+    _ => Err(OptimizerError::UnknownStrategy { name: name.to_string() }),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_segments() {
+    let mut problem = LayerProblem::new::<i32>(&[1, 2, 3, 4, 5], &[], &[]);
+    assert_eq!(problem.segments(), vec![0..5]);
+
+    problem.borders = Some(vec![1, 2, 4]);
+    assert_eq!(problem.segments(), vec![0..2, 2..3, 3..5]);
+
+    problem.groups = Some(vec![2, 3]);
+    problem.borders = Some(vec![1]);
+    assert_eq!(problem.unit_count(), 2);
+    assert_eq!(problem.segments(), vec![0..2]);
+  }
+
+  #[test]
+  fn test_barycenter() {
+    let nodes = [1, 2, 3, 4];
+    let problem = LayerProblem::new(&nodes, &[5, 6, 7], &[(1, 7, 1), (2, 5, 1), (3, 6, 1), (3, 7, 1)]);
+
+    // Barycenters 1, -1, 0.5 and 1.5 for the unconnected node 4
+    assert_eq!(Barycenter.reorder(&problem), vec![1, 2, 0, 3]);
+
+    let problem = LayerProblem {
+      groups: Some(vec![2, 2]),
+      ..problem
+    };
+    assert_eq!(Barycenter.reorder(&problem), vec![0, 1]);
+
+    let problem = LayerProblem {
+      groups: None,
+      borders: Some(vec![1]),
+      ..problem
+    };
+    assert_eq!(Barycenter.reorder(&problem), vec![1, 0, 2, 3]);
+  }
+
+  #[test]
+  fn test_strategy_from_name() {
+    let params = HashMap::from([("steps".to_string(), 2.)]);
+    assert!(strategy_from_name("barycenter", &HashMap::new()).is_ok());
+    assert!(strategy_from_name("annealing", &params).is_ok());
+    assert!(matches!(
+      strategy_from_name("barycenter", &params),
+      Err(OptimizerError::UnknownParameter { .. })
+    ));
+    assert!(matches!(
+      strategy_from_name("magic", &HashMap::new()),
+      Err(OptimizerError::UnknownStrategy { .. })
+    ));
+
+    for value in [2.5, -1., f64::NAN] {
+      let params = HashMap::from([("tenure".to_string(), value)]);
+      assert!(matches!(
+        strategy_from_name("tabu", &params),
+        Err(OptimizerError::InvalidParameter { .. })
+      ));
+    }
+
+    // Annealing settings are validated like the config of `optimize`
+    for (parameter, value) in [
+      ("start_temp", -1.),
+      ("end_temp", f64::NAN),
+      ("end_temp", 2.),
+      ("steps", 0.),
+    ] {
+      let params = HashMap::from([(parameter.to_string(), value)]);
+      assert!(matches!(
+        strategy_from_name("annealing", &params),
+        Err(OptimizerError::InvalidConfig { .. })
+      ));
+    }
+  }
+}
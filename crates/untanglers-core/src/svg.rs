@@ -0,0 +1,305 @@
+use std::fmt::Write;
+use std::hash::Hash;
+
+use crate::hierarchy_optimizer::Hierarchy;
+use crate::mapping::map_edges;
+
+const GROUP_COLORS: [&str; 6] = ["#4e79a7", "#f28e2b", "#59a14f", "#e15759", "#76b7b2", "#b07aa1"];
+
+/// Styling for [`render_svg`]. Distances are in SVG user units.
+#[derive(Clone, Debug)]
+pub struct SvgOptions {
+  pub layer_spacing: f64,
+  pub node_spacing: f64,
+  pub node_radius: f64,
+  /// Extra padding around each level of hierarchy groups
+  pub group_padding: f64,
+  pub margin: f64,
+  pub min_stroke_width: f64,
+  /// Stroke width of the edge with the largest weight, lighter edges are scaled linearly
+  pub max_stroke_width: f64,
+  pub show_labels: bool,
+  /// Draw edges that are involved in at least one crossing in a separate colour
+  pub highlight_crossings: bool,
+}
+
+impl Default for SvgOptions {
+  fn default() -> Self {
+    Self {
+      layer_spacing: 160.,
+      node_spacing: 40.,
+      node_radius: 12.,
+      group_padding: 6.,
+      margin: 20.,
+      min_stroke_width: 1.,
+      max_stroke_width: 6.,
+      show_labels: true,
+      highlight_crossings: false,
+    }
+  }
+}
+
+/**
+ * Flags every edge that crosses at least one other edge between the same pair of layers. This is quadratic in the
+ * number of edges, which is fine for anything small enough to be looked at.
+ */
+pub fn crossing_edges<T>(nodes1: &[T], nodes2: &[T], edges: &[(T, T, usize)]) -> Vec<bool>
+where
+  T: Eq + Hash + Clone,
+{
+  let mapped_edges = map_edges(nodes1, nodes2, edges);
+  let mut crossing = vec![false; edges.len()];
+
+  for i in 0..mapped_edges.len() {
+    let (a1, b1, _) = mapped_edges[i];
+    for j in i + 1..mapped_edges.len() {
+      let (a2, b2, _) = mapped_edges[j];
+      if (a1 < a2 && b1 > b2) || (a1 > a2 && b1 < b2) {
+        crossing[i] = true;
+        crossing[j] = true;
+      }
+    }
+  }
+
+  crossing
+}
+
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+/// Renders a layered graph as a standalone SVG document.
+///
+/// Layers are drawn left to right with nodes centred vertically, edges are drawn with a stroke width proportional to
+/// their weight and hierarchy groups are drawn as nested rectangles, the coarsest level outermost.
+///
+/// * `intra_edges` Edges within a layer, drawn as arcs to the right of the layer. May be empty.
+/// * `hierarchy` Optional group sizes per layer, as used by the `HierarchyOptimizer`
+/// * `label` The text of a node given its layer index, only used if `options.show_labels` is set
+pub fn render_svg<T>(
+  node_layers: &[Vec<T>],
+  edges: &[Vec<(T, T, usize)>],
+  intra_edges: &[Vec<(T, T, usize)>],
+  hierarchy: Option<&Hierarchy>,
+  options: &SvgOptions,
+  label: impl Fn(usize, &T) -> String,
+) -> String
+where
+  T: Eq + Hash + Clone,
+{
+  let max_len = node_layers.iter().map(|l| l.len()).max().unwrap_or(0);
+  let levels = hierarchy
+    .map(|h| h.iter().map(|l| l.len()).max().unwrap_or(0))
+    .unwrap_or(0);
+  let margin = options.margin + options.node_radius + levels as f64 * options.group_padding;
+
+  let width = 2. * margin + node_layers.len().saturating_sub(1) as f64 * options.layer_spacing;
+  let height = 2. * margin + max_len.saturating_sub(1) as f64 * options.node_spacing;
+
+  let x = |layer_index: usize| margin + layer_index as f64 * options.layer_spacing;
+  let y = |layer_index: usize, position: usize| {
+    let offset = (max_len - node_layers[layer_index].len()) as f64 / 2.;
+    margin + (offset + position as f64) * options.node_spacing
+  };
+
+  let mut svg = String::new();
+  writeln!(
+    svg,
+    r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width:.1}" height="{height:.1}" viewBox="0 0 {width:.1} {height:.1}">"#
+  )
+  .unwrap();
+  writeln!(svg, r#"<rect width="100%" height="100%" fill="white" />"#).unwrap();
+
+  // Groups, coarsest first so finer groups are drawn on top
+  if let Some(hierarchy) = hierarchy {
+    writeln!(svg, r#"<g class="groups" fill-opacity="0.12" stroke-width="1">"#).unwrap();
+    for (layer_index, layer_hierarchy) in hierarchy.iter().enumerate() {
+      for (granularity, group_sizes) in layer_hierarchy.iter().enumerate().rev() {
+        let padding = options.node_radius + (granularity + 1) as f64 * options.group_padding;
+        let mut group_start = 0;
+        for (group_index, group_size) in group_sizes.iter().enumerate() {
+          // Empty groups have nothing to enclose
+          if *group_size == 0 {
+            continue;
+          }
+          let color = GROUP_COLORS[group_index % GROUP_COLORS.len()];
+          let top = y(layer_index, group_start) - padding;
+          let bottom = y(layer_index, group_start + group_size - 1) + padding;
+          writeln!(
+            svg,
+            r#"<rect x="{:.1}" y="{top:.1}" width="{:.1}" height="{:.1}" rx="4" fill="{color}" stroke="{color}" />"#,
+            x(layer_index) - padding,
+            2. * padding,
+            bottom - top,
+          )
+          .unwrap();
+          group_start += group_size;
+        }
+      }
+    }
+    writeln!(svg, "</g>").unwrap();
+  }
+
+  // Edges
+  let max_weight = edges
+    .iter()
+    .chain(intra_edges)
+    .flatten()
+    .map(|(_, _, w)| *w)
+    .max()
+    .unwrap_or(1)
+    .max(1) as f64;
+  let stroke_width = |w: usize| {
+    options.min_stroke_width + (options.max_stroke_width - options.min_stroke_width) * (w as f64 / max_weight)
+  };
+  writeln!(svg, r##"<g class="edges" stroke="#555555" stroke-opacity="0.8">"##).unwrap();
+  for (layer_index, layer_edges) in edges.iter().enumerate() {
+    let nodes1 = &node_layers[layer_index];
+    let nodes2 = &node_layers[layer_index + 1];
+    let crossing = if options.highlight_crossings {
+      crossing_edges(nodes1, nodes2, layer_edges)
+    } else {
+      vec![false; layer_edges.len()]
+    };
+
+    for (edge_index, (a, b, w)) in map_edges(nodes1, nodes2, layer_edges).into_iter().enumerate() {
+      let class = if crossing[edge_index] {
+        r##" class="crossing" stroke="#d62728""##
+      } else {
+        ""
+      };
+      writeln!(
+        svg,
+        r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke-width="{:.2}"{class} />"#,
+        x(layer_index),
+        y(layer_index, a),
+        x(layer_index + 1),
+        y(layer_index + 1, b),
+        stroke_width(w),
+      )
+      .unwrap();
+    }
+  }
+
+  for (layer_index, layer_edges) in intra_edges.iter().enumerate() {
+    let nodes = &node_layers[layer_index];
+    for (a, b, w) in map_edges(nodes, nodes, layer_edges) {
+      let (y1, y2) = (y(layer_index, a), y(layer_index, b));
+      let bulge = ((y2 - y1).abs() / 2.).min(options.layer_spacing * 0.45);
+      writeln!(
+        svg,
+        r#"<path d="M {:.1} {y1:.1} Q {:.1} {:.1} {:.1} {y2:.1}" fill="none" stroke-width="{:.2}" />"#,
+        x(layer_index),
+        x(layer_index) + 2. * bulge,
+        (y1 + y2) / 2.,
+        x(layer_index),
+        stroke_width(w),
+      )
+      .unwrap();
+    }
+  }
+  writeln!(svg, "</g>").unwrap();
+
+  // Nodes
+  writeln!(svg, r#"<g class="nodes" fill="white" stroke="black" stroke-width="2">"#).unwrap();
+  for (layer_index, nodes) in node_layers.iter().enumerate() {
+    for position in 0..nodes.len() {
+      writeln!(
+        svg,
+        r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}" />"#,
+        x(layer_index),
+        y(layer_index, position),
+        options.node_radius,
+      )
+      .unwrap();
+    }
+  }
+  writeln!(svg, "</g>").unwrap();
+
+  if options.show_labels {
+    writeln!(
+      svg,
+      r#"<g class="labels" font-family="sans-serif" font-size="{:.1}" text-anchor="middle" dominant-baseline="central">"#,
+      options.node_radius
+    )
+    .unwrap();
+    for (layer_index, nodes) in node_layers.iter().enumerate() {
+      for (position, node) in nodes.iter().enumerate() {
+        writeln!(
+          svg,
+          r#"<text x="{:.1}" y="{:.1}">{}</text>"#,
+          x(layer_index),
+          y(layer_index, position),
+          escape(&label(layer_index, node)),
+        )
+        .unwrap();
+      }
+    }
+    writeln!(svg, "</g>").unwrap();
+  }
+
+  writeln!(svg, "</svg>").unwrap();
+
+  svg
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_crossing_edges() {
+    let nodes_left = vec![0, 1, 2];
+    let nodes_right = vec![3, 4, 5];
+    let edges = vec![(0, 5, 1), (1, 4, 2), (2, 5, 1), (2, 3, 1)];
+
+    assert_eq!(
+      crossing_edges(&nodes_left, &nodes_right, &edges),
+      vec![true, true, false, true]
+    );
+  }
+
+  #[test]
+  fn test_render_svg() {
+    let nodes = vec![vec!["a", "b<c"], vec!["d", "e", "f"]];
+    let edges = vec![vec![("a", "f", 3), ("b<c", "d", 1)]];
+    let hierarchy: Hierarchy = vec![vec![vec![1, 1], vec![2]], vec![]];
+    let options = SvgOptions {
+      highlight_crossings: true,
+      ..Default::default()
+    };
+
+    let intra_edges = vec![vec![], vec![("d", "f", 1)]];
+    let svg = render_svg(&nodes, &edges, &intra_edges, Some(&hierarchy), &options, |_, node| {
+      node.to_string()
+    });
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert_eq!(svg.matches("<circle").count(), 5);
+    assert_eq!(svg.matches("<line").count(), 2);
+    assert_eq!(svg.matches("<path").count(), 1);
+    assert_eq!(svg.matches(r#"class="crossing""#).count(), 2);
+    assert_eq!(svg.matches("<rect").count(), 4);
+    assert!(svg.contains("b&lt;c"));
+    assert!(svg.contains(r#"stroke-width="6.00""#));
+
+    let options = SvgOptions {
+      show_labels: false,
+      ..Default::default()
+    };
+    let svg = render_svg(&nodes, &edges, &[], None, &options, |_, node| node.to_string());
+    assert_eq!(svg.matches(r#"class="crossing""#).count(), 0);
+    assert_eq!(svg.matches("<text").count(), 0);
+    assert_eq!(svg.matches("<rect").count(), 1);
+
+    let hierarchy: Hierarchy = vec![vec![vec![0, 2]], vec![]];
+    let svg = render_svg(&nodes, &edges, &[], Some(&hierarchy), &options, |_, node| {
+      node.to_string()
+    });
+    assert_eq!(svg.matches("<rect").count(), 2);
+  }
+}
@@ -3,10 +3,14 @@ use matrixmultiply::dgemm;
 use rand::rngs::ThreadRng;
 use rand::seq::SliceRandom;
 use rand::Rng;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 use std::hash::Hash;
+#[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
+// `std::time::Instant::now` panics in the browser
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
 
 use crate::error::OptimizerError;
 
@@ -129,9 +133,11 @@ pub fn matmul(matrix_a: &[f64], matrix_b: &[f64], matrix_c: &mut [f64], m: usize
   }
 }
 
+/// Validates that every layer but the last has a list of edges, that no layer has a node twice and that the edges
+/// connect nodes of their layers.
 pub fn validate_layers<T>(nodes: &[Vec<T>], edges: &[Vec<(T, T, usize)>]) -> Result<(), OptimizerError>
 where
-  T: Clone + Display + Eq,
+  T: Eq + Hash,
 {
   if edges.len() != nodes.len() - 1 {
     return Err(OptimizerError::EdgeLayerMismatch {
@@ -140,18 +146,59 @@ where
     });
   }
 
+  for (layer_index, layer_nodes) in nodes.iter().enumerate() {
+    validate_node_uniqueness(layer_index, layer_nodes)?;
+  }
+
   for layer_index in 0..edges.len() {
-    for (node_a, node_b, _) in &edges[layer_index] {
-      if !nodes[layer_index].contains(node_a) {
+    for (edge_index, (node_a, node_b, _)) in edges[layer_index].iter().enumerate() {
+      if !nodes[layer_index].contains(node_a) || !nodes[layer_index + 1].contains(node_b) {
         return Err(OptimizerError::MissingNode {
-          node_name: node_a.clone().to_string(),
+          edge_index,
+          edge: None,
           layer_index,
         });
       }
-      if !nodes[layer_index + 1].contains(node_b) {
-        return Err(OptimizerError::MissingNode {
-          node_name: node_b.clone().to_string(),
-          layer_index: layer_index + 1,
+    }
+  }
+
+  Ok(())
+}
+
+/// Validates that no node appears twice in a layer, the error holds the position of its first occurrence.
+pub fn validate_node_uniqueness<'a, T>(
+  layer_index: usize,
+  nodes: impl IntoIterator<Item = &'a T>,
+) -> Result<(), OptimizerError>
+where
+  T: Eq + Hash + 'a,
+{
+  let mut positions = HashMap::<&T, usize>::new();
+  for (position, node) in nodes.into_iter().enumerate() {
+    if let Some(first) = positions.insert(node, position) {
+      return Err(OptimizerError::DuplicateNode {
+        position: first,
+        node: None,
+        layer_index,
+      });
+    }
+  }
+
+  Ok(())
+}
+
+pub fn validate_edge_uniqueness<T>(edges: &[Vec<(T, T, usize)>]) -> Result<(), OptimizerError>
+where
+  T: Eq + Hash,
+{
+  for (layer_index, layer_edges) in edges.iter().enumerate() {
+    let mut seen = HashSet::<(&T, &T)>::new();
+    for (edge_index, (node_a, node_b, _)) in layer_edges.iter().enumerate() {
+      if !seen.insert((node_a, node_b)) {
+        return Err(OptimizerError::DuplicateEdge {
+          edge_index,
+          edge: None,
+          layer_index,
         });
       }
     }
@@ -160,29 +207,79 @@ where
   Ok(())
 }
 
-pub fn validate_edge_uniqueness<T>(edges: &[Vec<(T, T, usize)>]) -> Result<(), OptimizerError>
+/// Validates edges between nodes in the same layer, an edge and its reverse count as duplicates.
+pub fn validate_intra_edges<T>(nodes: &[Vec<T>], intra_edges: &[Vec<(T, T, usize)>]) -> Result<(), OptimizerError>
 where
-  T: Clone + Display + Eq + Hash,
+  T: Eq + Hash,
 {
-  for layer_index in 0..edges.len() {
-    let edges = &edges[layer_index];
-    let mut seen = HashSet::<(T, T)>::new();
-    for (node_a, node_b, _) in edges {
-      let tuple = (node_a.clone(), node_b.clone());
-      if seen.contains(&tuple) {
+  if intra_edges.len() != nodes.len() {
+    return Err(OptimizerError::IntraEdgeLayerMismatch {
+      edges: intra_edges.len(),
+      layers: nodes.len(),
+    });
+  }
+
+  for layer_index in 0..intra_edges.len() {
+    let mut seen = HashSet::<(&T, &T)>::new();
+    for (edge_index, (node_a, node_b, _)) in intra_edges[layer_index].iter().enumerate() {
+      if !nodes[layer_index].contains(node_a) || !nodes[layer_index].contains(node_b) {
+        return Err(OptimizerError::MissingNode {
+          edge_index,
+          edge: None,
+          layer_index,
+        });
+      }
+
+      if node_a == node_b {
+        return Err(OptimizerError::SelfLoop {
+          edge_index,
+          edge: None,
+          layer_index,
+        });
+      }
+
+      if seen.contains(&(node_b, node_a)) || !seen.insert((node_a, node_b)) {
         return Err(OptimizerError::DuplicateEdge {
-          node_a: node_a.to_string(),
-          node_b: node_b.to_string(),
+          edge_index,
+          edge: None,
           layer_index,
         });
       }
-      seen.insert(tuple);
     }
   }
 
   Ok(())
 }
 
+/// Weights of cost terms have to be finite and non-negative, otherwise the term turns into a reward or poisons every
+/// delta with NaN.
+pub fn validate_weight(term: &str, weight: f64) -> Result<(), OptimizerError> {
+  if !weight.is_finite() || weight < 0. {
+    return Err(OptimizerError::InvalidWeight {
+      term: term.to_string(),
+      weight,
+    });
+  }
+  Ok(())
+}
+
+/// The order returned by a `LayerStrategy` has to contain every unit exactly once.
+pub fn validate_permutation(order: &[usize], unit_count: usize) -> Result<(), OptimizerError> {
+  let mut seen = vec![false; unit_count];
+  let valid = order.len() == unit_count
+    && order
+      .iter()
+      .all(|unit| *unit < unit_count && !std::mem::replace(&mut seen[*unit], true));
+
+  if !valid {
+    return Err(OptimizerError::InvalidStrategyOrder {
+      size: order.len(),
+      unit_count,
+    });
+  }
+  Ok(())
+}
+
 #[allow(dead_code)]
 pub fn print_matrix<T>(mat: &[T], rows: usize, cols: usize)
 where
@@ -238,19 +335,59 @@ mod tests {
       (2, 5, 0),
     ];
 
-    let result = validate_edge_uniqueness(&[invalid_edges]);
-    match result {
+    assert!(matches!(
+      validate_edge_uniqueness(&[invalid_edges]),
       Err(OptimizerError::DuplicateEdge {
-        node_a,
-        node_b,
-        layer_index,
-      }) => {
-        assert_eq!(node_a, "2".to_string());
-        assert_eq!(node_b, "4".to_string());
-        assert_eq!(layer_index, 0);
-      }
-      Err(other) => panic!("Unexpected error: {}", other),
-      Ok(_) => panic!("Expected an error"),
-    }
+        edge: None,
+        edge_index: 5,
+        layer_index: 0
+      })
+    ));
+  }
+
+  #[test]
+  fn test_validate_layers() {
+    let nodes = vec![vec![0, 1, 2], vec![3, 4]];
+
+    assert!(validate_layers(&nodes, &[vec![(0, 3, 1), (2, 4, 1)]]).is_ok());
+    assert!(matches!(
+      validate_layers(&nodes, &[vec![(0, 3, 1), (3, 4, 1)]]),
+      Err(OptimizerError::MissingNode {
+        edge: None,
+        edge_index: 1,
+        layer_index: 0
+      })
+    ));
+    assert!(matches!(
+      validate_layers(&[vec![0, 1, 2], vec![3, 4, 3]], &[vec![]]),
+      Err(OptimizerError::DuplicateNode {
+        node: None,
+        position: 0,
+        layer_index: 1
+      })
+    ));
+  }
+
+  #[test]
+  fn test_intra_edges() {
+    let nodes = vec![vec![0, 1, 2], vec![3, 4]];
+
+    assert!(validate_intra_edges(&nodes, &[vec![(0, 1, 1), (0, 2, 1)], vec![(3, 4, 1)]]).is_ok());
+    assert!(matches!(
+      validate_intra_edges(&nodes, &[vec![(0, 1, 1)]]),
+      Err(OptimizerError::IntraEdgeLayerMismatch { edges: 1, layers: 2 })
+    ));
+    assert!(matches!(
+      validate_intra_edges(&nodes, &[vec![(0, 3, 1)], vec![]]),
+      Err(OptimizerError::MissingNode { layer_index: 0, .. })
+    ));
+    assert!(matches!(
+      validate_intra_edges(&nodes, &[vec![], vec![(4, 4, 1)]]),
+      Err(OptimizerError::SelfLoop { layer_index: 1, .. })
+    ));
+    assert!(matches!(
+      validate_intra_edges(&nodes, &[vec![(0, 1, 1), (1, 0, 2)], vec![]]),
+      Err(OptimizerError::DuplicateEdge { layer_index: 0, .. })
+    ));
   }
 }
@@ -0,0 +1,16 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Generates the C header into `OUT_DIR`, so building never touches the source tree. The checked-in copy in `include/`
+/// is compared to it by `tests::test_header`.
+fn main() {
+  let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+  let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+  println!("cargo:rerun-if-changed=src");
+  println!("cargo:rerun-if-changed=cbindgen.toml");
+
+  let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+  cbindgen::generate_with_config(&crate_dir, config)
+    .expect("Unable to generate the C header")
+    .write_to_file(out_dir.join("untanglers.h"));
+}
@@ -0,0 +1,181 @@
+#ifndef UNTANGLERS_H
+#define UNTANGLERS_H
+
+/* Generated by cbindgen from crates/untanglers-ffi, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result of every call, failures leave a message for `untanglers_last_error`.
+ */
+typedef enum UntanglersStatus {
+  UNTANGLERS_STATUS_OK = 0,
+  /**
+   * A required pointer was null
+   */
+  UNTANGLERS_STATUS_NULL_POINTER = 1,
+  /**
+   * The nodes and edges don't form a valid layered graph
+   */
+  UNTANGLERS_STATUS_INVALID_GRAPH = 2,
+  /**
+   * A layer index or a configuration value is out of range
+   */
+  UNTANGLERS_STATUS_INVALID_ARGUMENT = 3,
+  /**
+   * The output buffer can't hold the result, see `untanglers_layer_size`
+   */
+  UNTANGLERS_STATUS_BUFFER_TOO_SMALL = 4,
+  /**
+   * The optimization was interrupted
+   */
+  UNTANGLERS_STATUS_INTERRUPTED = 5,
+  /**
+   * An unexpected failure, e.g. a bug in the library
+   */
+  UNTANGLERS_STATUS_INTERNAL = 6,
+} UntanglersStatus;
+
+/**
+ * An optimizer created by `untanglers_optimizer_new`, release it with `untanglers_optimizer_free`.
+ */
+typedef struct UntanglersOptimizer UntanglersOptimizer;
+
+/**
+ * An edge from `node_a` in one layer to `node_b` in the next.
+ */
+typedef struct UntanglersEdge {
+  int64_t node_a;
+  int64_t node_b;
+  size_t weight;
+} UntanglersEdge;
+
+/**
+ * Parameters of `untanglers_optimize`, see `untanglers_default_optimize_config` for sensible defaults.
+ */
+typedef struct UntanglersOptimizeConfig {
+  double start_temp;
+  double end_temp;
+  /**
+   * Number of temperature steps from `start_temp` to `end_temp`
+   */
+  size_t steps;
+  /**
+   * Maximum number of sweeps over a layer at each temperature
+   */
+  size_t max_iterations;
+  /**
+   * Number of sweeps over all layers
+   */
+  size_t passes;
+} UntanglersOptimizeConfig;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates an optimizer from the node ids of every layer and the edges between neighbouring layers, both concatenated
+ * over the layers.
+ *
+ * # Safety
+ * `layer_sizes` points to `layer_count` sizes and `nodes` to as many node ids as they add up to. `edge_counts` points
+ * to `layer_count - 1` counts and `edges` to as many edges as they add up to. `optimizer_out` is valid for writes.
+ */
+enum UntanglersStatus untanglers_optimizer_new(const size_t *layer_sizes,
+                                               size_t layer_count,
+                                               const int64_t *nodes,
+                                               const size_t *edge_counts,
+                                               const struct UntanglersEdge *edges,
+                                               struct UntanglersOptimizer **optimizer_out);
+
+/**
+ * Releases an optimizer, null is ignored.
+ *
+ * # Safety
+ * `optimizer` is null or was created by `untanglers_optimizer_new` and not yet freed.
+ */
+void untanglers_optimizer_free(struct UntanglersOptimizer *optimizer);
+
+/**
+ * The defaults of the command line tool.
+ */
+struct UntanglersOptimizeConfig untanglers_default_optimize_config(void);
+
+/**
+ * Reorders all layers with simulated annealing, see `LayoutOptimizer::optimize`. The crossings afterwards are written
+ * to `crossings_out` unless it is null. A config that `OptimizeConfig::builder` rejects, e.g. with `steps` 0, fails
+ * with `InvalidArgument`.
+ *
+ * # Safety
+ * `optimizer` was created by `untanglers_optimizer_new` and is not used by another thread. `config` points to a
+ * config. `crossings_out` is null or valid for writes.
+ */
+enum UntanglersStatus untanglers_optimize(struct UntanglersOptimizer *optimizer,
+                                          const struct UntanglersOptimizeConfig *config,
+                                          size_t *crossings_out);
+
+/**
+ * Writes the number of weighted crossings over all layers to `crossings_out`.
+ *
+ * # Safety
+ * `optimizer` was created by `untanglers_optimizer_new`. `crossings_out` is valid for writes.
+ */
+enum UntanglersStatus untanglers_count_crossings(const struct UntanglersOptimizer *optimizer,
+                                                 size_t *crossings_out);
+
+/**
+ * Writes the number of weighted crossings between a layer and its neighbours to `crossings_out`.
+ *
+ * # Safety
+ * `optimizer` was created by `untanglers_optimizer_new`. `crossings_out` is valid for writes.
+ */
+enum UntanglersStatus untanglers_count_layer_crossings(const struct UntanglersOptimizer *optimizer,
+                                                       size_t layer_index,
+                                                       size_t *crossings_out);
+
+/**
+ * Writes the number of layers to `count_out`.
+ *
+ * # Safety
+ * `optimizer` was created by `untanglers_optimizer_new`. `count_out` is valid for writes.
+ */
+enum UntanglersStatus untanglers_layer_count(const struct UntanglersOptimizer *optimizer,
+                                             size_t *count_out);
+
+/**
+ * Writes the number of nodes in a layer to `size_out`.
+ *
+ * # Safety
+ * `optimizer` was created by `untanglers_optimizer_new`. `size_out` is valid for writes.
+ */
+enum UntanglersStatus untanglers_layer_size(const struct UntanglersOptimizer *optimizer,
+                                            size_t layer_index,
+                                            size_t *size_out);
+
+/**
+ * Writes the node ids of a layer in their current order to `nodes_out`, which holds `capacity` ids.
+ *
+ * # Safety
+ * `optimizer` was created by `untanglers_optimizer_new`. `nodes_out` is valid for writing `capacity` ids.
+ */
+enum UntanglersStatus untanglers_get_layer(const struct UntanglersOptimizer *optimizer,
+                                           size_t layer_index,
+                                           int64_t *nodes_out,
+                                           size_t capacity);
+
+/**
+ * The message of the last failed call on this thread, or null. It stays valid until the next failing call on this
+ * thread.
+ */
+const char *untanglers_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* UNTANGLERS_H */
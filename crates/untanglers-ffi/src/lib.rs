@@ -0,0 +1,535 @@
+//! C API for the layout optimizer, for consumers that can't use the Rust crate or the Python module. The header
+//! `include/untanglers.h` is generated by the build script, `UPDATE_HEADER=1 cargo test -p untanglers-ffi` updates the
+//! checked-in copy.
+//!
+//! Nodes are integer ids. Every function returns an `UntanglersStatus`, on failure `untanglers_last_error` gives a
+//! message for the calling thread.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use untanglers_core::config::OptimizeConfig;
+use untanglers_core::error::OptimizerError;
+use untanglers_core::layout_optimizer::LayoutOptimizer;
+use untanglers_core::optimizer_ops::OptimizerOps;
+
+/// Result of every call, failures leave a message for `untanglers_last_error`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UntanglersStatus {
+  Ok = 0,
+  /// A required pointer was null
+  NullPointer = 1,
+  /// The nodes and edges don't form a valid layered graph
+  InvalidGraph = 2,
+  /// A layer index or a configuration value is out of range
+  InvalidArgument = 3,
+  /// The output buffer can't hold the result, see `untanglers_layer_size`
+  BufferTooSmall = 4,
+  /// The optimization was interrupted
+  Interrupted = 5,
+  /// An unexpected failure, e.g. a bug in the library
+  Internal = 6,
+}
+
+/// An edge from `node_a` in one layer to `node_b` in the next.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UntanglersEdge {
+  pub node_a: i64,
+  pub node_b: i64,
+  pub weight: usize,
+}
+
+/// Parameters of `untanglers_optimize`, see `untanglers_default_optimize_config` for sensible defaults.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UntanglersOptimizeConfig {
+  pub start_temp: f64,
+  pub end_temp: f64,
+  /// Number of temperature steps from `start_temp` to `end_temp`
+  pub steps: usize,
+  /// Maximum number of sweeps over a layer at each temperature
+  pub max_iterations: usize,
+  /// Number of sweeps over all layers
+  pub passes: usize,
+}
+
+/// An optimizer created by `untanglers_optimizer_new`, release it with `untanglers_optimizer_free`.
+pub struct UntanglersOptimizer {
+  inner: LayoutOptimizer<i64>,
+}
+
+thread_local! {
+  static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+struct Failure {
+  status: UntanglersStatus,
+  message: String,
+}
+
+impl Failure {
+  fn new(status: UntanglersStatus, message: impl Into<String>) -> Self {
+    Self {
+      status,
+      message: message.into(),
+    }
+  }
+}
+
+impl From<OptimizerError> for Failure {
+  fn from(err: OptimizerError) -> Self {
+    use OptimizerError::*;
+    let status = match err {
+      Interrupted => UntanglersStatus::Interrupted,
+      InvalidLayer { .. }
+      | InvalidPosition { .. }
+      | InvalidGranularity { .. }
+      | InvalidGroup { .. }
+      | ReferenceMismatch { .. }
+      | InvalidWeight { .. }
+      | UnknownStrategy { .. }
+      | UnknownParameter { .. }
+      | InvalidParameter { .. }
+      | InvalidConfig { .. }
+      | TooManyNodes { .. } => UntanglersStatus::InvalidArgument,
+      HierarchyMismatch { .. }
+      | EmptyGroup { .. }
+      | HierarchySizeMismatch { .. }
+      | HierarchyAlignmentError { .. }
+      | GroupDepthMismatch { .. }
+      | MissingGroupPath { .. }
+      | DuplicateCompoundGroup { .. }
+      | InvalidCompoundRange { .. }
+      | MissingCompoundGroup { .. }
+      | InconsistentCompoundOrder { .. }
+      | EdgeLayerMismatch { .. }
+      | IntraEdgeLayerMismatch { .. }
+      | SelfLoop { .. }
+      | DuplicateEdge { .. }
+      | MissingNode { .. }
+      | DuplicateNode { .. }
+      | UnknownNode { .. }
+      | UnknownEdge { .. } => UntanglersStatus::InvalidGraph,
+      ObjectiveSizeMismatch { .. }
+      | ObjectiveError { .. }
+      | InvalidStrategyOrder { .. }
+      | ObjectivesNotSerializable
+      | InvalidSnapshot { .. } => UntanglersStatus::Internal,
+    };
+    Self::new(status, err.to_string())
+  }
+}
+
+/// Runs the body of an exported function, turning failures and panics into a status and the last error.
+fn run(f: impl FnOnce() -> Result<(), Failure>) -> UntanglersStatus {
+  let failure = match catch_unwind(AssertUnwindSafe(f)) {
+    Ok(Ok(())) => return UntanglersStatus::Ok,
+    Ok(Err(failure)) => failure,
+    Err(_) => Failure::new(UntanglersStatus::Internal, "Panic in untanglers"),
+  };
+
+  let message = CString::new(failure.message.replace('\0', "")).unwrap();
+  LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+  failure.status
+}
+
+fn non_null<T>(pointer: *const T, name: &str) -> Result<(), Failure> {
+  if pointer.is_null() {
+    return Err(Failure::new(UntanglersStatus::NullPointer, format!("{name} is null")));
+  }
+  Ok(())
+}
+
+/// # Safety
+/// `pointer` is null or points to `len` values.
+unsafe fn as_slice<'a, T>(pointer: *const T, len: usize, name: &str) -> Result<&'a [T], Failure> {
+  if len == 0 {
+    return Ok(&[]);
+  }
+  non_null(pointer, name)?;
+  Ok(slice::from_raw_parts(pointer, len))
+}
+
+/// # Safety
+/// `optimizer` is null or was created by `untanglers_optimizer_new` and not yet freed.
+unsafe fn optimizer_ref<'a>(optimizer: *const UntanglersOptimizer) -> Result<&'a UntanglersOptimizer, Failure> {
+  non_null(optimizer, "optimizer")?;
+  Ok(&*optimizer)
+}
+
+/// Writes `value` to `out` if it isn't null.
+///
+/// # Safety
+/// `out` is null or valid for writes.
+unsafe fn write_optional<T>(out: *mut T, value: T) {
+  if !out.is_null() {
+    out.write(value);
+  }
+}
+
+/// Creates an optimizer from the node ids of every layer and the edges between neighbouring layers, both concatenated
+/// over the layers.
+///
+/// # Safety
+/// `layer_sizes` points to `layer_count` sizes and `nodes` to as many node ids as they add up to. `edge_counts` points
+/// to `layer_count - 1` counts and `edges` to as many edges as they add up to. `optimizer_out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn untanglers_optimizer_new(
+  layer_sizes: *const usize,
+  layer_count: usize,
+  nodes: *const i64,
+  edge_counts: *const usize,
+  edges: *const UntanglersEdge,
+  optimizer_out: *mut *mut UntanglersOptimizer,
+) -> UntanglersStatus {
+  run(|| {
+    non_null(optimizer_out, "optimizer_out")?;
+    let layer_sizes = as_slice(layer_sizes, layer_count, "layer_sizes")?;
+    let edge_counts = as_slice(edge_counts, layer_count.saturating_sub(1), "edge_counts")?;
+    let mut nodes = as_slice(nodes, layer_sizes.iter().sum(), "nodes")?;
+    let mut edges = as_slice(edges, edge_counts.iter().sum(), "edges")?;
+
+    let node_layers: Vec<Vec<i64>> = layer_sizes
+      .iter()
+      .map(|size| {
+        let (layer, rest) = nodes.split_at(*size);
+        nodes = rest;
+        layer.to_vec()
+      })
+      .collect();
+    let edge_layers: Vec<Vec<(i64, i64, usize)>> = edge_counts
+      .iter()
+      .map(|count| {
+        let (layer_edges, rest) = edges.split_at(*count);
+        edges = rest;
+        layer_edges
+          .iter()
+          .map(|edge| (edge.node_a, edge.node_b, edge.weight))
+          .collect()
+      })
+      .collect();
+
+    let inner = LayoutOptimizer::new(node_layers.clone(), edge_layers.clone())
+      .map_err(|err| err.with_graph(&node_layers, &edge_layers))?;
+    optimizer_out.write(Box::into_raw(Box::new(UntanglersOptimizer { inner })));
+    Ok(())
+  })
+}
+
+/// Releases an optimizer, null is ignored.
+///
+/// # Safety
+/// `optimizer` is null or was created by `untanglers_optimizer_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn untanglers_optimizer_free(optimizer: *mut UntanglersOptimizer) {
+  if !optimizer.is_null() {
+    drop(Box::from_raw(optimizer));
+  }
+}
+
+/// The defaults of the command line tool.
+#[no_mangle]
+pub extern "C" fn untanglers_default_optimize_config() -> UntanglersOptimizeConfig {
+  let config = OptimizeConfig::default();
+  UntanglersOptimizeConfig {
+    start_temp: config.start_temp(),
+    end_temp: config.end_temp(),
+    steps: config.steps(),
+    max_iterations: config.max_iterations(),
+    passes: config.passes(),
+  }
+}
+
+/// Reorders all layers with simulated annealing, see `LayoutOptimizer::optimize`. The crossings afterwards are written
+/// to `crossings_out` unless it is null. A config that `OptimizeConfig::builder` rejects, e.g. with `steps` 0, fails
+/// with `InvalidArgument`.
+///
+/// # Safety
+/// `optimizer` was created by `untanglers_optimizer_new` and is not used by another thread. `config` points to a
+/// config. `crossings_out` is null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn untanglers_optimize(
+  optimizer: *mut UntanglersOptimizer,
+  config: *const UntanglersOptimizeConfig,
+  crossings_out: *mut usize,
+) -> UntanglersStatus {
+  run(|| {
+    non_null(optimizer, "optimizer")?;
+    non_null(config, "config")?;
+    let config = &*config;
+    let config = OptimizeConfig::builder()
+      .start_temp(config.start_temp)
+      .end_temp(config.end_temp)
+      .steps(config.steps)
+      .max_iterations(config.max_iterations)
+      .passes(config.passes)
+      .build()?;
+    let crossings = (*optimizer).inner.optimize(&config)?;
+    write_optional(crossings_out, crossings);
+    Ok(())
+  })
+}
+
+/// Writes the number of weighted crossings over all layers to `crossings_out`.
+///
+/// # Safety
+/// `optimizer` was created by `untanglers_optimizer_new`. `crossings_out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn untanglers_count_crossings(
+  optimizer: *const UntanglersOptimizer,
+  crossings_out: *mut usize,
+) -> UntanglersStatus {
+  run(|| {
+    let optimizer = optimizer_ref(optimizer)?;
+    non_null(crossings_out, "crossings_out")?;
+    crossings_out.write(optimizer.inner.count_crossings());
+    Ok(())
+  })
+}
+
+/// Writes the number of weighted crossings between a layer and its neighbours to `crossings_out`.
+///
+/// # Safety
+/// `optimizer` was created by `untanglers_optimizer_new`. `crossings_out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn untanglers_count_layer_crossings(
+  optimizer: *const UntanglersOptimizer,
+  layer_index: usize,
+  crossings_out: *mut usize,
+) -> UntanglersStatus {
+  run(|| {
+    let optimizer = optimizer_ref(optimizer)?;
+    non_null(crossings_out, "crossings_out")?;
+    crossings_out.write(optimizer.inner.count_layer_crossings(layer_index)?);
+    Ok(())
+  })
+}
+
+/// Writes the number of layers to `count_out`.
+///
+/// # Safety
+/// `optimizer` was created by `untanglers_optimizer_new`. `count_out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn untanglers_layer_count(
+  optimizer: *const UntanglersOptimizer,
+  count_out: *mut usize,
+) -> UntanglersStatus {
+  run(|| {
+    let optimizer = optimizer_ref(optimizer)?;
+    non_null(count_out, "count_out")?;
+    count_out.write(optimizer.inner.get_nodes().len());
+    Ok(())
+  })
+}
+
+/// Writes the number of nodes in a layer to `size_out`.
+///
+/// # Safety
+/// `optimizer` was created by `untanglers_optimizer_new`. `size_out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn untanglers_layer_size(
+  optimizer: *const UntanglersOptimizer,
+  layer_index: usize,
+  size_out: *mut usize,
+) -> UntanglersStatus {
+  run(|| {
+    let optimizer = optimizer_ref(optimizer)?;
+    non_null(size_out, "size_out")?;
+    size_out.write(layer(optimizer, layer_index)?.len());
+    Ok(())
+  })
+}
+
+/// Writes the node ids of a layer in their current order to `nodes_out`, which holds `capacity` ids.
+///
+/// # Safety
+/// `optimizer` was created by `untanglers_optimizer_new`. `nodes_out` is valid for writing `capacity` ids.
+#[no_mangle]
+pub unsafe extern "C" fn untanglers_get_layer(
+  optimizer: *const UntanglersOptimizer,
+  layer_index: usize,
+  nodes_out: *mut i64,
+  capacity: usize,
+) -> UntanglersStatus {
+  run(|| {
+    let optimizer = optimizer_ref(optimizer)?;
+    let layer = layer(optimizer, layer_index)?;
+    if layer.len() > capacity {
+      return Err(Failure::new(
+        UntanglersStatus::BufferTooSmall,
+        format!(
+          "Layer {layer_index} has {} nodes, the buffer holds {capacity}",
+          layer.len()
+        ),
+      ));
+    }
+    if !layer.is_empty() {
+      non_null(nodes_out, "nodes_out")?;
+      ptr::copy_nonoverlapping(layer.as_ptr(), nodes_out, layer.len());
+    }
+    Ok(())
+  })
+}
+
+fn layer(optimizer: &UntanglersOptimizer, layer_index: usize) -> Result<Vec<i64>, Failure> {
+  let mut node_layers = optimizer.inner.get_nodes();
+  let layer_count = node_layers.len();
+  if layer_index >= layer_count {
+    return Err(
+      OptimizerError::InvalidLayer {
+        layer_index,
+        layer_count,
+      }
+      .into(),
+    );
+  }
+  Ok(node_layers.swap_remove(layer_index))
+}
+
+/// The message of the last failed call on this thread, or null. It stays valid until the next failing call on this
+/// thread.
+#[no_mangle]
+pub extern "C" fn untanglers_last_error() -> *const c_char {
+  LAST_ERROR.with(|last_error| {
+    last_error
+      .borrow()
+      .as_ref()
+      .map_or(ptr::null(), |message| message.as_ptr())
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::ffi::CStr;
+
+  use super::*;
+
+  #[test]
+  fn test_header() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/untanglers.h"));
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/include/untanglers.h");
+    if std::env::var_os("UPDATE_HEADER").is_some() {
+      std::fs::write(path, generated).unwrap();
+    }
+    assert!(
+      std::fs::read_to_string(path).unwrap() == generated,
+      "include/untanglers.h is out of date, run `UPDATE_HEADER=1 cargo test -p untanglers-ffi`"
+    );
+  }
+
+  fn edge(node_a: i64, node_b: i64) -> UntanglersEdge {
+    UntanglersEdge {
+      node_a,
+      node_b,
+      weight: 1,
+    }
+  }
+
+  #[test]
+  fn test_optimize() {
+    let layer_sizes = [3, 3];
+    let nodes = [1, 2, 3, 4, 5, 6];
+    let edges = [edge(1, 6), edge(2, 6), edge(3, 5)];
+    let mut optimizer = ptr::null_mut();
+
+    unsafe {
+      let status = untanglers_optimizer_new(
+        layer_sizes.as_ptr(),
+        2,
+        nodes.as_ptr(),
+        [3].as_ptr(),
+        edges.as_ptr(),
+        &mut optimizer,
+      );
+      assert_eq!(status, UntanglersStatus::Ok);
+
+      let mut crossings = 0;
+      assert_eq!(
+        untanglers_count_crossings(optimizer, &mut crossings),
+        UntanglersStatus::Ok
+      );
+      assert_eq!(crossings, 2);
+
+      let mut config = untanglers_default_optimize_config();
+      config.steps = 0;
+      assert_eq!(
+        untanglers_optimize(optimizer, &config, &mut crossings),
+        UntanglersStatus::InvalidArgument
+      );
+
+      config.steps = 5;
+      assert_eq!(
+        untanglers_optimize(optimizer, &config, &mut crossings),
+        UntanglersStatus::Ok
+      );
+      assert_eq!(crossings, 0);
+      assert_eq!(
+        untanglers_count_layer_crossings(optimizer, 1, &mut crossings),
+        UntanglersStatus::Ok
+      );
+      assert_eq!(crossings, 0);
+
+      let (mut layer_count, mut size) = (0, 0);
+      assert_eq!(
+        untanglers_layer_count(optimizer, &mut layer_count),
+        UntanglersStatus::Ok
+      );
+      assert_eq!(untanglers_layer_size(optimizer, 1, &mut size), UntanglersStatus::Ok);
+      assert_eq!((layer_count, size), (2, 3));
+
+      let mut layer = [0; 3];
+      assert_eq!(
+        untanglers_get_layer(optimizer, 0, layer.as_mut_ptr(), 3),
+        UntanglersStatus::Ok
+      );
+      layer.sort();
+      assert_eq!(layer, [1, 2, 3]);
+      assert_eq!(
+        untanglers_get_layer(optimizer, 0, layer.as_mut_ptr(), 2),
+        UntanglersStatus::BufferTooSmall
+      );
+      assert_eq!(
+        untanglers_layer_size(optimizer, 2, &mut size),
+        UntanglersStatus::InvalidArgument
+      );
+
+      untanglers_optimizer_free(optimizer);
+    }
+  }
+
+  #[test]
+  fn test_errors() {
+    let mut optimizer = ptr::null_mut();
+    unsafe {
+      let status = untanglers_optimizer_new(
+        [1, 1].as_ptr(),
+        2,
+        [1, 2].as_ptr(),
+        [1].as_ptr(),
+        [edge(1, 3)].as_ptr(),
+        &mut optimizer,
+      );
+      assert_eq!(status, UntanglersStatus::InvalidGraph);
+      assert!(optimizer.is_null());
+      let message = CStr::from_ptr(untanglers_last_error()).to_str().unwrap();
+      assert!(
+        message.contains("Edge 0 (1, 3) of layer 0 connects a node that is missing"),
+        "{message}"
+      );
+
+      let status = untanglers_optimizer_new([1].as_ptr(), 1, ptr::null(), ptr::null(), ptr::null(), &mut optimizer);
+      assert_eq!(status, UntanglersStatus::NullPointer);
+
+      let mut crossings = 0;
+      assert_eq!(
+        untanglers_count_crossings(ptr::null(), &mut crossings),
+        UntanglersStatus::NullPointer
+      );
+    }
+  }
+}
@@ -0,0 +1,114 @@
+use pyo3::exceptions::{PyOverflowError, PyTypeError, PyValueError};
+use pyo3::intern;
+use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyBytes};
+
+/**
+ * Reads a numpy integer array as int64 values in row-major order, along with its shape. Returns None for anything
+ * else, including arrays of other types, so these fall back to the regular conversion. The values are copied once
+ * through `tobytes`, the abi3 build can't use the buffer protocol and there are no numpy bindings for this pyo3
+ * version.
+ */
+pub fn int64_array(array: &Bound<'_, PyAny>) -> PyResult<Option<(Vec<usize>, Vec<i64>)>> {
+  let py = array.py();
+  if !array.hasattr(intern!(py, "__array_interface__"))? {
+    return Ok(None);
+  }
+  let kind: String = array
+    .getattr(intern!(py, "dtype"))?
+    .getattr(intern!(py, "kind"))?
+    .extract()?;
+  if kind != "i" && kind != "u" {
+    return Ok(None);
+  }
+
+  // Safe casting rejects uint64, whose values may not fit
+  let kwargs = [("casting", "safe")].into_py_dict_bound(py);
+  let converted = array.call_method(intern!(py, "astype"), ("<i8",), Some(&kwargs))?;
+  let shape = converted.getattr(intern!(py, "shape"))?.extract()?;
+  let bytes = converted.call_method0(intern!(py, "tobytes"))?;
+  let values = bytes
+    .downcast::<PyBytes>()?
+    .as_bytes()
+    .chunks_exact(8)
+    .map(|chunk| i64::from_le_bytes(chunk.try_into().unwrap()))
+    .collect();
+  Ok(Some((shape, values)))
+}
+
+/// Node types that can be read from an integer array.
+pub trait ArrayNode: Sized {
+  fn from_int64(value: i64) -> PyResult<Self>;
+}
+
+impl ArrayNode for i32 {
+  fn from_int64(value: i64) -> PyResult<Self> {
+    i32::try_from(value).map_err(|_| PyOverflowError::new_err(format!("Node {value} doesn't fit in a 32-bit integer")))
+  }
+}
+
+impl ArrayNode for String {
+  fn from_int64(_: i64) -> PyResult<Self> {
+    Err(PyTypeError::new_err("Expected string nodes, got an integer array"))
+  }
+}
+
+/// The nodes of every layer, each given as a list or as a one-dimensional integer array.
+pub struct NodeLayers<T>(pub Vec<Vec<T>>);
+
+impl<'py, T: ArrayNode + FromPyObject<'py>> FromPyObject<'py> for NodeLayers<T> {
+  fn extract_bound(layers: &Bound<'py, PyAny>) -> PyResult<Self> {
+    layers
+      .iter()?
+      .map(|layer| {
+        let layer = layer?;
+        match int64_array(&layer)? {
+          None => layer.extract(),
+          Some((shape, _)) if shape.len() != 1 => Err(PyValueError::new_err(format!(
+            "Expected a one-dimensional array of nodes, got shape {shape:?}"
+          ))),
+          Some((_, values)) => values.into_iter().map(T::from_int64).collect(),
+        }
+      })
+      .collect::<PyResult<_>>()
+      .map(Self)
+  }
+}
+
+/// The edges between every pair of neighbouring layers, each given as a list of `(node_a, node_b, weight)` or as an
+/// integer array of shape `(edge_count, 3)`.
+pub struct EdgeLayers<T>(pub Vec<Vec<(T, T, usize)>>);
+
+impl<'py, T: ArrayNode + FromPyObject<'py>> FromPyObject<'py> for EdgeLayers<T> {
+  fn extract_bound(edges: &Bound<'py, PyAny>) -> PyResult<Self> {
+    edges
+      .iter()?
+      .map(|layer_edges| {
+        let layer_edges = layer_edges?;
+        match int64_array(&layer_edges)? {
+          None => layer_edges.extract(),
+          Some((shape, values)) => {
+            // An empty layer may come out as a one-dimensional array
+            if values.is_empty() {
+              return Ok(vec![]);
+            }
+            if shape.len() != 2 || shape[1] != 3 {
+              return Err(PyValueError::new_err(format!(
+                "Expected an edge array of shape (edge_count, 3), got shape {shape:?}"
+              )));
+            }
+            values
+              .chunks_exact(3)
+              .map(|edge| {
+                let weight = usize::try_from(edge[2])
+                  .map_err(|_| PyValueError::new_err(format!("Negative edge weight {}", edge[2])))?;
+                Ok((T::from_int64(edge[0])?, T::from_int64(edge[1])?, weight))
+              })
+              .collect()
+          }
+        }
+      })
+      .collect::<PyResult<_>>()
+      .map(Self)
+  }
+}
@@ -0,0 +1,105 @@
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::intern;
+use pyo3::prelude::*;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use untanglers_core::error::OptimizerError;
+use untanglers_core::optimizer_ops::OptimizerOps;
+
+use crate::threading::try_lock_optimizer;
+
+/// Done callback of the future that interrupts the optimization once the future is cancelled.
+#[pyclass]
+struct CancelOnDone {
+  interrupt: Arc<AtomicBool>,
+}
+
+#[pymethods]
+impl CancelOnDone {
+  fn __call__(&self, future: &Bound<'_, PyAny>) -> PyResult<()> {
+    if future.call_method0(intern!(future.py(), "cancelled"))?.is_truthy()? {
+      self.interrupt.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+  }
+}
+
+/// Resolves the future on the thread of its event loop, unless it was cancelled in the meantime.
+#[pyclass]
+struct ResolveFuture {
+  future: PyObject,
+  result: Mutex<Option<PyResult<PyObject>>>,
+}
+
+#[pymethods]
+impl ResolveFuture {
+  fn __call__(&self, py: Python<'_>) -> PyResult<()> {
+    let future = self.future.bind(py);
+    let Some(result) = self.result.lock().unwrap().take() else {
+      return Ok(());
+    };
+    if future.call_method0(intern!(py, "done"))?.is_truthy()? {
+      return Ok(());
+    }
+
+    match result {
+      Ok(value) => future.call_method1(intern!(py, "set_result"), (value,))?,
+      Err(err) => future.call_method1(intern!(py, "set_exception"), (err.into_value(py),))?,
+    };
+    Ok(())
+  }
+}
+
+/**
+ * Runs `f` on the optimizer in a new thread and returns an `asyncio.Future` of the running event loop that resolves
+ * with its result. Cancelling the future sets the interrupt flag of the optimizer once the event loop runs the done
+ * callbacks of the future, so the optimization stops after the current layer. The future fails with a `RuntimeError`
+ * instead of waiting if the optimizer is busy, see `try_lock_optimizer`, while other calls wait for it to finish.
+ */
+pub fn spawn_future<'py, O, N, F, T>(py: Python<'py>, optimizer: &Arc<Mutex<O>>, f: F) -> PyResult<Bound<'py, PyAny>>
+where
+  O: OptimizerOps<N> + Send + 'static,
+  N: Eq + Hash + Clone,
+  F: Send + 'static + FnOnce(&mut O) -> Result<T, OptimizerError>,
+  T: Send + 'static + IntoPy<PyObject>,
+{
+  let event_loop = py.import_bound("asyncio")?.call_method0("get_running_loop")?;
+  let future = event_loop.call_method0("create_future")?;
+  let interrupt = Arc::new(AtomicBool::new(false));
+  let cancel = Bound::new(
+    py,
+    CancelOnDone {
+      interrupt: Arc::clone(&interrupt),
+    },
+  )?;
+  future.call_method1("add_done_callback", (cancel,))?;
+
+  let optimizer = Arc::clone(optimizer);
+  let (event_loop, resolved) = (event_loop.unbind(), future.clone().unbind());
+  std::thread::spawn(move || {
+    // The lock is released before waiting for the GIL, which other calls on the optimizer may hold
+    let result = try_lock_optimizer(&optimizer).map(|mut guard| {
+      guard.set_interrupt(Some(Arc::clone(&interrupt)));
+      let result = f(&mut guard);
+      guard.set_interrupt(None);
+      result.map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    });
+
+    // Nobody waits for a cancelled future, and the interpreter may already be shutting down
+    if interrupt.load(Ordering::Relaxed) {
+      return;
+    }
+    Python::with_gil(|py| {
+      let result = result.and_then(|result| result).map(|value| value.into_py(py));
+      let resolve = ResolveFuture {
+        future: resolved,
+        result: Mutex::new(Some(result)),
+      };
+      // Fails if the event loop has been closed, then nobody is waiting for the result anymore
+      let _ = event_loop.bind(py).call_method1("call_soon_threadsafe", (resolve,));
+    });
+  });
+
+  Ok(future)
+}
@@ -0,0 +1,186 @@
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+use untanglers_core::config::{OptimizeConfig, OptimizeConfigBuilder};
+
+use crate::to_pyerr;
+
+/// Settings of `optimize`, like a frozen dataclass with keyword arguments. Invalid values raise a `ValueError`.
+#[pyclass(module = "untanglers", name = "OptimizeConfig", frozen, eq)]
+#[derive(Clone, PartialEq)]
+pub struct PyOptimizeConfig {
+  pub inner: OptimizeConfig,
+}
+
+/// Applies the settings that are given, then validates the config.
+fn build(
+  mut builder: OptimizeConfigBuilder,
+  start_temp: Option<f64>,
+  end_temp: Option<f64>,
+  steps: Option<usize>,
+  max_iterations: Option<usize>,
+  passes: Option<usize>,
+) -> PyResult<OptimizeConfig> {
+  if let Some(start_temp) = start_temp {
+    builder = builder.start_temp(start_temp);
+  }
+  if let Some(end_temp) = end_temp {
+    builder = builder.end_temp(end_temp);
+  }
+  if let Some(steps) = steps {
+    builder = builder.steps(steps);
+  }
+  if let Some(max_iterations) = max_iterations {
+    builder = builder.max_iterations(max_iterations);
+  }
+  if let Some(passes) = passes {
+    builder = builder.passes(passes);
+  }
+  builder.build().map_err(to_pyerr)
+}
+
+#[pymethods]
+impl PyOptimizeConfig {
+  #[new]
+  #[pyo3(signature = (*, start_temp=None, end_temp=None, steps=None, max_iterations=None, passes=None))]
+  fn new(
+    start_temp: Option<f64>,
+    end_temp: Option<f64>,
+    steps: Option<usize>,
+    max_iterations: Option<usize>,
+    passes: Option<usize>,
+  ) -> PyResult<Self> {
+    let inner = build(
+      OptimizeConfig::builder(),
+      start_temp,
+      end_temp,
+      steps,
+      max_iterations,
+      passes,
+    )?;
+    Ok(Self { inner })
+  }
+
+  #[getter]
+  fn start_temp(&self) -> f64 {
+    self.inner.start_temp()
+  }
+
+  #[getter]
+  fn end_temp(&self) -> f64 {
+    self.inner.end_temp()
+  }
+
+  #[getter]
+  fn steps(&self) -> usize {
+    self.inner.steps()
+  }
+
+  #[getter]
+  fn max_iterations(&self) -> usize {
+    self.inner.max_iterations()
+  }
+
+  #[getter]
+  fn passes(&self) -> usize {
+    self.inner.passes()
+  }
+
+  /// A copy with the given settings changed, like `dataclasses.replace`.
+  #[pyo3(signature = (*, start_temp=None, end_temp=None, steps=None, max_iterations=None, passes=None))]
+  fn replace(
+    &self,
+    start_temp: Option<f64>,
+    end_temp: Option<f64>,
+    steps: Option<usize>,
+    max_iterations: Option<usize>,
+    passes: Option<usize>,
+  ) -> PyResult<Self> {
+    let inner = build(
+      self.inner.to_builder(),
+      start_temp,
+      end_temp,
+      steps,
+      max_iterations,
+      passes,
+    )?;
+    Ok(Self { inner })
+  }
+
+  fn __repr__(&self) -> String {
+    format!(
+      "OptimizeConfig(start_temp={:?}, end_temp={:?}, steps={}, max_iterations={}, passes={})",
+      self.start_temp(),
+      self.end_temp(),
+      self.steps(),
+      self.max_iterations(),
+      self.passes()
+    )
+  }
+
+  fn __getnewargs_ex__<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyTuple>, Bound<'py, PyDict>)> {
+    let kwargs = PyDict::new_bound(py);
+    kwargs.set_item("start_temp", self.start_temp())?;
+    kwargs.set_item("end_temp", self.end_temp())?;
+    kwargs.set_item("steps", self.steps())?;
+    kwargs.set_item("max_iterations", self.max_iterations())?;
+    kwargs.set_item("passes", self.passes())?;
+    Ok((PyTuple::empty_bound(py), kwargs))
+  }
+}
+
+/// The first argument of `optimize`, which takes either a config or the start temperature followed by the other
+/// settings.
+#[derive(FromPyObject)]
+pub enum ConfigOrStartTemp {
+  Config(PyOptimizeConfig),
+  StartTemp(f64),
+}
+
+/// The config for `optimize(config)` or `optimize(start_temp, end_temp, steps, max_iterations, passes)`, where
+/// settings that aren't given keep their default.
+pub fn optimize_config(
+  start_temp: Option<ConfigOrStartTemp>,
+  end_temp: Option<f64>,
+  steps: Option<usize>,
+  max_iterations: Option<usize>,
+  passes: Option<usize>,
+) -> PyResult<OptimizeConfig> {
+  let start_temp = match start_temp {
+    Some(ConfigOrStartTemp::Config(config)) => {
+      if end_temp.is_some() || steps.is_some() || max_iterations.is_some() || passes.is_some() {
+        return Err(PyTypeError::new_err(
+          "Expected either an OptimizeConfig or separate settings, use OptimizeConfig.replace to change a config",
+        ));
+      }
+      return Ok(config.inner);
+    }
+    Some(ConfigOrStartTemp::StartTemp(start_temp)) => Some(start_temp),
+    None => None,
+  };
+  build(
+    OptimizeConfig::builder(),
+    start_temp,
+    end_temp,
+    steps,
+    max_iterations,
+    passes,
+  )
+}
+
+/// The config for `cooldown`, which has no passes and needs all settings.
+pub fn cooldown_config(
+  start_temp: f64,
+  end_temp: f64,
+  steps: usize,
+  max_iterations: usize,
+) -> PyResult<OptimizeConfig> {
+  build(
+    OptimizeConfig::builder(),
+    Some(start_temp),
+    Some(end_temp),
+    Some(steps),
+    Some(max_iterations),
+    None,
+  )
+}
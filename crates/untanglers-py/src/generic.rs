@@ -0,0 +1,340 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyDict, PySequence, PyString};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use untanglers_core::hierarchy_optimizer::HierarchyOptimizer;
+use untanglers_core::layout_optimizer::LayoutOptimizer;
+
+use crate::arrays::int64_array;
+use crate::graph_error;
+use crate::nodes::PyNodes;
+
+/// A Python object interned to an index. Nodes are compared by index so the optimizer never needs the GIL, the label
+/// is only used for rendering and error messages.
+#[derive(Clone)]
+pub struct InternedNode {
+  index: usize,
+  label: Arc<str>,
+  object: Arc<PyObject>,
+}
+
+impl PartialEq for InternedNode {
+  fn eq(&self, other: &Self) -> bool {
+    self.index == other.index
+  }
+}
+
+impl Eq for InternedNode {}
+
+impl Hash for InternedNode {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.index.hash(state);
+  }
+}
+
+impl Display for InternedNode {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.label)
+  }
+}
+
+/// Errors name nodes by their label as well, see `OptimizerError::with_node`.
+impl Debug for InternedNode {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.label)
+  }
+}
+
+impl ToPyObject for InternedNode {
+  fn to_object(&self, py: Python<'_>) -> PyObject {
+    self.object.clone_ref(py)
+  }
+}
+
+type InternedEdges = Vec<Vec<(InternedNode, InternedNode, usize)>>;
+
+/// Maps hashable Python objects to interned nodes. Equal objects get the same index, following Python's `__hash__`
+/// and `__eq__`. Objects stay interned after their node is removed, so adding it again reuses the index.
+pub struct Interner {
+  indices: Py<PyDict>,
+  nodes: Vec<InternedNode>,
+}
+
+impl Interner {
+  fn interned(&self, object: &Bound<'_, PyAny>) -> PyResult<Option<InternedNode>> {
+    match self.indices.bind(object.py()).get_item(object)? {
+      Some(index) => Ok(Some(self.nodes[index.extract::<usize>()?].clone())),
+      None => Ok(None),
+    }
+  }
+
+  fn new_node(object: &Bound<'_, PyAny>, index: usize) -> PyResult<InternedNode> {
+    Ok(InternedNode {
+      index,
+      label: object.str()?.to_cow()?.into(),
+      object: Arc::new(object.clone().unbind()),
+    })
+  }
+
+  /// Integer arrays are converted to a list first, which is much faster than reading them element by element.
+  fn as_list<'py>(sequence: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+    match int64_array(sequence)? {
+      Some(_) => sequence.call_method0("tolist"),
+      None => Ok(sequence.clone()),
+    }
+  }
+}
+
+impl PyNodes<InternedNode> for Interner {
+  fn new(py: Python<'_>) -> Self {
+    Self {
+      indices: PyDict::new_bound(py).unbind(),
+      nodes: vec![],
+    }
+  }
+
+  /// A copy that interns new objects separately, the objects interned so far are shared.
+  fn clone_ref(&self, py: Python<'_>) -> PyResult<Self> {
+    Ok(Self {
+      indices: self.indices.bind(py).copy()?.unbind(),
+      nodes: self.nodes.clone(),
+    })
+  }
+
+  fn node(&mut self, object: &Bound<'_, PyAny>) -> PyResult<InternedNode> {
+    if let Some(node) = self.interned(object)? {
+      return Ok(node);
+    }
+
+    let node = Self::new_node(object, self.nodes.len())?;
+    self.indices.bind(object.py()).set_item(object, node.index)?;
+    self.nodes.push(node.clone());
+    Ok(node)
+  }
+
+  /// Objects that were never interned get an index that no node has, so looking them up fails like for any node that
+  /// isn't part of the optimizer.
+  fn lookup(&self, object: &Bound<'_, PyAny>) -> PyResult<InternedNode> {
+    match self.interned(object)? {
+      Some(node) => Ok(node),
+      None => Self::new_node(object, usize::MAX),
+    }
+  }
+
+  fn node_layers(&mut self, node_layers: &Bound<'_, PyAny>) -> PyResult<Vec<Vec<InternedNode>>> {
+    node_layers
+      .iter()?
+      .map(|layer| Self::as_list(&layer?)?.iter()?.map(|node| self.node(&node?)).collect())
+      .collect()
+  }
+
+  fn edges(&mut self, edges: &Bound<'_, PyAny>) -> PyResult<InternedEdges> {
+    edges
+      .iter()?
+      .map(|layer_edges| {
+        Self::as_list(&layer_edges?)?
+          .iter()?
+          .map(|edge| {
+            let edge = edge?;
+            let edge = edge.downcast::<PySequence>()?;
+            Ok((
+              self.node(&edge.get_item(0)?)?,
+              self.node(&edge.get_item(1)?)?,
+              edge.get_item(2)?.extract()?,
+            ))
+          })
+          .collect()
+      })
+      .collect()
+  }
+}
+
+/// A networkx graph split into layers.
+struct NetworkxGraph {
+  node_layers: Vec<Vec<InternedNode>>,
+  edges: InternedEdges,
+  intra_edges: InternedEdges,
+}
+
+/// `graph.nodes(data=attr)`, pairs of each node with the value of the attribute or None.
+fn node_data<'py>(graph: &Bound<'py, PyAny>, attr: &str) -> PyResult<Bound<'py, PyAny>> {
+  let kwargs = [("data", attr)].into_py_dict_bound(graph.py());
+  graph.getattr("nodes")?.call((), Some(&kwargs))
+}
+
+/// Edge weights are counts, networkx attributes may be floats though, e.g. after summing or scaling weights. Floats are
+/// accepted if they are whole numbers.
+fn edge_weight(weight: &Bound<'_, PyAny>) -> Option<usize> {
+  if let Ok(weight) = weight.extract::<usize>() {
+    return Some(weight);
+  }
+  let weight = weight.extract::<f64>().ok()?;
+  (weight >= 0. && weight.fract() == 0. && weight <= usize::MAX as f64).then_some(weight as usize)
+}
+
+/**
+ * Splits a networkx graph into layers by the `layer_attr` attribute of every node. The distinct values are sorted, so
+ * they can be any comparable values, and consecutive values are neighbouring layers. Edges are taken from the earlier
+ * to the later layer whatever their direction, edges within a layer become intra-layer edges, and edges between the
+ * same two nodes are merged by adding their weights. Edges without `weight_attr` have weight 1, see `edge_weight` for
+ * the weights that are accepted.
+ */
+fn networkx_graph(
+  interner: &mut Interner,
+  graph: &Bound<'_, PyAny>,
+  layer_attr: &str,
+  weight_attr: &str,
+) -> PyResult<NetworkxGraph> {
+  let py = graph.py();
+  let mut nodes = vec![];
+  for item in node_data(graph, layer_attr)?.iter()? {
+    let (node, layer): (Bound<'_, PyAny>, Bound<'_, PyAny>) = item?.extract()?;
+    if layer.is_none() {
+      return Err(PyValueError::new_err(format!(
+        "Node {} has no {layer_attr:?} attribute",
+        node.repr()?
+      )));
+    }
+    nodes.push((interner.node(&node)?, layer));
+  }
+
+  let layer_values = py
+    .import_bound("builtins")?
+    .getattr("sorted")?
+    .call1((nodes.iter().map(|(_, layer)| layer.clone()).collect::<Vec<_>>(),))?;
+  let layer_indices = PyDict::new_bound(py);
+  for value in layer_values.iter()? {
+    let value = value?;
+    if !layer_indices.contains(&value)? {
+      layer_indices.set_item(&value, layer_indices.len())?;
+    }
+  }
+
+  let layer_count = layer_indices.len();
+  let mut node_layers = vec![vec![]; layer_count];
+  let mut node_layer = HashMap::new();
+  for (node, layer) in nodes {
+    let layer_index: usize = layer_indices.get_item(layer)?.unwrap().extract()?;
+    node_layer.insert(node.clone(), layer_index);
+    node_layers[layer_index].push(node);
+  }
+
+  let mut edges: InternedEdges = vec![vec![]; layer_count.saturating_sub(1)];
+  let mut intra_edges: InternedEdges = vec![vec![]; layer_count];
+  let mut positions = HashMap::<(InternedNode, InternedNode), usize>::new();
+  let kwargs = [("data", weight_attr.into_py(py)), ("default", 1.into_py(py))].into_py_dict_bound(py);
+  for item in graph.getattr("edges")?.call((), Some(&kwargs))?.iter()? {
+    let (node_a, node_b, weight): (Bound<'_, PyAny>, Bound<'_, PyAny>, Bound<'_, PyAny>) = item?.extract()?;
+    let (mut node_a, mut node_b) = (interner.node(&node_a)?, interner.node(&node_b)?);
+    let weight = edge_weight(&weight).ok_or_else(|| {
+      PyValueError::new_err(format!(
+        "Edge from {node_a} to {node_b} has {weight_attr:?} {}, expected a whole number >= 0",
+        weight.repr().map_or(String::new(), |repr| repr.to_string())
+      ))
+    })?;
+    let (mut layer_a, mut layer_b) = (node_layer[&node_a], node_layer[&node_b]);
+    if layer_a > layer_b || (layer_a == layer_b && node_a.index > node_b.index) {
+      (node_a, node_b, layer_a, layer_b) = (node_b, node_a, layer_b, layer_a);
+    }
+
+    let layer_edges = match layer_b - layer_a {
+      0 => &mut intra_edges[layer_a],
+      1 => &mut edges[layer_a],
+      _ => {
+        return Err(PyValueError::new_err(format!(
+          "Edge from {node_a} in layer {layer_a} to {node_b} in layer {layer_b} skips a layer"
+        )))
+      }
+    };
+    match positions.get(&(node_a.clone(), node_b.clone())) {
+      Some(position) => layer_edges[*position].2 += weight,
+      None => {
+        positions.insert((node_a.clone(), node_b.clone()), layer_edges.len());
+        layer_edges.push((node_a, node_b, weight));
+      }
+    }
+  }
+
+  Ok(NetworkxGraph {
+    node_layers,
+    edges,
+    intra_edges,
+  })
+}
+
+/// The group path of every node from `group_attr`, either a single group name or a list of names, coarse to fine.
+/// Nodes without the attribute are left out.
+fn networkx_group_paths(
+  interner: &mut Interner,
+  graph: &Bound<'_, PyAny>,
+  group_attr: &str,
+) -> PyResult<HashMap<InternedNode, Vec<String>>> {
+  let mut paths = HashMap::new();
+  for item in node_data(graph, group_attr)?.iter()? {
+    let (node, group): (Bound<'_, PyAny>, Bound<'_, PyAny>) = item?.extract()?;
+    if group.is_none() {
+      continue;
+    }
+    let path = match group.downcast::<PyString>() {
+      Ok(name) => vec![name.to_string()],
+      Err(_) => group.extract()?,
+    };
+    paths.insert(interner.node(&node)?, path);
+  }
+  Ok(paths)
+}
+
+/// Builds a `LayoutOptimizer` from a networkx graph, see `networkx_graph` for how it is split into layers.
+pub fn layout_from_networkx(
+  graph: &Bound<'_, PyAny>,
+  layer_attr: &str,
+  weight_attr: &str,
+) -> PyResult<(LayoutOptimizer<InternedNode>, Interner)> {
+  let mut interner = Interner::new(graph.py());
+  let graph = networkx_graph(&mut interner, graph, layer_attr, weight_attr)?;
+  let mut inner = LayoutOptimizer::new(graph.node_layers.clone(), graph.edges.clone())
+    .map_err(graph_error(&graph.node_layers, &graph.edges))?;
+  inner
+    .set_intra_edges(graph.intra_edges.clone())
+    .map_err(graph_error(&graph.node_layers, &graph.intra_edges))?;
+  Ok((inner, interner))
+}
+
+/// Builds a `HierarchyOptimizer` from a networkx graph, see `networkx_graph` for how it is split into layers. The
+/// hierarchy follows the `group_attr` of every node, see `networkx_group_paths`. Either all nodes of a layer need a
+/// group or none, in which case the layer has no hierarchy.
+pub fn hierarchy_from_networkx(
+  graph: &Bound<'_, PyAny>,
+  layer_attr: &str,
+  weight_attr: &str,
+  group_attr: &str,
+) -> PyResult<(HierarchyOptimizer<InternedNode>, Interner)> {
+  let mut interner = Interner::new(graph.py());
+  let paths = networkx_group_paths(&mut interner, graph, group_attr)?;
+  let graph = networkx_graph(&mut interner, graph, layer_attr, weight_attr)?;
+  let paths = graph
+    .node_layers
+    .iter()
+    .map(|layer| {
+      // A layer without any groups gets no hierarchy
+      let grouped = layer.iter().any(|node| paths.contains_key(node));
+      layer
+        .iter()
+        .filter_map(|node| match paths.get(node) {
+          Some(path) => Some((node.clone(), path.clone())),
+          None => (!grouped).then(|| (node.clone(), vec![])),
+        })
+        .collect()
+    })
+    .collect();
+
+  let mut inner = HierarchyOptimizer::from_group_paths(graph.node_layers.clone(), graph.edges.clone(), paths)
+    .map_err(graph_error(&graph.node_layers, &graph.edges))?;
+  inner
+    .set_intra_edges(graph.intra_edges.clone())
+    .map_err(graph_error(&graph.node_layers, &graph.intra_edges))?;
+  Ok((inner, interner))
+}
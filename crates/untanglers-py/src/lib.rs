@@ -1,45 +1,217 @@
+mod arrays;
+mod asyncio;
+mod config;
+mod generic;
+mod nodes;
 mod threading;
 
-use pyo3::exceptions::PyValueError;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyBytes, PyDict};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use untanglers_core as core;
+use untanglers_core::compound::CompoundGroup;
+use untanglers_core::edge_length::centred_position;
 use untanglers_core::error::OptimizerError;
+use untanglers_core::genetic_optimizer::GeneticConfig;
+use untanglers_core::group_tree::{tree_nodes, GroupNames, GroupTree};
 use untanglers_core::hierarchy_optimizer::Hierarchy;
+use untanglers_core::objective::{GroupCohesion, Objective};
+use untanglers_core::optimizer::Optimizer;
 use untanglers_core::optimizer_ops::OptimizerOps;
+use untanglers_core::stability::StabilityMetric;
+use untanglers_core::strategy::{strategy_from_name, LayerStrategy};
+use untanglers_core::svg::SvgOptions;
 use untanglers_core::utils;
 
-use crate::threading::run_in_thread;
+use crate::asyncio::spawn_future;
+use crate::config::{cooldown_config, optimize_config, ConfigOrStartTemp};
+use crate::generic::{InternedNode, Interner};
+use crate::nodes::{Extract, PyNodes};
+use crate::threading::{lock_optimizer, run_interruptible};
+
+/// The genetic configuration as pickled: population size, elite count, tournament size, mutation rate and whether local
+/// search is used.
+type GeneticState = (usize, usize, usize, f64, bool);
 
 fn to_pyerr(err: OptimizerError) -> PyErr {
   PyValueError::new_err(err.to_string())
 }
 
-macro_rules! optimizers {
-  ($ty: ty, $name1: ident, $name2: ident) => {
-    #[pyclass]
-    struct $name1 {
+/// Names the node or edge of an error about the given graph, see `OptimizerError::with_graph`.
+fn graph_error<'a, T: Debug>(
+  nodes: &'a [Vec<T>],
+  edges: &'a [Vec<(T, T, usize)>],
+) -> impl Fn(OptimizerError) -> PyErr + 'a {
+  move |err| to_pyerr(err.with_graph(nodes, edges))
+}
+
+fn stability_metric(metric: &str) -> PyResult<StabilityMetric> {
+  match metric {
+    "kendall" => Ok(StabilityMetric::KendallTau),
+    "displacement" => Ok(StabilityMetric::Displacement),
+    _ => Err(PyValueError::new_err(format!(
+      "Unknown stability metric {metric:?}, expected 'kendall' or 'displacement'"
+    ))),
+  }
+}
+
+/// Positions as used by networkx drawing functions: the layer index as x, and layers centred on each other along y with
+/// the first node on top.
+fn node_positions<T: Clone>(node_layers: Vec<Vec<T>>) -> Vec<(T, (f64, f64))> {
+  node_layers
+    .into_iter()
+    .enumerate()
+    .flat_map(|(layer_index, layer)| {
+      let node_count = layer.len();
+      layer
+        .into_iter()
+        .enumerate()
+        .map(move |(position, node)| (node, (layer_index as f64, -centred_position(position, node_count))))
+    })
+    .collect()
+}
+
+fn svg_options(highlight_crossings: bool, show_labels: bool) -> SvgOptions {
+  SvgOptions {
+    highlight_crossings,
+    show_labels,
+    ..Default::default()
+  }
+}
+
+/// Strategies are given by name, optionally with settings as `(name, {"setting": value})`.
+fn layer_strategies(strategies: &[Bound<'_, PyAny>]) -> PyResult<Vec<Box<dyn LayerStrategy>>> {
+  strategies
+    .iter()
+    .map(|strategy| {
+      let (name, params): (String, HashMap<String, f64>) = match strategy.extract::<String>() {
+        Ok(name) => (name, HashMap::new()),
+        Err(_) => strategy.extract()?,
+      };
+      strategy_from_name(&name, &params).map_err(to_pyerr)
+    })
+    .collect()
+}
+
+/// Groups are given as `{name: subgroups}`, the innermost groups hold a list of nodes.
+fn group_tree<T, N>(nodes: &mut N, tree: &Bound<'_, PyAny>) -> PyResult<Vec<GroupTree<T>>>
+where
+  T: Eq + Hash,
+  N: PyNodes<T>,
+{
+  match tree.downcast::<PyDict>() {
+    Ok(groups) => groups
+      .iter()
+      .map(|(name, children)| {
+        Ok(GroupTree::Group {
+          name: name.extract()?,
+          children: group_tree(nodes, &children)?,
+        })
+      })
+      .collect(),
+    Err(_) => tree
+      .iter()?
+      .map(|node| Ok(GroupTree::Node(nodes.node(&node?)?)))
+      .collect(),
+  }
+}
+
+fn group_tree_to_py<T: ToPyObject>(py: Python<'_>, tree: &[GroupTree<T>]) -> PyResult<PyObject> {
+  let groups = PyDict::new_bound(py);
+  let mut nodes = vec![];
+  for child in tree {
+    match child {
+      GroupTree::Group { name, children } => groups.set_item(name, group_tree_to_py(py, children)?)?,
+      GroupTree::Node(node) => nodes.push(node.to_object(py)),
+    }
+  }
+
+  if nodes.is_empty() && !tree.is_empty() {
+    Ok(groups.into_any().unbind())
+  } else {
+    Ok(nodes.to_object(py))
+  }
+}
+
+/// An objective implemented in python as `callback(layer_index, nodes) -> (matrix, cost)`, with the matrix flattened
+/// row by row.
+struct PyObjective {
+  callback: PyObject,
+}
+
+impl<T> Objective<T> for PyObjective
+where
+  T: Eq + Hash + Clone + ToPyObject,
+{
+  fn layer_matrix_and_cost(
+    &self,
+    optimizer: &Optimizer<T>,
+    layer_index: usize,
+  ) -> Result<(Vec<f64>, f64), OptimizerError> {
+    Python::with_gil(|py| {
+      self
+        .callback
+        .call1(py, (layer_index, optimizer.layer_nodes(layer_index).to_object(py)))
+        .and_then(|result| result.extract(py))
+    })
+    .map_err(|err| OptimizerError::ObjectiveError {
+      message: err.to_string(),
+    })
+  }
+}
+
+/**
+ * The optimizer classes are generated for every node type: `$ty` is the node type of the optimizer and `$nodes` converts
+ * the nodes given from Python to it, see `nodes::PyNodes`. Methods that only exist for some node types, e.g.
+ * `from_networkx`, are passed as a trailing block.
+ */
+macro_rules! layout_optimizer {
+  ($name: ident, $pyname: literal, $ty: ty, $nodes: ty $(, { $($extra: tt)* })?) => {
+    #[pyclass(module = "untanglers", name = $pyname)]
+    pub struct $name {
       inner: Arc<Mutex<core::layout_optimizer::LayoutOptimizer<$ty>>>,
+      nodes: $nodes,
     }
 
     #[pymethods]
-    impl $name1 {
+    impl $name {
       #[new]
-      pub fn layout_optimizer_new(nodes_left: Vec<Vec<$ty>>, edges: Vec<Vec<($ty, $ty, usize)>>) -> PyResult<Self> {
-        let inner = core::layout_optimizer::LayoutOptimizer::<$ty>::new(nodes_left, edges).map_err(to_pyerr)?;
+      #[pyo3(signature = (nodes_left, edges, intra_edges=None))]
+      pub fn layout_optimizer_new(
+        py: Python<'_>,
+        nodes_left: &Bound<'_, PyAny>,
+        edges: &Bound<'_, PyAny>,
+        intra_edges: Option<&Bound<'_, PyAny>>,
+      ) -> PyResult<Self> {
+        let mut nodes = <$nodes>::new(py);
+        let (node_layers, edges) = (nodes.node_layers(nodes_left)?, nodes.edges(edges)?);
+        let mut inner = core::layout_optimizer::LayoutOptimizer::<$ty>::new(node_layers.clone(), edges.clone())
+          .map_err(graph_error(&node_layers, &edges))?;
+        if let Some(intra_edges) = intra_edges {
+          let intra_edges = nodes.edges(intra_edges)?;
+          inner
+            .set_intra_edges(intra_edges.clone())
+            .map_err(graph_error(&node_layers, &intra_edges))?;
+        }
         Ok(Self {
           inner: Arc::new(Mutex::new(inner)),
+          nodes,
         })
       }
 
-      pub fn swap_nodes(&mut self, temperature: f64, max_iterations: usize, layer_index: usize) -> PyResult<usize> {
-        let inner = Arc::clone(&self.inner);
-        Python::with_gil(|py| {
-          run_in_thread(py, move || {
-            let mut guard = inner.lock().unwrap();
-            guard.swap_nodes(temperature, max_iterations, layer_index)
-          })
-        })
+      pub fn swap_nodes(
+        &mut self,
+        py: Python<'_>,
+        temperature: f64,
+        max_iterations: usize,
+        layer_index: usize,
+      ) -> PyResult<usize> {
+        run_interruptible(py, &self.inner, move |inner| inner.swap_nodes(temperature, max_iterations, layer_index))
       }
 
       pub fn cooldown(
@@ -51,56 +223,314 @@ macro_rules! optimizers {
         max_iterations: usize,
         layer_index: usize,
       ) -> PyResult<usize> {
-        let inner = Arc::clone(&self.inner);
-        run_in_thread(py, move || {
-          let mut guard = inner.lock().unwrap();
-          guard.cooldown(start_temp, end_temp, steps, max_iterations, layer_index)
-        })
+        let config = cooldown_config(start_temp, end_temp, steps, max_iterations)?;
+        run_interruptible(py, &self.inner, move |inner| inner.cooldown(&config, layer_index))
       }
 
-      pub fn optimize(
+      pub fn tabu_search(
         &mut self,
         py: Python<'_>,
-        start_temp: f64,
-        end_temp: f64,
-        steps: usize,
+        tenure: usize,
         max_iterations: usize,
+        layer_index: usize,
+      ) -> PyResult<usize> {
+        run_interruptible(py, &self.inner, move |inner| inner.tabu_search(tenure, max_iterations, layer_index))
+      }
+
+      #[pyo3(signature = (start_temp=None, end_temp=None, steps=None, max_iterations=None, passes=None))]
+      pub fn optimize(
+        &mut self,
+        py: Python<'_>,
+        start_temp: Option<ConfigOrStartTemp>,
+        end_temp: Option<f64>,
+        steps: Option<usize>,
+        max_iterations: Option<usize>,
+        passes: Option<usize>,
+      ) -> PyResult<usize> {
+        let config = optimize_config(start_temp, end_temp, steps, max_iterations, passes)?;
+        run_interruptible(py, &self.inner, move |inner| inner.optimize(&config))
+      }
+
+      /// Like `optimize`, without blocking: returns a future of the running event loop, cancelling it stops the
+      /// optimization.
+      #[pyo3(signature = (start_temp=None, end_temp=None, steps=None, max_iterations=None, passes=None))]
+      pub fn optimize_async<'py>(
+        &self,
+        py: Python<'py>,
+        start_temp: Option<ConfigOrStartTemp>,
+        end_temp: Option<f64>,
+        steps: Option<usize>,
+        max_iterations: Option<usize>,
+        passes: Option<usize>,
+      ) -> PyResult<Bound<'py, PyAny>> {
+        let config = optimize_config(start_temp, end_temp, steps, max_iterations, passes)?;
+        spawn_future(py, &self.inner, move |inner| inner.optimize(&config))
+      }
+
+      pub fn optimize_with(
+        &mut self,
+        py: Python<'_>,
+        strategies: Vec<Bound<'_, PyAny>>,
         passes: usize,
       ) -> PyResult<usize> {
-        let inner = Arc::clone(&self.inner);
-        run_in_thread(py, move || {
-          let mut guard = inner.lock().unwrap();
-          guard.optimize(start_temp, end_temp, steps, max_iterations, passes)
-        })
+        let strategies = layer_strategies(&strategies)?;
+        run_interruptible(py, &self.inner, move |inner| inner.optimize_with(&strategies, passes))
+      }
+
+      #[pyo3(signature = (two_opt=false, block_reversal=false))]
+      pub fn polish(&mut self, py: Python<'_>, two_opt: bool, block_reversal: bool) -> PyResult<usize> {
+        run_interruptible(py, &self.inner, move |inner| inner.polish(two_opt, block_reversal))
       }
 
-      pub fn get_nodes(&self) -> Vec<Vec<$ty>> {
+      #[pyo3(signature = (max_nodes=30, time_limit=None))]
+      pub fn solve_exact(
+        &mut self,
+        py: Python<'_>,
+        max_nodes: usize,
+        time_limit: Option<f64>,
+      ) -> PyResult<(usize, bool)> {
+        let time_limit = time_limit
+          .map(Duration::try_from_secs_f64)
+          .transpose()
+          .map_err(|err| PyValueError::new_err(format!("Invalid time limit: {err}")))?;
+        run_interruptible(py, &self.inner, move |inner| inner.solve_exact(max_nodes, time_limit))
+      }
+
+      pub fn get_nodes(&self, py: Python<'_>) -> PyResult<PyObject> {
         // cheap read; no thread needed
-        self.inner.lock().unwrap().get_nodes()
+        Ok(lock_optimizer(py, &self.inner)?.get_nodes().to_object(py))
+      }
+
+      /// `{node: (x, y)}` for drawing with networkx
+      pub fn to_networkx_positions<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        Ok(node_positions(lock_optimizer(py, &self.inner)?.get_nodes()).into_py_dict_bound(py))
+      }
+
+      /// Arguments for the empty optimizer that unpickling restores the state into
+      pub fn __getnewargs__(&self) -> (Vec<Vec<PyObject>>, Vec<Vec<PyObject>>) {
+        (vec![vec![]], vec![])
+      }
+
+      /// The nodes and a compact binary snapshot of the rest, see `snapshot::Snapshot`
+      pub fn __getstate__<'py>(&self, py: Python<'py>) -> PyResult<(PyObject, Bound<'py, PyBytes>)> {
+        let (nodes, bytes) = lock_optimizer(py, &self.inner)?.to_snapshot().map_err(to_pyerr)?;
+        Ok((nodes.to_object(py), PyBytes::new_bound(py, &bytes)))
+      }
+
+      pub fn __setstate__(&mut self, py: Python<'_>, state: (Bound<'_, PyAny>, Bound<'_, PyBytes>)) -> PyResult<()> {
+        let (node_layers, bytes) = state;
+        let mut nodes = <$nodes>::new(py);
+        let node_layers = nodes.node_layers(&node_layers)?;
+        let inner = core::layout_optimizer::LayoutOptimizer::from_snapshot(node_layers, bytes.as_bytes()).map_err(to_pyerr)?;
+        *lock_optimizer(py, &self.inner)? = inner;
+        self.nodes = nodes;
+        Ok(())
+      }
+
+      /// Objectives are shared with the copy
+      pub fn __copy__(&self, py: Python<'_>) -> PyResult<Self> {
+        Ok(Self {
+          inner: Arc::new(Mutex::new(lock_optimizer(py, &self.inner)?.clone())),
+          nodes: self.nodes.clone_ref(py)?,
+        })
+      }
+
+      pub fn __deepcopy__(&self, py: Python<'_>, _memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        self.__copy__(py)
+      }
+
+      pub fn count_crossings(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(lock_optimizer(py, &self.inner)?.count_crossings())
+      }
+
+      #[pyo3(signature = (highlight_crossings=false, show_labels=true))]
+      pub fn to_svg(&self, py: Python<'_>, highlight_crossings: bool, show_labels: bool) -> PyResult<String> {
+        Ok(lock_optimizer(py, &self.inner)?
+          .to_svg(&svg_options(highlight_crossings, show_labels)))
+      }
+
+      pub fn add_node(&mut self, py: Python<'_>, layer_index: usize, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        let node = self.nodes.node(node)?;
+        lock_optimizer(py, &self.inner)?
+          .add_node(layer_index, node.clone())
+          .map_err(|err| to_pyerr(err.with_node(&node)))
+      }
+
+      pub fn add_edge(
+        &mut self,
+        py: Python<'_>,
+        layer_index: usize,
+        node_a: &Bound<'_, PyAny>,
+        node_b: &Bound<'_, PyAny>,
+        weight: usize,
+      ) -> PyResult<()> {
+        let (node_a, node_b) = (self.nodes.node(node_a)?, self.nodes.node(node_b)?);
+        lock_optimizer(py, &self.inner)?
+          .add_edge(layer_index, node_a.clone(), node_b.clone(), weight)
+          .map_err(|err| to_pyerr(err.with_edge(&node_a, &node_b)))
+      }
+
+      pub fn remove_edge(
+        &mut self,
+        py: Python<'_>,
+        layer_index: usize,
+        node_a: &Bound<'_, PyAny>,
+        node_b: &Bound<'_, PyAny>,
+      ) -> PyResult<usize> {
+        let (node_a, node_b) = (self.nodes.lookup(node_a)?, self.nodes.lookup(node_b)?);
+        lock_optimizer(py, &self.inner)?
+          .remove_edge(layer_index, &node_a, &node_b)
+          .map_err(|err| to_pyerr(err.with_edge(&node_a, &node_b)))
+      }
+
+      pub fn set_weight(
+        &mut self,
+        py: Python<'_>,
+        layer_index: usize,
+        node_a: &Bound<'_, PyAny>,
+        node_b: &Bound<'_, PyAny>,
+        weight: usize,
+      ) -> PyResult<()> {
+        let (node_a, node_b) = (self.nodes.lookup(node_a)?, self.nodes.lookup(node_b)?);
+        lock_optimizer(py, &self.inner)?
+          .set_weight(layer_index, &node_a, &node_b, weight)
+          .map_err(|err| to_pyerr(err.with_edge(&node_a, &node_b)))
+      }
+
+      pub fn remove_node(&mut self, py: Python<'_>, layer_index: usize, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        let node = self.nodes.lookup(node)?;
+        lock_optimizer(py, &self.inner)?
+          .remove_node(layer_index, &node)
+          .map_err(|err| to_pyerr(err.with_node(&node)))
+      }
+
+      #[pyo3(signature = (reference, metric="kendall", weight=1.))]
+      pub fn set_reference(
+        &mut self,
+        py: Python<'_>,
+        reference: &Bound<'_, PyAny>,
+        metric: &str,
+        weight: f64,
+      ) -> PyResult<()> {
+        let reference = self.nodes.node_layers(reference)?;
+        lock_optimizer(py, &self.inner)?
+          .set_reference(&reference, stability_metric(metric)?, weight)
+          .map_err(to_pyerr)
+      }
+
+      pub fn clear_reference(&mut self, py: Python<'_>) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?.clear_reference();
+        Ok(())
       }
 
-      pub fn count_crossings(&self) -> usize {
-        self.inner.lock().unwrap().count_crossings()
+      pub fn set_edge_length_weight(&mut self, py: Python<'_>, weight: f64) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?.set_edge_length_weight(weight).map_err(to_pyerr)
       }
+
+      pub fn edge_length(&self, py: Python<'_>) -> PyResult<f64> {
+        Ok(lock_optimizer(py, &self.inner)?.edge_length())
+      }
+
+      #[pyo3(signature = (callback, weight=1.))]
+      pub fn add_objective(&mut self, py: Python<'_>, callback: PyObject, weight: f64) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?
+          .add_objective(Box::new(PyObjective { callback }), weight);
+        Ok(())
+      }
+
+      #[pyo3(signature = (labels, weight=1.))]
+      pub fn add_group_cohesion(&mut self, py: Python<'_>, labels: &Bound<'_, PyAny>, weight: f64) -> PyResult<()> {
+        let labels = self.nodes.node_maps(labels)?;
+        lock_optimizer(py, &self.inner)?
+          .add_objective(Box::new(GroupCohesion::new(labels)), weight);
+        Ok(())
+      }
+
+      pub fn clear_objectives(&mut self, py: Python<'_>) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?.clear_objectives();
+        Ok(())
+      }
+
+      pub fn cost(&self, py: Python<'_>) -> PyResult<f64> {
+        lock_optimizer(py, &self.inner)?.cost().map_err(to_pyerr)
+      }
+
+      $($($extra)*)?
     }
+  };
+}
 
-    #[pyclass]
-    struct $name2 {
+macro_rules! hierarchy_optimizer {
+  ($name: ident, $pyname: literal, $ty: ty, $nodes: ty $(, { $($extra: tt)* })?) => {
+    #[pyclass(module = "untanglers", name = $pyname)]
+    pub struct $name {
       inner: Arc<Mutex<core::hierarchy_optimizer::HierarchyOptimizer<$ty>>>,
+      nodes: $nodes,
     }
 
     #[pymethods]
-    impl $name2 {
+    impl $name {
       #[new]
+      #[pyo3(signature = (nodes_left, edges, hierarchy, intra_edges=None))]
       pub fn layout_optimizer_new(
-        nodes_left: Vec<Vec<$ty>>,
-        edges: Vec<Vec<($ty, $ty, usize)>>,
+        py: Python<'_>,
+        nodes_left: &Bound<'_, PyAny>,
+        edges: &Bound<'_, PyAny>,
         hierarchy: Hierarchy,
+        intra_edges: Option<&Bound<'_, PyAny>>,
       ) -> PyResult<Self> {
+        let mut nodes = <$nodes>::new(py);
+        let (node_layers, edges) = (nodes.node_layers(nodes_left)?, nodes.edges(edges)?);
+        let mut inner =
+          core::hierarchy_optimizer::HierarchyOptimizer::<$ty>::new(node_layers.clone(), edges.clone(), hierarchy)
+            .map_err(graph_error(&node_layers, &edges))?;
+        if let Some(intra_edges) = intra_edges {
+          let intra_edges = nodes.edges(intra_edges)?;
+          inner
+            .set_intra_edges(intra_edges.clone())
+            .map_err(graph_error(&node_layers, &intra_edges))?;
+        }
+        Ok(Self {
+          inner: Arc::new(Mutex::new(inner)),
+          nodes,
+        })
+      }
+
+      /// Nodes and hierarchy given per layer as `{group: {subgroup: [nodes]}}`, the initial order follows the tree.
+      #[staticmethod]
+      pub fn from_group_tree(py: Python<'_>, trees: Vec<Bound<'_, PyAny>>, edges: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let mut nodes = <$nodes>::new(py);
+        let trees = trees
+          .iter()
+          .map(|tree| group_tree(&mut nodes, tree))
+          .collect::<PyResult<Vec<_>>>()?;
+        let edges = nodes.edges(edges)?;
+        let node_layers = trees.iter().map(|tree| tree_nodes(tree)).collect::<Vec<_>>();
+        let inner = core::hierarchy_optimizer::HierarchyOptimizer::<$ty>::from_group_tree(trees, edges.clone())
+          .map_err(graph_error(&node_layers, &edges))?;
+        Ok(Self {
+          inner: Arc::new(Mutex::new(inner)),
+          nodes,
+        })
+      }
+
+      /// Hierarchy given per layer as `{node: [group, subgroup]}`, nodes are reordered so every group is contiguous.
+      #[staticmethod]
+      pub fn from_group_paths(
+        py: Python<'_>,
+        nodes: &Bound<'_, PyAny>,
+        edges: &Bound<'_, PyAny>,
+        paths: &Bound<'_, PyAny>,
+      ) -> PyResult<Self> {
+        let mut converter = <$nodes>::new(py);
+        let (node_layers, edges) = (converter.node_layers(nodes)?, converter.edges(edges)?);
+        let paths = converter.node_maps(paths)?;
         let inner =
-          core::hierarchy_optimizer::HierarchyOptimizer::<$ty>::new(nodes_left, edges, hierarchy).map_err(to_pyerr)?;
+          core::hierarchy_optimizer::HierarchyOptimizer::<$ty>::from_group_paths(node_layers.clone(), edges.clone(), paths)
+            .map_err(graph_error(&node_layers, &edges))?;
         Ok(Self {
           inner: Arc::new(Mutex::new(inner)),
+          nodes: converter,
         })
       }
 
@@ -113,10 +543,8 @@ macro_rules! optimizers {
         layer_index: usize,
         granularity: Option<usize>,
       ) -> PyResult<usize> {
-        let inner = Arc::clone(&self.inner);
-        run_in_thread(py, move || {
-          let mut guard = inner.lock().unwrap();
-          guard.swap_nodes(temperature, max_iterations, layer_index, granularity)
+        run_interruptible(py, &self.inner, move |inner| {
+          inner.swap_nodes(temperature, max_iterations, layer_index, granularity)
         })
       }
 
@@ -132,46 +560,530 @@ macro_rules! optimizers {
         layer_index: usize,
         granularity: Option<usize>,
       ) -> PyResult<usize> {
-        let inner = Arc::clone(&self.inner);
-        run_in_thread(py, move || {
-          let mut guard = inner.lock().unwrap();
-          guard.cooldown(start_temp, end_temp, steps, max_iterations, layer_index, granularity)
+        let config = cooldown_config(start_temp, end_temp, steps, max_iterations)?;
+        run_interruptible(py, &self.inner, move |inner| inner.cooldown(&config, layer_index, granularity))
+      }
+
+      #[pyo3(signature = (tenure, max_iterations, layer_index, granularity))]
+      pub fn tabu_search(
+        &mut self,
+        py: Python<'_>,
+        tenure: usize,
+        max_iterations: usize,
+        layer_index: usize,
+        granularity: Option<usize>,
+      ) -> PyResult<usize> {
+        run_interruptible(py, &self.inner, move |inner| {
+          inner.tabu_search(tenure, max_iterations, layer_index, granularity)
         })
       }
 
+      #[pyo3(signature = (start_temp=None, end_temp=None, steps=None, max_iterations=None, passes=None))]
       pub fn optimize(
         &mut self,
         py: Python<'_>,
-        start_temp: f64,
-        end_temp: f64,
-        steps: usize,
-        max_iterations: usize,
+        start_temp: Option<ConfigOrStartTemp>,
+        end_temp: Option<f64>,
+        steps: Option<usize>,
+        max_iterations: Option<usize>,
+        passes: Option<usize>,
+      ) -> PyResult<usize> {
+        let config = optimize_config(start_temp, end_temp, steps, max_iterations, passes)?;
+        run_interruptible(py, &self.inner, move |inner| inner.optimize(&config))
+      }
+
+      /// Like `optimize`, without blocking: returns a future of the running event loop, cancelling it stops the
+      /// optimization.
+      #[pyo3(signature = (start_temp=None, end_temp=None, steps=None, max_iterations=None, passes=None))]
+      pub fn optimize_async<'py>(
+        &self,
+        py: Python<'py>,
+        start_temp: Option<ConfigOrStartTemp>,
+        end_temp: Option<f64>,
+        steps: Option<usize>,
+        max_iterations: Option<usize>,
+        passes: Option<usize>,
+      ) -> PyResult<Bound<'py, PyAny>> {
+        let config = optimize_config(start_temp, end_temp, steps, max_iterations, passes)?;
+        spawn_future(py, &self.inner, move |inner| inner.optimize(&config))
+      }
+
+      pub fn optimize_with(
+        &mut self,
+        py: Python<'_>,
+        strategies: Vec<Bound<'_, PyAny>>,
         passes: usize,
       ) -> PyResult<usize> {
-        let inner = Arc::clone(&self.inner);
-        run_in_thread(py, move || {
-          let mut guard = inner.lock().unwrap();
-          guard.optimize(start_temp, end_temp, steps, max_iterations, passes)
+        let strategies = layer_strategies(&strategies)?;
+        run_interruptible(py, &self.inner, move |inner| inner.optimize_with(&strategies, passes))
+      }
+
+      #[pyo3(signature = (two_opt=false, block_reversal=false))]
+      pub fn polish(&mut self, py: Python<'_>, two_opt: bool, block_reversal: bool) -> PyResult<usize> {
+        run_interruptible(py, &self.inner, move |inner| inner.polish(two_opt, block_reversal))
+      }
+
+      pub fn get_nodes(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(lock_optimizer(py, &self.inner)?.get_nodes().to_object(py))
+      }
+
+      /// `{node: (x, y)}` for drawing with networkx
+      pub fn to_networkx_positions<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        Ok(node_positions(lock_optimizer(py, &self.inner)?.get_nodes()).into_py_dict_bound(py))
+      }
+
+      /// Arguments for the empty optimizer that unpickling restores the state into
+      pub fn __getnewargs__(&self) -> (Vec<Vec<PyObject>>, Vec<Vec<PyObject>>, Hierarchy) {
+        (vec![vec![]], vec![], vec![vec![]])
+      }
+
+      /// The nodes and a compact binary snapshot of the rest, see `snapshot::Snapshot`
+      pub fn __getstate__<'py>(&self, py: Python<'py>) -> PyResult<(PyObject, Bound<'py, PyBytes>)> {
+        let (nodes, bytes) = lock_optimizer(py, &self.inner)?.to_snapshot().map_err(to_pyerr)?;
+        Ok((nodes.to_object(py), PyBytes::new_bound(py, &bytes)))
+      }
+
+      pub fn __setstate__(&mut self, py: Python<'_>, state: (Bound<'_, PyAny>, Bound<'_, PyBytes>)) -> PyResult<()> {
+        let (node_layers, bytes) = state;
+        let mut nodes = <$nodes>::new(py);
+        let node_layers = nodes.node_layers(&node_layers)?;
+        let inner =
+          core::hierarchy_optimizer::HierarchyOptimizer::from_snapshot(node_layers, bytes.as_bytes()).map_err(to_pyerr)?;
+        *lock_optimizer(py, &self.inner)? = inner;
+        self.nodes = nodes;
+        Ok(())
+      }
+
+      /// Objectives are shared with the copy
+      pub fn __copy__(&self, py: Python<'_>) -> PyResult<Self> {
+        Ok(Self {
+          inner: Arc::new(Mutex::new(lock_optimizer(py, &self.inner)?.clone())),
+          nodes: self.nodes.clone_ref(py)?,
         })
       }
 
-      pub fn get_nodes(&self) -> Vec<Vec<$ty>> {
-        self.inner.lock().unwrap().get_nodes()
+      pub fn __deepcopy__(&self, py: Python<'_>, _memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        self.__copy__(py)
+      }
+
+      pub fn get_hierarchy(&self, py: Python<'_>) -> PyResult<Hierarchy> {
+        Ok(lock_optimizer(py, &self.inner)?.get_hierarchy())
+      }
+
+      pub fn get_group_names(&self, py: Python<'_>) -> PyResult<GroupNames> {
+        Ok(lock_optimizer(py, &self.inner)?.get_group_names())
       }
 
-      pub fn count_crossings(&self) -> usize {
-        self.inner.lock().unwrap().count_crossings()
+      pub fn get_group_order(&self, py: Python<'_>, layer_index: usize, granularity: usize) -> PyResult<Vec<String>> {
+        lock_optimizer(py, &self.inner)?
+          .get_group_order(layer_index, granularity)
+          .map_err(to_pyerr)
       }
+
+      /// Compound groups as `(name, first_layer, last_layer)`.
+      pub fn set_compound_groups(&mut self, py: Python<'_>, groups: Vec<(String, usize, usize)>) -> PyResult<()> {
+        let groups = groups
+          .into_iter()
+          .map(|(name, first_layer, last_layer)| CompoundGroup {
+            name,
+            first_layer,
+            last_layer,
+          })
+          .collect();
+        lock_optimizer(py, &self.inner)?.set_compound_groups(groups).map_err(to_pyerr)
+      }
+
+      pub fn get_compound_order(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        Ok(lock_optimizer(py, &self.inner)?.get_compound_order())
+      }
+
+      pub fn get_group_tree(&self, py: Python<'_>, layer_index: usize) -> PyResult<PyObject> {
+        let tree = lock_optimizer(py, &self.inner)?.get_group_tree(layer_index).map_err(to_pyerr)?;
+        group_tree_to_py(py, &tree)
+      }
+
+      /// Node crossings, or with `granularity` the crossings between the bundles of edges that connect groups
+      #[pyo3(signature = (granularity=None))]
+      pub fn count_crossings(&self, py: Python<'_>, granularity: Option<usize>) -> PyResult<usize> {
+        let optimizer = lock_optimizer(py, &self.inner)?;
+        match granularity {
+          None => Ok(optimizer.count_crossings()),
+          Some(granularity) => optimizer.count_group_crossings(granularity).map_err(to_pyerr),
+        }
+      }
+
+      pub fn group_crossings(&self, py: Python<'_>, granularity: usize) -> PyResult<Vec<Vec<(String, usize)>>> {
+        lock_optimizer(py, &self.inner)?
+          .group_crossings(granularity)
+          .map_err(to_pyerr)
+      }
+
+      #[pyo3(signature = (highlight_crossings=false, show_labels=true))]
+      pub fn to_svg(&self, py: Python<'_>, highlight_crossings: bool, show_labels: bool) -> PyResult<String> {
+        Ok(lock_optimizer(py, &self.inner)?
+          .to_svg(&svg_options(highlight_crossings, show_labels)))
+      }
+
+      #[pyo3(signature = (layer_index, node, group_index=None))]
+      pub fn add_node(
+        &mut self,
+        py: Python<'_>,
+        layer_index: usize,
+        node: &Bound<'_, PyAny>,
+        group_index: Option<usize>,
+      ) -> PyResult<()> {
+        let node = self.nodes.node(node)?;
+        lock_optimizer(py, &self.inner)?
+          .add_node(layer_index, node.clone(), group_index)
+          .map_err(|err| to_pyerr(err.with_node(&node)))
+      }
+
+      pub fn add_edge(
+        &mut self,
+        py: Python<'_>,
+        layer_index: usize,
+        node_a: &Bound<'_, PyAny>,
+        node_b: &Bound<'_, PyAny>,
+        weight: usize,
+      ) -> PyResult<()> {
+        let (node_a, node_b) = (self.nodes.node(node_a)?, self.nodes.node(node_b)?);
+        lock_optimizer(py, &self.inner)?
+          .add_edge(layer_index, node_a.clone(), node_b.clone(), weight)
+          .map_err(|err| to_pyerr(err.with_edge(&node_a, &node_b)))
+      }
+
+      pub fn remove_edge(
+        &mut self,
+        py: Python<'_>,
+        layer_index: usize,
+        node_a: &Bound<'_, PyAny>,
+        node_b: &Bound<'_, PyAny>,
+      ) -> PyResult<usize> {
+        let (node_a, node_b) = (self.nodes.lookup(node_a)?, self.nodes.lookup(node_b)?);
+        lock_optimizer(py, &self.inner)?
+          .remove_edge(layer_index, &node_a, &node_b)
+          .map_err(|err| to_pyerr(err.with_edge(&node_a, &node_b)))
+      }
+
+      pub fn set_weight(
+        &mut self,
+        py: Python<'_>,
+        layer_index: usize,
+        node_a: &Bound<'_, PyAny>,
+        node_b: &Bound<'_, PyAny>,
+        weight: usize,
+      ) -> PyResult<()> {
+        let (node_a, node_b) = (self.nodes.lookup(node_a)?, self.nodes.lookup(node_b)?);
+        lock_optimizer(py, &self.inner)?
+          .set_weight(layer_index, &node_a, &node_b, weight)
+          .map_err(|err| to_pyerr(err.with_edge(&node_a, &node_b)))
+      }
+
+      pub fn remove_node(&mut self, py: Python<'_>, layer_index: usize, node: &Bound<'_, PyAny>) -> PyResult<()> {
+        let node = self.nodes.lookup(node)?;
+        lock_optimizer(py, &self.inner)?
+          .remove_node(layer_index, &node)
+          .map_err(|err| to_pyerr(err.with_node(&node)))
+      }
+
+      #[pyo3(signature = (reference, metric="kendall", weight=1.))]
+      pub fn set_reference(
+        &mut self,
+        py: Python<'_>,
+        reference: &Bound<'_, PyAny>,
+        metric: &str,
+        weight: f64,
+      ) -> PyResult<()> {
+        let reference = self.nodes.node_layers(reference)?;
+        lock_optimizer(py, &self.inner)?
+          .set_reference(&reference, stability_metric(metric)?, weight)
+          .map_err(to_pyerr)
+      }
+
+      pub fn clear_reference(&mut self, py: Python<'_>) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?.clear_reference();
+        Ok(())
+      }
+
+      pub fn set_edge_length_weight(&mut self, py: Python<'_>, weight: f64) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?.set_edge_length_weight(weight).map_err(to_pyerr)
+      }
+
+      pub fn edge_length(&self, py: Python<'_>) -> PyResult<f64> {
+        Ok(lock_optimizer(py, &self.inner)?.edge_length())
+      }
+
+      #[pyo3(signature = (callback, weight=1.))]
+      pub fn add_objective(&mut self, py: Python<'_>, callback: PyObject, weight: f64) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?
+          .add_objective(Box::new(PyObjective { callback }), weight);
+        Ok(())
+      }
+
+      #[pyo3(signature = (labels, weight=1.))]
+      pub fn add_group_cohesion(&mut self, py: Python<'_>, labels: &Bound<'_, PyAny>, weight: f64) -> PyResult<()> {
+        let labels = self.nodes.node_maps(labels)?;
+        lock_optimizer(py, &self.inner)?
+          .add_objective(Box::new(GroupCohesion::new(labels)), weight);
+        Ok(())
+      }
+
+      pub fn clear_objectives(&mut self, py: Python<'_>) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?.clear_objectives();
+        Ok(())
+      }
+
+      pub fn cost(&self, py: Python<'_>) -> PyResult<f64> {
+        lock_optimizer(py, &self.inner)?.cost().map_err(to_pyerr)
+      }
+
+      $($($extra)*)?
     }
   };
 }
 
-optimizers!(String, LayoutOptimizerString, HierarchyOptimizerString);
-optimizers!(i32, LayoutOptimizerInt, HierarchyOptimizerInt);
+macro_rules! genetic_optimizer {
+  ($name: ident, $pyname: literal, $ty: ty, $nodes: ty $(, { $($extra: tt)* })?) => {
+    #[pyclass(module = "untanglers", name = $pyname)]
+    pub struct $name {
+      inner: Arc<Mutex<core::genetic_optimizer::GeneticOptimizer<$ty>>>,
+      nodes: $nodes,
+    }
+
+    #[pymethods]
+    impl $name {
+      #[new]
+      #[allow(clippy::too_many_arguments)]
+      #[pyo3(signature = (nodes_left, edges, hierarchy=None, population_size=20, elite_count=2, tournament_size=3, mutation_rate=0.2, local_search=true))]
+      pub fn genetic_optimizer_new(
+        py: Python<'_>,
+        nodes_left: &Bound<'_, PyAny>,
+        edges: &Bound<'_, PyAny>,
+        hierarchy: Option<Hierarchy>,
+        population_size: usize,
+        elite_count: usize,
+        tournament_size: usize,
+        mutation_rate: f64,
+        local_search: bool,
+      ) -> PyResult<Self> {
+        let config = GeneticConfig {
+          population_size,
+          elite_count,
+          tournament_size,
+          mutation_rate,
+          local_search: GeneticConfig::default().local_search.filter(|_| local_search),
+        };
+        let mut nodes = <$nodes>::new(py);
+        let (node_layers, edges) = (nodes.node_layers(nodes_left)?, nodes.edges(edges)?);
+        let inner =
+          core::genetic_optimizer::GeneticOptimizer::<$ty>::new(node_layers.clone(), edges.clone(), hierarchy, config)
+            .map_err(graph_error(&node_layers, &edges))?;
+        Ok(Self {
+          inner: Arc::new(Mutex::new(inner)),
+          nodes,
+        })
+      }
+
+      pub fn optimize(&mut self, py: Python<'_>, generations: usize) -> PyResult<usize> {
+        run_interruptible(py, &self.inner, move |inner| inner.optimize(generations))
+      }
+
+      /// Like `optimize`, without blocking: returns a future of the running event loop, cancelling it stops the
+      /// optimization.
+      pub fn optimize_async<'py>(&self, py: Python<'py>, generations: usize) -> PyResult<Bound<'py, PyAny>> {
+        spawn_future(py, &self.inner, move |inner| inner.optimize(generations))
+      }
+
+      pub fn get_nodes(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(lock_optimizer(py, &self.inner)?.get_nodes().to_object(py))
+      }
+
+      /// `{node: (x, y)}` for drawing with networkx
+      pub fn to_networkx_positions<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        Ok(node_positions(lock_optimizer(py, &self.inner)?.get_nodes()).into_py_dict_bound(py))
+      }
+
+      /// Arguments for the empty optimizer that unpickling restores the state into
+      pub fn __getnewargs__(&self) -> (Vec<Vec<PyObject>>, Vec<Vec<PyObject>>) {
+        (vec![vec![]], vec![])
+      }
+
+      /// The nodes, a compact binary snapshot of the rest and the configuration, see `snapshot::Snapshot`
+      pub fn __getstate__<'py>(&self, py: Python<'py>) -> PyResult<(PyObject, Bound<'py, PyBytes>, GeneticState)> {
+        let inner = lock_optimizer(py, &self.inner)?;
+        let (nodes, bytes) = inner.to_snapshot().map_err(to_pyerr)?;
+        let config = &inner.config;
+        let state = (
+          config.population_size,
+          config.elite_count,
+          config.tournament_size,
+          config.mutation_rate,
+          config.local_search.is_some(),
+        );
+        Ok((nodes.to_object(py), PyBytes::new_bound(py, &bytes), state))
+      }
+
+      pub fn __setstate__(
+        &mut self,
+        py: Python<'_>,
+        state: (Bound<'_, PyAny>, Bound<'_, PyBytes>, GeneticState),
+      ) -> PyResult<()> {
+        let (node_layers, bytes, (population_size, elite_count, tournament_size, mutation_rate, local_search)) = state;
+        let mut nodes = <$nodes>::new(py);
+        let node_layers = nodes.node_layers(&node_layers)?;
+        let config = GeneticConfig {
+          population_size,
+          elite_count,
+          tournament_size,
+          mutation_rate,
+          local_search: GeneticConfig::default().local_search.filter(|_| local_search),
+        };
+        *lock_optimizer(py, &self.inner)? =
+          core::genetic_optimizer::GeneticOptimizer::from_snapshot(node_layers, bytes.as_bytes(), config).map_err(to_pyerr)?;
+        self.nodes = nodes;
+        Ok(())
+      }
+
+      /// Objectives are shared with the copy
+      pub fn __copy__(&self, py: Python<'_>) -> PyResult<Self> {
+        Ok(Self {
+          inner: Arc::new(Mutex::new(lock_optimizer(py, &self.inner)?.clone())),
+          nodes: self.nodes.clone_ref(py)?,
+        })
+      }
+
+      pub fn __deepcopy__(&self, py: Python<'_>, _memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        self.__copy__(py)
+      }
+
+      pub fn get_hierarchy(&self, py: Python<'_>) -> PyResult<Hierarchy> {
+        Ok(lock_optimizer(py, &self.inner)?.get_hierarchy())
+      }
+
+      pub fn count_crossings(&self, py: Python<'_>) -> PyResult<usize> {
+        Ok(lock_optimizer(py, &self.inner)?.count_crossings())
+      }
+
+      #[pyo3(signature = (highlight_crossings=false, show_labels=true))]
+      pub fn to_svg(&self, py: Python<'_>, highlight_crossings: bool, show_labels: bool) -> PyResult<String> {
+        Ok(lock_optimizer(py, &self.inner)?
+          .to_svg(&svg_options(highlight_crossings, show_labels)))
+      }
+
+      #[pyo3(signature = (reference, metric="kendall", weight=1.))]
+      pub fn set_reference(
+        &mut self,
+        py: Python<'_>,
+        reference: &Bound<'_, PyAny>,
+        metric: &str,
+        weight: f64,
+      ) -> PyResult<()> {
+        let reference = self.nodes.node_layers(reference)?;
+        lock_optimizer(py, &self.inner)?
+          .set_reference(&reference, stability_metric(metric)?, weight)
+          .map_err(to_pyerr)
+      }
+
+      pub fn clear_reference(&mut self, py: Python<'_>) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?.clear_reference();
+        Ok(())
+      }
+
+      pub fn set_edge_length_weight(&mut self, py: Python<'_>, weight: f64) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?.set_edge_length_weight(weight).map_err(to_pyerr)
+      }
+
+      pub fn edge_length(&self, py: Python<'_>) -> PyResult<f64> {
+        Ok(lock_optimizer(py, &self.inner)?.edge_length())
+      }
+
+      #[pyo3(signature = (callback, weight=1.))]
+      pub fn add_objective(&mut self, py: Python<'_>, callback: PyObject, weight: f64) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?
+          .add_objective(Box::new(PyObjective { callback }), weight);
+        Ok(())
+      }
+
+      #[pyo3(signature = (labels, weight=1.))]
+      pub fn add_group_cohesion(&mut self, py: Python<'_>, labels: &Bound<'_, PyAny>, weight: f64) -> PyResult<()> {
+        let labels = self.nodes.node_maps(labels)?;
+        lock_optimizer(py, &self.inner)?
+          .add_objective(Box::new(GroupCohesion::new(labels)), weight);
+        Ok(())
+      }
+
+      pub fn clear_objectives(&mut self, py: Python<'_>) -> PyResult<()> {
+        lock_optimizer(py, &self.inner)?.clear_objectives();
+        Ok(())
+      }
+
+      pub fn cost(&self, py: Python<'_>) -> PyResult<f64> {
+        lock_optimizer(py, &self.inner)?.cost().map_err(to_pyerr)
+      }
+
+      $($($extra)*)?
+    }
+  };
+}
+
+layout_optimizer!(LayoutOptimizerString, "LayoutOptimizerString", String, Extract<String>);
+hierarchy_optimizer!(
+  HierarchyOptimizerString,
+  "HierarchyOptimizerString",
+  String,
+  Extract<String>
+);
+genetic_optimizer!(
+  GeneticOptimizerString,
+  "GeneticOptimizerString",
+  String,
+  Extract<String>
+);
+layout_optimizer!(LayoutOptimizerInt, "LayoutOptimizerInt", i32, Extract<i32>);
+hierarchy_optimizer!(HierarchyOptimizerInt, "HierarchyOptimizerInt", i32, Extract<i32>);
+genetic_optimizer!(GeneticOptimizerInt, "GeneticOptimizerInt", i32, Extract<i32>);
+
+// Like the classes above, for nodes that are any hashable Python objects
+layout_optimizer!(GenericLayoutOptimizer, "LayoutOptimizer", InternedNode, Interner, {
+  /// Builds the optimizer from a networkx graph, see `generic::networkx_graph` for how it is split into layers.
+  #[staticmethod]
+  #[pyo3(signature = (graph, layer_attr="layer", weight_attr="weight"))]
+  pub fn from_networkx(graph: &Bound<'_, PyAny>, layer_attr: &str, weight_attr: &str) -> PyResult<Self> {
+    let (inner, nodes) = generic::layout_from_networkx(graph, layer_attr, weight_attr)?;
+    Ok(Self {
+      inner: Arc::new(Mutex::new(inner)),
+      nodes,
+    })
+  }
+});
+hierarchy_optimizer!(
+  GenericHierarchyOptimizer,
+  "HierarchyOptimizer",
+  InternedNode,
+  Interner,
+  {
+    /// Builds the optimizer from a networkx graph, see `generic::hierarchy_from_networkx`.
+    #[staticmethod]
+    #[pyo3(signature = (graph, layer_attr="layer", weight_attr="weight", group_attr="group"))]
+    pub fn from_networkx(
+      graph: &Bound<'_, PyAny>,
+      layer_attr: &str,
+      weight_attr: &str,
+      group_attr: &str,
+    ) -> PyResult<Self> {
+      let (inner, nodes) = generic::hierarchy_from_networkx(graph, layer_attr, weight_attr, group_attr)?;
+      Ok(Self {
+        inner: Arc::new(Mutex::new(inner)),
+        nodes,
+      })
+    }
+  }
+);
 
 #[pyfunction]
 fn generate_multipartite_graph(py: Python<'_>, n_nodes: Vec<usize>) -> PyResult<utils::GraphType> {
-  run_in_thread(py, move || utils::generate_multipartite_graph(n_nodes))
+  py.allow_threads(|| utils::generate_multipartite_graph(n_nodes))
+    .map_err(|e| PyRuntimeError::new_err(e.to_string()))
 }
 
 #[pymodule]
@@ -188,6 +1100,21 @@ mod untanglers {
   #[pymodule_export]
   use crate::HierarchyOptimizerInt;
 
+  #[pymodule_export]
+  use crate::GeneticOptimizerString;
+
+  #[pymodule_export]
+  use crate::GeneticOptimizerInt;
+
+  #[pymodule_export]
+  use crate::config::PyOptimizeConfig;
+
+  #[pymodule_export]
+  use crate::GenericLayoutOptimizer;
+
+  #[pymodule_export]
+  use crate::GenericHierarchyOptimizer;
+
   #[pymodule_export]
   use crate::generate_multipartite_graph;
 }
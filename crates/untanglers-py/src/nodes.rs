@@ -0,0 +1,83 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use crate::arrays::{ArrayNode, EdgeLayers, NodeLayers};
+
+/**
+ * Converts nodes given from Python to the node type of an optimizer class. The typed classes extract their nodes
+ * directly, the generic classes intern any hashable object, see `generic::Interner`. Every class holds a converter,
+ * so the optimizer macros in lib.rs generate the methods once for all node types.
+ */
+pub trait PyNodes<T>: Sized
+where
+  T: Eq + Hash,
+{
+  fn new(py: Python<'_>) -> Self;
+
+  /// A copy that converts the nodes seen so far the same way, for `__copy__`.
+  fn clone_ref(&self, py: Python<'_>) -> PyResult<Self>;
+
+  /// Converts a node that may be new to the optimizer.
+  fn node(&mut self, node: &Bound<'_, PyAny>) -> PyResult<T>;
+
+  /// Converts a node that is only looked up, unlike `node` this never remembers it.
+  fn lookup(&self, node: &Bound<'_, PyAny>) -> PyResult<T>;
+
+  /// The nodes of every layer, see `arrays::NodeLayers`.
+  fn node_layers(&mut self, node_layers: &Bound<'_, PyAny>) -> PyResult<Vec<Vec<T>>>;
+
+  /// The edges between every pair of neighbouring layers, or within every layer, see `arrays::EdgeLayers`.
+  fn edges(&mut self, edges: &Bound<'_, PyAny>) -> PyResult<Vec<Vec<(T, T, usize)>>>;
+
+  /// A `{node: value}` dict for every layer, e.g. group labels or group paths.
+  fn node_maps<V>(&mut self, maps: &Bound<'_, PyAny>) -> PyResult<Vec<HashMap<T, V>>>
+  where
+    V: for<'py> FromPyObject<'py>,
+  {
+    maps
+      .iter()?
+      .map(|map| {
+        map?
+          .downcast::<PyDict>()?
+          .iter()
+          .map(|(node, value)| Ok((self.node(&node)?, value.extract()?)))
+          .collect()
+      })
+      .collect()
+  }
+}
+
+/// Nodes that are extracted as `T`, e.g. strings or integers.
+pub struct Extract<T>(PhantomData<T>);
+
+impl<T> PyNodes<T> for Extract<T>
+where
+  T: Eq + Hash + ArrayNode + for<'py> FromPyObject<'py>,
+{
+  fn new(_: Python<'_>) -> Self {
+    Self(PhantomData)
+  }
+
+  fn clone_ref(&self, _: Python<'_>) -> PyResult<Self> {
+    Ok(Self(PhantomData))
+  }
+
+  fn node(&mut self, node: &Bound<'_, PyAny>) -> PyResult<T> {
+    node.extract()
+  }
+
+  fn lookup(&self, node: &Bound<'_, PyAny>) -> PyResult<T> {
+    node.extract()
+  }
+
+  fn node_layers(&mut self, node_layers: &Bound<'_, PyAny>) -> PyResult<Vec<Vec<T>>> {
+    Ok(node_layers.extract::<NodeLayers<T>>()?.0)
+  }
+
+  fn edges(&mut self, edges: &Bound<'_, PyAny>) -> PyResult<Vec<Vec<(T, T, usize)>>> {
+    Ok(edges.extract::<EdgeLayers<T>>()?.0)
+  }
+}
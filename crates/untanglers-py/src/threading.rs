@@ -1,6 +1,82 @@
-use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::{PyKeyboardInterrupt, PyRuntimeError};
 use pyo3::prelude::*;
+use signal_hook::consts::SIGINT;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError, TryLockError};
 use std::time::Duration;
+use untanglers_core::error::OptimizerError;
+use untanglers_core::optimizer_ops::OptimizerOps;
+
+/**
+ * Locks the optimizer for a call from Python, waiting with the GIL released while an optimization holds the lock.
+ * Optimizations run with the GIL released and may need it again, e.g. for objectives implemented in Python, so waiting
+ * while holding the GIL could deadlock. The guard can't leave `allow_threads`, so this only waits there until the lock
+ * is free and takes it once the GIL is held again.
+ */
+pub fn lock_optimizer<'a, O: Send>(py: Python<'_>, optimizer: &'a Mutex<O>) -> PyResult<MutexGuard<'a, O>> {
+  loop {
+    match optimizer.try_lock() {
+      Ok(guard) => return Ok(guard),
+      Err(TryLockError::Poisoned(err)) => return Err(poisoned(err)),
+      Err(TryLockError::WouldBlock) => py.allow_threads(|| drop(optimizer.lock())),
+    }
+  }
+}
+
+/// Locks the optimizer without waiting, failing with a `RuntimeError` while an optimization holds the lock.
+pub fn try_lock_optimizer<O>(optimizer: &Mutex<O>) -> PyResult<MutexGuard<'_, O>> {
+  optimizer.try_lock().map_err(|err| match err {
+    TryLockError::WouldBlock => PyRuntimeError::new_err("optimization in progress"),
+    TryLockError::Poisoned(err) => poisoned(err),
+  })
+}
+
+fn poisoned<T>(_: PoisonError<T>) -> PyErr {
+  PyRuntimeError::new_err("A previous optimization panicked")
+}
+
+/**
+ * The interrupt flag shared by all optimizations running with `run_interruptible`, set by a SIGINT handler that is
+ * registered once. The flag is cleared when an optimization starts while no other one is running.
+ */
+struct SigintFlag {
+  interrupt: Arc<AtomicBool>,
+  running: AtomicUsize,
+}
+
+/// Marks an optimization as running until it is dropped
+struct Running<'a>(&'a SigintFlag);
+
+impl SigintFlag {
+  /// `None` if the handler can't be registered
+  fn get() -> Option<&'static SigintFlag> {
+    static FLAG: OnceLock<Option<SigintFlag>> = OnceLock::new();
+    FLAG
+      .get_or_init(|| {
+        let interrupt = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(SIGINT, Arc::clone(&interrupt)).ok()?;
+        Some(SigintFlag {
+          interrupt,
+          running: AtomicUsize::new(0),
+        })
+      })
+      .as_ref()
+  }
+
+  fn start(&self) -> Running<'_> {
+    if self.running.fetch_add(1, Ordering::SeqCst) == 0 {
+      self.interrupt.store(false, Ordering::SeqCst);
+    }
+    Running(self)
+  }
+}
+
+impl Drop for Running<'_> {
+  fn drop(&mut self) {
+    self.0.running.fetch_sub(1, Ordering::SeqCst);
+  }
+}
 
 /// Run a long-running Rust function in a background thread,
 /// while keeping Python responsive to signals (Ctrl+C, etc.).
@@ -29,3 +105,45 @@ where
     .map_err(|_| PyRuntimeError::new_err("Worker thread panicked"))?
     .map_err(|e| PyRuntimeError::new_err(e.to_string()))
 }
+
+/**
+ * Runs `f` on the optimizer on the calling thread with the GIL released. A SIGINT handler sets the interrupt flag of
+ * the running optimizations, so Ctrl+C stops them after the current layer and raises `KeyboardInterrupt`. The handler
+ * runs before Python's own, which is still called. If the handler can't be registered this falls back to
+ * `run_in_thread`. Waits for the lock while another optimization is running.
+ */
+pub fn run_interruptible<O, N, F, T>(py: Python<'_>, optimizer: &Arc<Mutex<O>>, f: F) -> PyResult<T>
+where
+  O: OptimizerOps<N> + Send + 'static,
+  N: Eq + Hash + Clone,
+  F: Send + 'static + FnOnce(&mut O) -> Result<T, OptimizerError>,
+  T: Send + 'static,
+{
+  let Some(sigint) = SigintFlag::get() else {
+    let optimizer = Arc::clone(optimizer);
+    return run_in_thread(py, move || {
+      let mut guard = optimizer
+        .lock()
+        .map_err(|_| "A previous optimization panicked".to_string())?;
+      f(&mut guard).map_err(|e| e.to_string())
+    });
+  };
+
+  let result = py.allow_threads(|| {
+    let mut guard = optimizer.lock().map_err(poisoned)?;
+    let _running = sigint.start();
+    guard.set_interrupt(Some(Arc::clone(&sigint.interrupt)));
+    let result = f(&mut guard);
+    guard.set_interrupt(None);
+    Ok::<_, PyErr>(result)
+  });
+
+  match result? {
+    Err(OptimizerError::Interrupted) => {
+      // Raises the KeyboardInterrupt of Python's handler, which only runs on the main thread
+      py.check_signals()?;
+      Err(PyKeyboardInterrupt::new_err("Optimization was interrupted"))
+    }
+    result => result.map_err(|e| PyRuntimeError::new_err(e.to_string())),
+  }
+}
@@ -0,0 +1,316 @@
+//! WebAssembly bindings for laying out graphs in the browser, built with `wasm-pack build crates/untanglers-wasm`.
+//!
+//! Nodes are integer ids. Graphs come in as typed arrays: the node ids of all layers one after another along with the
+//! size of each layer, and the edges as `node_a, node_b, weight` triples along with the number of edges between each
+//! pair of neighbouring layers. Everything runs on the calling thread, so long optimizations are best run in a worker.
+
+use js_sys::{Array, Function, Uint32Array};
+use std::fmt::Display;
+use untanglers_core::config::OptimizeConfig;
+use untanglers_core::error::OptimizerError;
+use untanglers_core::hierarchy_optimizer::{self, Hierarchy};
+use untanglers_core::layout_optimizer;
+use untanglers_core::optimizer_ops::OptimizerOps;
+use wasm_bindgen::prelude::*;
+
+type Edges = Vec<Vec<(u32, u32, usize)>>;
+
+fn js_error(err: impl Display) -> JsValue {
+  JsError::new(&err.to_string()).into()
+}
+
+/// Splits the concatenated node ids into layers.
+fn split_layers(layer_sizes: &[u32], nodes: &[u32]) -> Result<Vec<Vec<u32>>, String> {
+  let total = layer_sizes.iter().map(|size| *size as usize).sum::<usize>();
+  if total != nodes.len() {
+    return Err(format!("The layer sizes add up to {total} nodes, got {}", nodes.len()));
+  }
+
+  let mut rest = nodes;
+  Ok(
+    layer_sizes
+      .iter()
+      .map(|size| {
+        let (layer, next) = rest.split_at(*size as usize);
+        rest = next;
+        layer.to_vec()
+      })
+      .collect(),
+  )
+}
+
+/// Splits the concatenated `node_a, node_b, weight` triples into the edges between each pair of layers.
+fn split_edges(edge_counts: &[u32], edges: &[u32]) -> Result<Edges, String> {
+  let total = edge_counts.iter().map(|count| *count as usize).sum::<usize>();
+  if edges.len() != 3 * total {
+    return Err(format!(
+      "The edge counts add up to {total} edges, expected {} values but got {}",
+      3 * total,
+      edges.len()
+    ));
+  }
+
+  let mut rest = edges;
+  Ok(
+    edge_counts
+      .iter()
+      .map(|count| {
+        let (layer_edges, next) = rest.split_at(3 * *count as usize);
+        rest = next;
+        layer_edges
+          .chunks_exact(3)
+          .map(|edge| (edge[0], edge[1], edge[2] as usize))
+          .collect()
+      })
+      .collect(),
+  )
+}
+
+/// An array of arrays of group sizes per layer, from fine to coarse. Plain arrays and typed arrays both work.
+fn hierarchy_from_js(hierarchy: &Array) -> Hierarchy {
+  hierarchy
+    .iter()
+    .map(|levels| {
+      Array::from(&levels)
+        .iter()
+        .map(|sizes| {
+          Uint32Array::new(&sizes)
+            .to_vec()
+            .into_iter()
+            .map(|size| size as usize)
+            .collect()
+        })
+        .collect()
+    })
+    .collect()
+}
+
+fn nodes_to_js(node_layers: Vec<Vec<u32>>) -> Array {
+  node_layers
+    .into_iter()
+    .map(|layer| JsValue::from(Uint32Array::from(layer.as_slice())))
+    .collect()
+}
+
+fn layer(node_layers: Vec<Vec<u32>>, layer_index: usize) -> Result<Vec<u32>, JsValue> {
+  let layer_count = node_layers.len();
+  node_layers.into_iter().nth(layer_index).ok_or_else(|| {
+    js_error(OptimizerError::InvalidLayer {
+      layer_index,
+      layer_count,
+    })
+  })
+}
+
+/// Validates the settings, the config runs a single pass since `run_passes` reports the progress after every pass.
+fn single_pass_config(
+  start_temp: f64,
+  end_temp: f64,
+  steps: usize,
+  max_iterations: usize,
+) -> Result<OptimizeConfig, JsValue> {
+  OptimizeConfig::builder()
+    .start_temp(start_temp)
+    .end_temp(end_temp)
+    .steps(steps)
+    .max_iterations(max_iterations)
+    .passes(1)
+    .build()
+    .map_err(js_error)
+}
+
+/**
+ * Runs `pass` once per pass and reports the progress as `on_progress(pass, passes, crossings)` after each. The
+ * callback can return `false` to stop early. Returns the crossings after the last pass that ran.
+ */
+fn run_passes(
+  passes: usize,
+  crossings: usize,
+  on_progress: Option<Function>,
+  mut pass: impl FnMut() -> Result<usize, OptimizerError>,
+) -> Result<usize, JsValue> {
+  let mut crossings = crossings;
+  for index in 0..passes {
+    crossings = pass().map_err(js_error)?;
+    if let Some(on_progress) = &on_progress {
+      let proceed = on_progress.call3(
+        &JsValue::NULL,
+        &JsValue::from(index + 1),
+        &JsValue::from(passes),
+        &JsValue::from(crossings),
+      )?;
+      if proceed.as_bool() == Some(false) {
+        break;
+      }
+    }
+  }
+  Ok(crossings)
+}
+
+#[wasm_bindgen]
+pub struct LayoutOptimizer {
+  inner: layout_optimizer::LayoutOptimizer<u32>,
+}
+
+#[wasm_bindgen]
+impl LayoutOptimizer {
+  #[wasm_bindgen(constructor)]
+  pub fn new(layer_sizes: &[u32], nodes: &[u32], edge_counts: &[u32], edges: &[u32]) -> Result<Self, JsValue> {
+    let node_layers = split_layers(layer_sizes, nodes).map_err(js_error)?;
+    let edges = split_edges(edge_counts, edges).map_err(js_error)?;
+    let inner = layout_optimizer::LayoutOptimizer::new(node_layers.clone(), edges.clone())
+      .map_err(|err| js_error(err.with_graph(&node_layers, &edges)))?;
+    Ok(Self { inner })
+  }
+
+  /// Like `LayoutOptimizer.optimize` in Python, calling `on_progress(pass, passes, crossings)` after every pass.
+  pub fn optimize(
+    &mut self,
+    start_temp: f64,
+    end_temp: f64,
+    steps: usize,
+    max_iterations: usize,
+    passes: usize,
+    on_progress: Option<Function>,
+  ) -> Result<usize, JsValue> {
+    let config = single_pass_config(start_temp, end_temp, steps, max_iterations)?;
+    let crossings = self.inner.count_crossings();
+    run_passes(passes, crossings, on_progress, || self.inner.optimize(&config))
+  }
+
+  #[wasm_bindgen(js_name = countCrossings)]
+  pub fn count_crossings(&self) -> usize {
+    self.inner.count_crossings()
+  }
+
+  /// The node ids of every layer in their current order, as an array of `Uint32Array`.
+  #[wasm_bindgen(js_name = getNodes)]
+  pub fn get_nodes(&self) -> Array {
+    nodes_to_js(self.inner.get_nodes())
+  }
+
+  #[wasm_bindgen(js_name = getLayer)]
+  pub fn get_layer(&self, layer_index: usize) -> Result<Vec<u32>, JsValue> {
+    layer(self.inner.get_nodes(), layer_index)
+  }
+}
+
+#[wasm_bindgen]
+pub struct HierarchyOptimizer {
+  inner: hierarchy_optimizer::HierarchyOptimizer<u32>,
+}
+
+#[wasm_bindgen]
+impl HierarchyOptimizer {
+  /// Like `LayoutOptimizer`, with the group sizes of every layer from fine to coarse, e.g. `[[[1, 1, 1], [2, 1]], []]`
+  /// for three nodes grouped as (1, 2), (3) in the first layer and no groups in the second.
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    layer_sizes: &[u32],
+    nodes: &[u32],
+    edge_counts: &[u32],
+    edges: &[u32],
+    hierarchy: &Array,
+  ) -> Result<Self, JsValue> {
+    Self::from_parts(layer_sizes, nodes, edge_counts, edges, hierarchy_from_js(hierarchy)).map_err(js_error)
+  }
+
+  fn from_parts(
+    layer_sizes: &[u32],
+    nodes: &[u32],
+    edge_counts: &[u32],
+    edges: &[u32],
+    hierarchy: Hierarchy,
+  ) -> Result<Self, String> {
+    let node_layers = split_layers(layer_sizes, nodes)?;
+    let edges = split_edges(edge_counts, edges)?;
+    let inner = hierarchy_optimizer::HierarchyOptimizer::new(node_layers.clone(), edges.clone(), hierarchy)
+      .map_err(|err| err.with_graph(&node_layers, &edges).to_string())?;
+    Ok(Self { inner })
+  }
+
+  /// Like `HierarchyOptimizer.optimize` in Python, calling `on_progress(pass, passes, crossings)` after every pass.
+  pub fn optimize(
+    &mut self,
+    start_temp: f64,
+    end_temp: f64,
+    steps: usize,
+    max_iterations: usize,
+    passes: usize,
+    on_progress: Option<Function>,
+  ) -> Result<usize, JsValue> {
+    let config = single_pass_config(start_temp, end_temp, steps, max_iterations)?;
+    let crossings = self.inner.count_crossings();
+    run_passes(passes, crossings, on_progress, || self.inner.optimize(&config))
+  }
+
+  #[wasm_bindgen(js_name = countCrossings)]
+  pub fn count_crossings(&self) -> usize {
+    self.inner.count_crossings()
+  }
+
+  /// The node ids of every layer in their current order, as an array of `Uint32Array`.
+  #[wasm_bindgen(js_name = getNodes)]
+  pub fn get_nodes(&self) -> Array {
+    nodes_to_js(self.inner.get_nodes())
+  }
+
+  #[wasm_bindgen(js_name = getLayer)]
+  pub fn get_layer(&self, layer_index: usize) -> Result<Vec<u32>, JsValue> {
+    layer(self.inner.get_nodes(), layer_index)
+  }
+
+  /// The group sizes in their current order, in the same shape as the constructor argument.
+  #[wasm_bindgen(js_name = getHierarchy)]
+  pub fn get_hierarchy(&self) -> Array {
+    self
+      .inner
+      .get_hierarchy()
+      .into_iter()
+      .map(|levels| {
+        levels
+          .into_iter()
+          .map(|sizes| {
+            let sizes = sizes.into_iter().map(|size| size as u32).collect::<Vec<_>>();
+            JsValue::from(Uint32Array::from(sizes.as_slice()))
+          })
+          .collect::<Array>()
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_split_layers() {
+    assert_eq!(
+      split_layers(&[2, 0, 1], &[1, 2, 3]).unwrap(),
+      vec![vec![1, 2], vec![], vec![3]]
+    );
+    assert!(split_layers(&[2, 2], &[1, 2, 3]).is_err());
+  }
+
+  #[test]
+  fn test_split_edges() {
+    assert_eq!(
+      split_edges(&[1, 2], &[1, 3, 1, 3, 4, 2, 3, 5, 1]).unwrap(),
+      vec![vec![(1, 3, 1)], vec![(3, 4, 2), (3, 5, 1)]]
+    );
+    assert!(split_edges(&[1], &[1, 3]).is_err());
+  }
+
+  #[test]
+  fn test_hierarchy_example() {
+    // The example from the `HierarchyOptimizer` constructor
+    let hierarchy = vec![vec![vec![1, 1, 1], vec![2, 1]], vec![]];
+    let optimizer =
+      HierarchyOptimizer::from_parts(&[3, 1], &[1, 2, 3, 4], &[2], &[1, 4, 1, 3, 4, 1], hierarchy.clone());
+    assert_eq!(optimizer.unwrap().inner.get_hierarchy(), hierarchy);
+
+    let reversed = vec![vec![vec![2, 1], vec![1, 1, 1]], vec![]];
+    assert!(HierarchyOptimizer::from_parts(&[3, 1], &[1, 2, 3, 4], &[2], &[1, 4, 1, 3, 4, 1], reversed).is_err());
+  }
+}
@@ -1,3 +1,5 @@
+import pytest
+
 import untanglers
 
 
@@ -16,6 +18,348 @@ class TestUntangleRs:
     rs_crossings.swap_nodes(0, 10, 0)
     assert rs_crossings.count_crossings() == 0
 
+  def test_to_svg(self):
+    nodes, edges = untanglers.generate_multipartite_graph([3, 4])
+    hierarchy = [[[2, 1]], [[4]]]
+
+    optimizer = untanglers.HierarchyOptimizerInt(nodes, edges, hierarchy)
+    svg = optimizer.to_svg(highlight_crossings=True)
+    assert svg.startswith("<svg")
+    assert svg.count("<circle") == 7
+    assert svg.count("<line") == len(edges[0])
+
+  def test_objectives(self):
+    nodes, edges = untanglers.generate_multipartite_graph([5, 5])
+    optimizer = untanglers.LayoutOptimizerInt(nodes, edges)
+    optimizer.add_group_cohesion([{n: n % 2 for n in layer} for layer in nodes], weight=2.0)
+
+    layers = []
+
+    def objective(layer_index, layer_nodes):
+      layers.append(layer_index)
+      return [0.0] * len(layer_nodes) ** 2, 0.0
+
+    optimizer.add_objective(objective)
+    optimizer.optimize(1.0, 0.1, 3, 10, 1)
+    assert layers == [0, 1]
+    assert optimizer.cost() >= optimizer.count_crossings()
+
+  def test_optimize_with(self):
+    nodes, edges = untanglers.generate_multipartite_graph([20, 20, 20])
+    optimizer = untanglers.LayoutOptimizerInt(nodes, edges)
+    start_crossings = optimizer.count_crossings()
+
+    strategies = ["barycenter", ("annealing", {"start_temp": 1.0, "steps": 3})]
+    assert optimizer.optimize_with(strategies, 2) < start_crossings
+
+    crossings = optimizer.tabu_search(7, 100, 1)
+    assert crossings == optimizer.count_crossings()
+
+    crossings = optimizer.optimize_with([("tabu", {"tenure": 5}), ("greedy", {"two_opt": 1})], 1)
+    assert optimizer.polish(two_opt=True, block_reversal=True) <= crossings
+
+    with pytest.raises(ValueError):
+      optimizer.optimize_with([("annealing", {"temperature": 1.0})], 1)
+
+  def test_genetic(self):
+    nodes, edges = untanglers.generate_multipartite_graph([15, 15, 15])
+    optimizer = untanglers.GeneticOptimizerInt(nodes, edges, population_size=6)
+    start_crossings = optimizer.count_crossings()
+    assert optimizer.optimize(3) < start_crossings
+
+    hierarchy = [[[5, 10]], [[7, 8]], [[3, 12]]]
+    optimizer = untanglers.GeneticOptimizerInt(nodes, edges, hierarchy, population_size=6, local_search=False)
+    start_cost = optimizer.cost()
+    optimizer.optimize(3)
+    assert optimizer.cost() <= start_cost
+    assert sorted(optimizer.get_hierarchy()[0][0]) == [5, 10]
+
+  def test_solve_exact(self):
+    nodes, edges = untanglers.generate_multipartite_graph([5, 5, 5])
+    annealed = untanglers.LayoutOptimizerInt(nodes, edges)
+    annealed_crossings = annealed.optimize(1.0, 0.1, 5, 100, 3)
+
+    optimizer = untanglers.LayoutOptimizerInt(nodes, edges)
+    crossings, optimal = optimizer.solve_exact(time_limit=10.0)
+    assert optimal
+    assert crossings == optimizer.count_crossings()
+    assert crossings <= annealed_crossings
+
+    with pytest.raises(RuntimeError):
+      optimizer.solve_exact(max_nodes=10)
+
+  def test_group_tree(self):
+    trees = [
+      {"db1": {"a": [0, 1, 2], "b": [3, 4]}, "db2": {"c": [5, 6, 7]}},
+      {"x": [8, 9, 10], "y": [11, 12]},
+    ]
+    edges = [[(0, 12, 1), (3, 8, 1), (5, 9, 1), (7, 11, 1)]]
+    optimizer = untanglers.HierarchyOptimizerInt.from_group_tree(trees, edges)
+    assert optimizer.get_group_tree(0) == trees[0]
+    assert optimizer.get_group_names()[1] == [["x", "y"]]
+
+    optimizer.optimize(1.0, 0.1, 5, 20, 3)
+    tree = optimizer.get_group_tree(0)
+    assert sorted(tree) == ["db1", "db2"]
+    assert sorted(tree["db1"]["b"]) == [3, 4]
+    assert optimizer.get_group_order(0, 1) == list(tree)
+    assert optimizer.get_hierarchy()[0][1] == [sum(len(nodes) for nodes in tree[name].values()) for name in tree]
+
+    paths = [{0: ["db1"], 1: ["db2"], 2: ["db1"]}, {3: ["db3"]}]
+    optimizer = untanglers.HierarchyOptimizerInt.from_group_paths([[0, 1, 2], [3]], [[]], paths)
+    assert optimizer.get_nodes()[0] == [0, 2, 1]
+    assert optimizer.get_group_tree(0) == {"db1": [0, 2], "db2": [1]}
+
+    with pytest.raises(ValueError):
+      untanglers.HierarchyOptimizerInt.from_group_paths([[0, 1]], [], [{0: ["db1"]}])
+    with pytest.raises(ValueError):
+      optimizer.get_group_order(1, 1)
+
+  def test_compound_groups(self):
+    nodes, edges = untanglers.generate_multipartite_graph([10, 10, 10])
+    trees = [
+      {"p1": nodes[0][:5], "x": nodes[0][5:]},
+      {"p2": nodes[1][:5], "p1": nodes[1][5:]},
+      {"p1": nodes[2][:5], "p2": nodes[2][5:]},
+    ]
+    optimizer = untanglers.HierarchyOptimizerInt.from_group_tree(trees, edges)
+    with pytest.raises(ValueError):
+      optimizer.set_compound_groups([("p1", 0, 2), ("p2", 1, 2)])
+
+    optimizer.set_compound_groups([("p1", 0, 2), ("p2", 1, 1)])
+    optimizer.optimize(1.0, 0.1, 5, 20, 3)
+    order = optimizer.get_compound_order()
+    assert sorted(order) == ["p1", "p2"]
+    assert optimizer.get_group_order(1, 0) == order
+
+  def test_group_crossings(self):
+    trees = [{"a": [0, 1], "b": [2]}, {"c": [3], "d": [4, 5]}, [6, 7]]
+    edges = [[(0, 4, 1), (1, 5, 2), (2, 3, 1)], [(3, 7, 1), (4, 6, 1), (5, 6, 1)]]
+    optimizer = untanglers.HierarchyOptimizerInt.from_group_tree(trees, edges)
+    assert optimizer.count_crossings(granularity=0) == 5
+    assert optimizer.count_crossings() >= optimizer.count_crossings(granularity=0)
+    assert optimizer.group_crossings(0) == [[("a", 3), ("b", 3)], [("c", 5), ("d", 5)], [("6", 2), ("7", 2)]]
+    with pytest.raises(ValueError, match="Granularity out of range at layer 0"):
+      optimizer.count_crossings(granularity=1)
+
+  def test_generic_nodes(self):
+    nodes = [[("db", "a"), ("db", "b"), None], [1.5, frozenset({1})]]
+    edges = [[(("db", "a"), frozenset({1}), 1), (("db", "b"), 1.5, 1), (None, 1.5, 2)]]
+    optimizer = untanglers.LayoutOptimizer(nodes, edges)
+    assert optimizer.optimize(1.0, 0.1, 5, 20, 2) == 0
+    assert {node for layer in optimizer.get_nodes() for node in layer} == {node for layer in nodes for node in layer}
+    assert "frozenset" in optimizer.to_svg()
+
+    with pytest.raises(ValueError, match=r"Edge 0 \(\('db', 'c'\), 1.5\) of layer 0"):
+      untanglers.LayoutOptimizer(nodes, [[(("db", "c"), 1.5, 1)]])
+
+    with pytest.raises(ValueError, match=r"Node \('db', 'z'\) not found in layer 0"):
+      optimizer.remove_node(0, ("db", "z"))
+    assert optimizer.remove_edge(0, None, 1.5) == 2
+    optimizer.add_node(0, ("db", "z"))
+    assert optimizer.tabu_search(2, 10, 0) == optimizer.count_crossings()
+    assert optimizer.cost() == optimizer.count_crossings()
+
+    optimizer = untanglers.HierarchyOptimizer(nodes, edges, [[[2, 1]], []])
+    optimizer.optimize(1.0, 0.1, 5, 20, 2)
+    assert sorted(optimizer.get_hierarchy()[0][0]) == [1, 2]
+
+    trees = [{"x": [("db", "a")], "y": [("db", "b"), None]}, [1.5, frozenset({1})]]
+    optimizer = untanglers.HierarchyOptimizer.from_group_tree(trees, edges)
+    assert optimizer.get_group_tree(0) == trees[0]
+
+  def test_error_names(self):
+    optimizer = untanglers.HierarchyOptimizerString([["a", "b"], ["c"]], [[("a", "c", 1)]], [[[2]], []])
+    with pytest.raises(ValueError, match='Node "x" not found in layer 0'):
+      optimizer.remove_node(0, "x")
+    with pytest.raises(ValueError, match=r'Edge 1 \("b", "x"\) of layer 0 connects a node that is missing'):
+      optimizer.add_edge(0, "b", "x", 1)
+    with pytest.raises(ValueError, match='Node "a" is already part of layer 0, at position 0'):
+      optimizer.add_node(0, "a")
+    with pytest.raises(ValueError, match=r'Node 1 "b" of layer 0 has no group path'):
+      untanglers.HierarchyOptimizerString.from_group_paths([["a", "b"], ["c"]], [[]], [{"a": ["x"]}, {"c": []}])
+
+  def test_numpy_arrays(self):
+    np = pytest.importorskip("numpy")
+    nodes = [np.arange(4), np.arange(4, 8)]
+    edges = [np.array([[0, 5, 1], [1, 4, 1], [2, 7, 2], [3, 6, 1]])]
+    optimizer = untanglers.LayoutOptimizerInt(nodes, edges)
+    assert optimizer.optimize(1.0, 0.1, 5, 20, 2) == 0
+    assert sorted(optimizer.get_nodes()[1]) == [4, 5, 6, 7]
+
+    generic = untanglers.LayoutOptimizer(nodes, edges)
+    assert generic.count_crossings() == untanglers.LayoutOptimizerInt(nodes, edges).count_crossings()
+
+    with pytest.raises(ValueError):
+      untanglers.LayoutOptimizerInt(nodes, [np.array([0, 5, 1])])
+    with pytest.raises(TypeError):
+      untanglers.LayoutOptimizerString(nodes, edges)
+
+  def test_networkx(self):
+    nx = pytest.importorskip("networkx")
+    graph = nx.DiGraph()
+    for node, layer, group in [("a", 0, "x"), ("b", 0, "y"), ("c", 0, "x"), ("d", 1, None), ("e", 1, None)]:
+      graph.add_node(node, layer=layer, group=group)
+    graph.add_edges_from([("a", "e", {"weight": 2}), ("b", "d"), ("e", "c"), ("a", "c")])
+
+    optimizer = untanglers.LayoutOptimizer.from_networkx(graph)
+    assert [sorted(layer) for layer in optimizer.get_nodes()] == [["a", "b", "c"], ["d", "e"]]
+    assert optimizer.optimize(1.0, 0.1, 5, 20, 2) == 0
+    positions = optimizer.to_networkx_positions()
+    assert set(positions) == set(graph.nodes)
+    assert {x for x, _ in positions.values()} == {0, 1}
+
+    optimizer = untanglers.HierarchyOptimizer.from_networkx(graph)
+    assert optimizer.get_group_names() == [[["x", "y"]], []]
+    assert optimizer.get_nodes()[0] == ["a", "c", "b"]
+    optimizer.optimize(1.0, 0.1, 5, 20, 2)
+
+    graph.edges["a", "e"]["weight"] = 2.0
+    assert untanglers.LayoutOptimizer.from_networkx(graph).optimize(1.0, 0.1, 5, 20, 2) == 0
+    graph.edges["a", "e"]["weight"] = 1.5
+    with pytest.raises(ValueError, match='Edge from a to e has "weight" 1.5, expected a whole number'):
+      untanglers.LayoutOptimizer.from_networkx(graph)
+
+    graph.add_node("f", layer=2)
+    graph.add_edge("a", "f")
+    with pytest.raises(ValueError):
+      untanglers.LayoutOptimizer.from_networkx(graph)
+
+    assert untanglers.LayoutOptimizerInt([[0, 1], [2]], [[(0, 2, 1)]]).to_networkx_positions() == {
+      0: (0, 0.5),
+      1: (0, -0.5),
+      2: (1, 0),
+    }
+
+  def test_pickle_and_copy(self):
+    import copy
+    import pickle
+
+    edges = [[("a", "e", 1), ("b", "d", 2), ("c", "d", 1)]]
+    optimizers = [
+      untanglers.LayoutOptimizerString([["a", "b", "c"], ["d", "e"]], edges),
+      untanglers.HierarchyOptimizerString([["a", "b", "c"], ["d", "e"]], edges, [[[2, 1]], []]),
+      untanglers.GeneticOptimizerString([["a", "b", "c"], ["d", "e"]], edges, population_size=4),
+      untanglers.LayoutOptimizer([[1, (2, 3), "x"], [None, 4.5]], [[(1, None, 1), ("x", 4.5, 1), ((2, 3), None, 1)]]),
+    ]
+    for optimizer in optimizers[:3]:
+      optimizer.set_edge_length_weight(0.5)
+      optimizer.set_reference([["c", "b", "a"], ["e", "d"]])
+    for optimizer in optimizers:
+      restored = pickle.loads(pickle.dumps(optimizer))
+      assert type(restored) is type(optimizer)
+      assert restored.get_nodes() == optimizer.get_nodes()
+      assert restored.count_crossings() == optimizer.count_crossings()
+      if optimizer in optimizers[:3]:
+        assert restored.cost() == optimizer.cost()
+
+      nodes = optimizer.get_nodes()
+      copied = copy.copy(optimizer)
+      if isinstance(copied, untanglers.GeneticOptimizerString):
+        copied.optimize(2)
+      else:
+        copied.optimize(1.0, 0.1, 5, 20, 2)
+      assert optimizer.get_nodes() == nodes
+      assert copy.deepcopy(optimizer).get_nodes() == nodes
+
+    optimizer = optimizers[0]
+    optimizer.add_objective(lambda layer, nodes: ([0.0] * len(nodes) ** 2, 1.0))
+    with pytest.raises(ValueError):
+      pickle.dumps(optimizer)
+    assert copy.copy(optimizer).cost() == optimizer.cost()
+
+  def test_keyboard_interrupt(self):
+    import os
+    import signal
+    import threading
+    import time
+
+    nodes, edges = untanglers.generate_multipartite_graph([200, 200, 200])
+    optimizer = untanglers.LayoutOptimizerInt(nodes, edges)
+    threading.Timer(0.2, os.kill, (os.getpid(), signal.SIGINT)).start()
+    start = time.perf_counter()
+    with pytest.raises(KeyboardInterrupt):
+      optimizer.optimize(1.0, 0.1, 50, 1000, 1000)
+    assert time.perf_counter() - start < 5
+    assert [sorted(layer) for layer in optimizer.get_nodes()] == [sorted(layer) for layer in nodes]
+    assert optimizer.swap_nodes(1.0, 10, 0) >= 0
+
+  def test_objective_while_locked(self):
+    import asyncio
+    import time
+
+    def objective(layer_index, layer):
+      time.sleep(0.01)
+      return [0.0] * len(layer) ** 2, 0.0
+
+    async def run():
+      nodes, edges = untanglers.generate_multipartite_graph([20, 20, 20])
+      optimizer = untanglers.LayoutOptimizerInt(nodes, edges)
+      optimizer.add_objective(objective)
+      future = optimizer.optimize_async(1.0, 0.1, 5, 10, 5)
+      await asyncio.sleep(0.05)
+
+      # The objective needs the GIL while the optimization holds the optimizer, which is released while waiting for it
+      assert optimizer.optimize(1.0, 0.1, 1, 10, 1) == optimizer.count_crossings()
+      assert await future >= 0
+
+    asyncio.run(asyncio.wait_for(run(), timeout=10))
+
+  def test_optimize_async(self):
+    import asyncio
+
+    async def run():
+      nodes, edges = untanglers.generate_multipartite_graph([20, 20, 20])
+      optimizer = untanglers.LayoutOptimizerInt(nodes, edges)
+      crossings = await optimizer.optimize_async(1.0, 0.1, 5, 20, 2)
+      assert crossings == optimizer.count_crossings()
+
+      genetic = untanglers.GeneticOptimizerInt(nodes, edges, population_size=4)
+      assert await genetic.optimize_async(2) == genetic.count_crossings()
+
+      nodes, edges = untanglers.generate_multipartite_graph([200, 200, 200])
+      optimizer = untanglers.LayoutOptimizerInt(nodes, edges)
+      async def optimize():
+        return await optimizer.optimize_async(1.0, 0.1, 50, 1000, 1000)
+
+      task = asyncio.create_task(optimize())
+      await asyncio.sleep(0.2)
+      # Another optimization fails instead of waiting until this one is done
+      with pytest.raises(RuntimeError, match="optimization in progress"):
+        await optimizer.optimize_async(1.0, 0.1, 1, 10, 1)
+      task.cancel()
+      with pytest.raises(asyncio.CancelledError):
+        await task
+
+      # Other calls wait until the interrupted optimization releases the optimizer after the current layer
+      assert [sorted(layer) for layer in optimizer.get_nodes()] == [sorted(layer) for layer in nodes]
+
+    asyncio.run(asyncio.wait_for(run(), timeout=10))
+
+  def test_optimize_config(self):
+    import pickle
+
+    config = untanglers.OptimizeConfig(steps=3, max_iterations=20, passes=2)
+    assert (config.start_temp, config.end_temp, config.steps, config.passes) == (1.0, 0.1, 3, 2)
+    assert config == untanglers.OptimizeConfig(start_temp=1.0, steps=3, max_iterations=20, passes=2)
+    assert config.replace(passes=1).passes == 1
+    assert repr(config) == "OptimizeConfig(start_temp=1.0, end_temp=0.1, steps=3, max_iterations=20, passes=2)"
+    assert pickle.loads(pickle.dumps(config)) == config
+
+    for invalid in [dict(start_temp=0.1, end_temp=1.0), dict(end_temp=-1.0), dict(steps=0)]:
+      with pytest.raises(ValueError):
+        untanglers.OptimizeConfig(**invalid)
+    with pytest.raises(ValueError):
+      config.replace(steps=0)
+
+    nodes, edges = untanglers.generate_multipartite_graph([5, 5])
+    optimizer = untanglers.LayoutOptimizerInt(nodes, edges)
+    assert optimizer.optimize(config) == optimizer.count_crossings()
+    assert optimizer.optimize(passes=1) == optimizer.count_crossings()
+    with pytest.raises(ValueError):
+      optimizer.optimize(1.0, 0.1, 0, 20, 1)
+    with pytest.raises(TypeError):
+      optimizer.optimize(config, passes=1)
 
 if __name__ == "__main__":
   TestUntangleRs().test_crossings_simple()
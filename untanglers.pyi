@@ -1,14 +1,98 @@
+import asyncio
+from collections.abc import Hashable
+from typing import Any, Callable, Literal, overload
+
+import numpy as np
+import numpy.typing as npt
+
+StrategyName = Literal["annealing", "barycenter", "greedy", "tabu"]
+Strategy = StrategyName | tuple[StrategyName, dict[str, float]]
+GroupTree = dict[str, "GroupTree"] | list[Hashable]
+# Integer arrays, one-dimensional for the nodes of a layer and of shape (edge_count, 3) for edges
+IntArray = npt.NDArray[np.integer]
+IntNodes = list[list[int]] | list[IntArray]
+IntEdges = list[list[tuple[int, int, int]]] | list[IntArray]
+
+class OptimizeConfig:
+  """Settings of `optimize`, invalid values such as end_temp > start_temp or steps == 0 raise a ValueError"""
+
+  def __init__(
+    self,
+    *,
+    start_temp: float = 1.0,
+    end_temp: float = 0.1,
+    steps: int = 5,
+    max_iterations: int = 200,
+    passes: int = 10,
+  ): ...
+  @property
+  def start_temp(self) -> float: ...
+  @property
+  def end_temp(self) -> float: ...
+  @property
+  def steps(self) -> int: ...
+  @property
+  def max_iterations(self) -> int: ...
+  @property
+  def passes(self) -> int: ...
+  def replace(
+    self,
+    *,
+    start_temp: float | None = None,
+    end_temp: float | None = None,
+    steps: int | None = None,
+    max_iterations: int | None = None,
+    passes: int | None = None,
+  ) -> OptimizeConfig: ...
+
 class LayoutOptimizerString:
   def __init__(
     self,
     nodes: list[list[str]],
     edges: list[list[tuple[str, str, int]]],
+    intra_edges: list[list[tuple[str, str, int]]] | None = None,
   ): ...
   def swap_nodes(self, temperature: float, max_iterations: int, layer_index: int) -> int: ...
   def cooldown(self, start_temp: float, end_temp: float, steps: int, max_iterations: int, layer_index: int) -> int: ...
-  def optimize(self, start_temp: float, end_temp: float, steps: int, max_iterations: int, passes: int) -> int: ...
+  def tabu_search(self, tenure: int, max_iterations: int, layer_index: int) -> int: ...
+  @overload
+  def optimize(self, config: OptimizeConfig, /) -> int: ...
+  @overload
+  def optimize(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> int: ...
+  @overload
+  def optimize_async(self, config: OptimizeConfig, /) -> asyncio.Future[int]: ...
+  @overload
+  def optimize_async(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> asyncio.Future[int]: ...
+  def optimize_with(self, strategies: list[Strategy], passes: int) -> int: ...
+  def polish(self, two_opt: bool = False, block_reversal: bool = False) -> int: ...
+  def solve_exact(self, max_nodes: int = 30, time_limit: float | None = None) -> tuple[int, bool]: ...
   def get_nodes(self) -> list[list[str]]: ...
+  def to_networkx_positions(self) -> dict[str, tuple[float, float]]: ...
+  def __copy__(self) -> LayoutOptimizerString: ...
+  def __deepcopy__(self, memo: dict) -> LayoutOptimizerString: ...
   def count_crossings(self) -> int: ...
+  def to_svg(self, highlight_crossings: bool = False, show_labels: bool = True) -> str: ...
+  def add_node(self, layer_index: int, node: str) -> None: ...
+  def remove_node(self, layer_index: int, node: str) -> None: ...
+  def add_edge(self, layer_index: int, node_a: str, node_b: str, weight: int) -> None: ...
+  def remove_edge(self, layer_index: int, node_a: str, node_b: str) -> int: ...
+  def set_weight(self, layer_index: int, node_a: str, node_b: str, weight: int) -> None: ...
+  def set_reference(
+    self, reference: list[list[str]], metric: Literal["kendall", "displacement"] = "kendall", weight: float = 1.0
+  ) -> None: ...
+  def clear_reference(self) -> None: ...
+  def set_edge_length_weight(self, weight: float) -> None: ...
+  def edge_length(self) -> float: ...
+  def add_objective(
+    self, callback: Callable[[int, list[str]], tuple[list[float], float]], weight: float = 1.0
+  ) -> None: ...
+  def add_group_cohesion(self, labels: list[dict[str, int]], weight: float = 1.0) -> None: ...
+  def clear_objectives(self) -> None: ...
+  def cost(self) -> float: ...
 
 class HierarchyOptimizerString:
   def __init__(
@@ -16,40 +100,368 @@ class HierarchyOptimizerString:
     nodes: list[list[str]],
     edges: list[list[tuple[str, str, int]]],
     hierarchy: list[list[list[int]]],
+    intra_edges: list[list[tuple[str, str, int]]] | None = None,
   ): ...
+  @staticmethod
+  def from_group_tree(trees: list[GroupTree], edges: list[list[tuple[str, str, int]]]) -> HierarchyOptimizerString: ...
+  @staticmethod
+  def from_group_paths(
+    nodes: list[list[str]], edges: list[list[tuple[str, str, int]]], paths: list[dict[str, list[str]]]
+  ) -> HierarchyOptimizerString: ...
   def swap_nodes(self, temperature: float, max_iterations: int, layer_index: int, granularity: int | None) -> int: ...
   def cooldown(
     self, start_temp: float, end_temp: float, steps: int, max_iterations: int, layer_index: int, granularity: int | None
   ) -> int: ...
-  def optimize(self, start_temp: float, end_temp: float, steps: int, max_iterations: int, passes: int) -> int: ...
+  def tabu_search(self, tenure: int, max_iterations: int, layer_index: int, granularity: int | None) -> int: ...
+  @overload
+  def optimize(self, config: OptimizeConfig, /) -> int: ...
+  @overload
+  def optimize(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> int: ...
+  @overload
+  def optimize_async(self, config: OptimizeConfig, /) -> asyncio.Future[int]: ...
+  @overload
+  def optimize_async(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> asyncio.Future[int]: ...
+  def optimize_with(self, strategies: list[Strategy], passes: int) -> int: ...
+  def polish(self, two_opt: bool = False, block_reversal: bool = False) -> int: ...
   def get_nodes(self) -> list[list[str]]: ...
+  def to_networkx_positions(self) -> dict[str, tuple[float, float]]: ...
+  def __copy__(self) -> HierarchyOptimizerString: ...
+  def __deepcopy__(self, memo: dict) -> HierarchyOptimizerString: ...
+  def get_hierarchy(self) -> list[list[list[int]]]: ...
+  def get_group_names(self) -> list[list[list[str]]]: ...
+  def get_group_order(self, layer_index: int, granularity: int) -> list[str]: ...
+  def set_compound_groups(self, groups: list[tuple[str, int, int]]) -> None: ...
+  def get_compound_order(self) -> list[str]: ...
+  def get_group_tree(self, layer_index: int) -> GroupTree: ...
+  def count_crossings(self, granularity: int | None = None) -> int: ...
+  def group_crossings(self, granularity: int) -> list[list[tuple[str, int]]]: ...
+  def to_svg(self, highlight_crossings: bool = False, show_labels: bool = True) -> str: ...
+  def add_node(self, layer_index: int, node: str, group_index: int | None = None) -> None: ...
+  def remove_node(self, layer_index: int, node: str) -> None: ...
+  def add_edge(self, layer_index: int, node_a: str, node_b: str, weight: int) -> None: ...
+  def remove_edge(self, layer_index: int, node_a: str, node_b: str) -> int: ...
+  def set_weight(self, layer_index: int, node_a: str, node_b: str, weight: int) -> None: ...
+  def set_reference(
+    self, reference: list[list[str]], metric: Literal["kendall", "displacement"] = "kendall", weight: float = 1.0
+  ) -> None: ...
+  def clear_reference(self) -> None: ...
+  def set_edge_length_weight(self, weight: float) -> None: ...
+  def edge_length(self) -> float: ...
+  def add_objective(
+    self, callback: Callable[[int, list[str]], tuple[list[float], float]], weight: float = 1.0
+  ) -> None: ...
+  def add_group_cohesion(self, labels: list[dict[str, int]], weight: float = 1.0) -> None: ...
+  def clear_objectives(self) -> None: ...
+  def cost(self) -> float: ...
+
+class GeneticOptimizerString:
+  def __init__(
+    self,
+    nodes: list[list[str]],
+    edges: list[list[tuple[str, str, int]]],
+    hierarchy: list[list[list[int]]] | None = None,
+    population_size: int = 20,
+    elite_count: int = 2,
+    tournament_size: int = 3,
+    mutation_rate: float = 0.2,
+    local_search: bool = True,
+  ): ...
+  def optimize(self, generations: int) -> int: ...
+  def optimize_async(self, generations: int) -> asyncio.Future[int]: ...
+  def get_nodes(self) -> list[list[str]]: ...
+  def to_networkx_positions(self) -> dict[str, tuple[float, float]]: ...
+  def __copy__(self) -> GeneticOptimizerString: ...
+  def __deepcopy__(self, memo: dict) -> GeneticOptimizerString: ...
+  def get_hierarchy(self) -> list[list[list[int]]]: ...
   def count_crossings(self) -> int: ...
+  def to_svg(self, highlight_crossings: bool = False, show_labels: bool = True) -> str: ...
+  def set_reference(
+    self, reference: list[list[str]], metric: Literal["kendall", "displacement"] = "kendall", weight: float = 1.0
+  ) -> None: ...
+  def clear_reference(self) -> None: ...
+  def set_edge_length_weight(self, weight: float) -> None: ...
+  def edge_length(self) -> float: ...
+  def add_objective(
+    self, callback: Callable[[int, list[str]], tuple[list[float], float]], weight: float = 1.0
+  ) -> None: ...
+  def add_group_cohesion(self, labels: list[dict[str, int]], weight: float = 1.0) -> None: ...
+  def clear_objectives(self) -> None: ...
+  def cost(self) -> float: ...
 
 class LayoutOptimizerInt:
   def __init__(
     self,
-    nodes: list[list[int]],
-    edges: list[list[tuple[int, int, int]]],
+    nodes: IntNodes,
+    edges: IntEdges,
+    intra_edges: list[list[tuple[int, int, int]]] | None = None,
   ): ...
   def swap_nodes(self, temperature: float, max_iterations: int, layer_index: int) -> int: ...
   def cooldown(self, start_temp: float, end_temp: float, steps: int, max_iterations: int, layer_index: int) -> int: ...
-  def optimize(self, start_temp: float, end_temp: float, steps: int, max_iterations: int, passes: int) -> int: ...
+  def tabu_search(self, tenure: int, max_iterations: int, layer_index: int) -> int: ...
+  @overload
+  def optimize(self, config: OptimizeConfig, /) -> int: ...
+  @overload
+  def optimize(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> int: ...
+  @overload
+  def optimize_async(self, config: OptimizeConfig, /) -> asyncio.Future[int]: ...
+  @overload
+  def optimize_async(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> asyncio.Future[int]: ...
+  def optimize_with(self, strategies: list[Strategy], passes: int) -> int: ...
+  def polish(self, two_opt: bool = False, block_reversal: bool = False) -> int: ...
+  def solve_exact(self, max_nodes: int = 30, time_limit: float | None = None) -> tuple[int, bool]: ...
   def get_nodes(self) -> list[list[int]]: ...
+  def to_networkx_positions(self) -> dict[int, tuple[float, float]]: ...
+  def __copy__(self) -> LayoutOptimizerInt: ...
+  def __deepcopy__(self, memo: dict) -> LayoutOptimizerInt: ...
   def count_crossings(self) -> int: ...
+  def to_svg(self, highlight_crossings: bool = False, show_labels: bool = True) -> str: ...
+  def add_node(self, layer_index: int, node: int) -> None: ...
+  def remove_node(self, layer_index: int, node: int) -> None: ...
+  def add_edge(self, layer_index: int, node_a: int, node_b: int, weight: int) -> None: ...
+  def remove_edge(self, layer_index: int, node_a: int, node_b: int) -> int: ...
+  def set_weight(self, layer_index: int, node_a: int, node_b: int, weight: int) -> None: ...
+  def set_reference(
+    self, reference: list[list[int]], metric: Literal["kendall", "displacement"] = "kendall", weight: float = 1.0
+  ) -> None: ...
+  def clear_reference(self) -> None: ...
+  def set_edge_length_weight(self, weight: float) -> None: ...
+  def edge_length(self) -> float: ...
+  def add_objective(
+    self, callback: Callable[[int, list[int]], tuple[list[float], float]], weight: float = 1.0
+  ) -> None: ...
+  def add_group_cohesion(self, labels: list[dict[int, int]], weight: float = 1.0) -> None: ...
+  def clear_objectives(self) -> None: ...
+  def cost(self) -> float: ...
 
 class HierarchyOptimizerInt:
   def __init__(
     self,
-    nodes: list[list[int]],
-    edges: list[list[tuple[int, int, int]]],
+    nodes: IntNodes,
+    edges: IntEdges,
     hierarchy: list[list[list[int]]],
+    intra_edges: list[list[tuple[int, int, int]]] | None = None,
   ): ...
+  @staticmethod
+  def from_group_tree(trees: list[GroupTree], edges: IntEdges) -> HierarchyOptimizerInt: ...
+  @staticmethod
+  def from_group_paths(
+    nodes: IntNodes, edges: IntEdges, paths: list[dict[int, list[str]]]
+  ) -> HierarchyOptimizerInt: ...
   def swap_nodes(self, temperature: float, max_iterations: int, layer_index: int, granularity: int | None) -> int: ...
   def cooldown(
     self, start_temp: float, end_temp: float, steps: int, max_iterations: int, layer_index: int, granularity: int | None
   ) -> int: ...
-  def optimize(self, start_temp: float, end_temp: float, steps: int, max_iterations: int, passes: int) -> int: ...
+  def tabu_search(self, tenure: int, max_iterations: int, layer_index: int, granularity: int | None) -> int: ...
+  @overload
+  def optimize(self, config: OptimizeConfig, /) -> int: ...
+  @overload
+  def optimize(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> int: ...
+  @overload
+  def optimize_async(self, config: OptimizeConfig, /) -> asyncio.Future[int]: ...
+  @overload
+  def optimize_async(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> asyncio.Future[int]: ...
+  def optimize_with(self, strategies: list[Strategy], passes: int) -> int: ...
+  def polish(self, two_opt: bool = False, block_reversal: bool = False) -> int: ...
   def get_nodes(self) -> list[list[int]]: ...
+  def to_networkx_positions(self) -> dict[int, tuple[float, float]]: ...
+  def __copy__(self) -> HierarchyOptimizerInt: ...
+  def __deepcopy__(self, memo: dict) -> HierarchyOptimizerInt: ...
+  def get_hierarchy(self) -> list[list[list[int]]]: ...
+  def get_group_names(self) -> list[list[list[str]]]: ...
+  def get_group_order(self, layer_index: int, granularity: int) -> list[str]: ...
+  def set_compound_groups(self, groups: list[tuple[str, int, int]]) -> None: ...
+  def get_compound_order(self) -> list[str]: ...
+  def get_group_tree(self, layer_index: int) -> GroupTree: ...
+  def count_crossings(self, granularity: int | None = None) -> int: ...
+  def group_crossings(self, granularity: int) -> list[list[tuple[str, int]]]: ...
+  def to_svg(self, highlight_crossings: bool = False, show_labels: bool = True) -> str: ...
+  def add_node(self, layer_index: int, node: int, group_index: int | None = None) -> None: ...
+  def remove_node(self, layer_index: int, node: int) -> None: ...
+  def add_edge(self, layer_index: int, node_a: int, node_b: int, weight: int) -> None: ...
+  def remove_edge(self, layer_index: int, node_a: int, node_b: int) -> int: ...
+  def set_weight(self, layer_index: int, node_a: int, node_b: int, weight: int) -> None: ...
+  def set_reference(
+    self, reference: list[list[int]], metric: Literal["kendall", "displacement"] = "kendall", weight: float = 1.0
+  ) -> None: ...
+  def clear_reference(self) -> None: ...
+  def set_edge_length_weight(self, weight: float) -> None: ...
+  def edge_length(self) -> float: ...
+  def add_objective(
+    self, callback: Callable[[int, list[int]], tuple[list[float], float]], weight: float = 1.0
+  ) -> None: ...
+  def add_group_cohesion(self, labels: list[dict[int, int]], weight: float = 1.0) -> None: ...
+  def clear_objectives(self) -> None: ...
+  def cost(self) -> float: ...
+
+class GeneticOptimizerInt:
+  def __init__(
+    self,
+    nodes: IntNodes,
+    edges: IntEdges,
+    hierarchy: list[list[list[int]]] | None = None,
+    population_size: int = 20,
+    elite_count: int = 2,
+    tournament_size: int = 3,
+    mutation_rate: float = 0.2,
+    local_search: bool = True,
+  ): ...
+  def optimize(self, generations: int) -> int: ...
+  def optimize_async(self, generations: int) -> asyncio.Future[int]: ...
+  def get_nodes(self) -> list[list[int]]: ...
+  def to_networkx_positions(self) -> dict[int, tuple[float, float]]: ...
+  def __copy__(self) -> GeneticOptimizerInt: ...
+  def __deepcopy__(self, memo: dict) -> GeneticOptimizerInt: ...
+  def get_hierarchy(self) -> list[list[list[int]]]: ...
+  def count_crossings(self) -> int: ...
+  def to_svg(self, highlight_crossings: bool = False, show_labels: bool = True) -> str: ...
+  def set_reference(
+    self, reference: list[list[int]], metric: Literal["kendall", "displacement"] = "kendall", weight: float = 1.0
+  ) -> None: ...
+  def clear_reference(self) -> None: ...
+  def set_edge_length_weight(self, weight: float) -> None: ...
+  def edge_length(self) -> float: ...
+  def add_objective(
+    self, callback: Callable[[int, list[int]], tuple[list[float], float]], weight: float = 1.0
+  ) -> None: ...
+  def add_group_cohesion(self, labels: list[dict[int, int]], weight: float = 1.0) -> None: ...
+  def clear_objectives(self) -> None: ...
+  def cost(self) -> float: ...
+
+class LayoutOptimizer:
+  """Like `LayoutOptimizerString` and `LayoutOptimizerInt`, for nodes that are any hashable objects"""
+  def __init__(
+    self,
+    nodes: list[list[Hashable]] | list[IntArray],
+    edges: list[list[tuple[Hashable, Hashable, int]]] | list[IntArray],
+    intra_edges: list[list[tuple[Hashable, Hashable, int]]] | None = None,
+  ): ...
+  @staticmethod
+  def from_networkx(graph: Any, layer_attr: str = "layer", weight_attr: str = "weight") -> LayoutOptimizer: ...
+  def swap_nodes(self, temperature: float, max_iterations: int, layer_index: int) -> int: ...
+  def cooldown(self, start_temp: float, end_temp: float, steps: int, max_iterations: int, layer_index: int) -> int: ...
+  def tabu_search(self, tenure: int, max_iterations: int, layer_index: int) -> int: ...
+  @overload
+  def optimize(self, config: OptimizeConfig, /) -> int: ...
+  @overload
+  def optimize(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> int: ...
+  @overload
+  def optimize_async(self, config: OptimizeConfig, /) -> asyncio.Future[int]: ...
+  @overload
+  def optimize_async(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> asyncio.Future[int]: ...
+  def optimize_with(self, strategies: list[Strategy], passes: int) -> int: ...
+  def polish(self, two_opt: bool = False, block_reversal: bool = False) -> int: ...
+  def solve_exact(self, max_nodes: int = 30, time_limit: float | None = None) -> tuple[int, bool]: ...
+  def get_nodes(self) -> list[list[Hashable]]: ...
+  def to_networkx_positions(self) -> dict[Hashable, tuple[float, float]]: ...
+  def __copy__(self) -> LayoutOptimizer: ...
+  def __deepcopy__(self, memo: dict) -> LayoutOptimizer: ...
   def count_crossings(self) -> int: ...
+  def to_svg(self, highlight_crossings: bool = False, show_labels: bool = True) -> str: ...
+  def add_node(self, layer_index: int, node: Hashable) -> None: ...
+  def remove_node(self, layer_index: int, node: Hashable) -> None: ...
+  def add_edge(self, layer_index: int, node_a: Hashable, node_b: Hashable, weight: int) -> None: ...
+  def remove_edge(self, layer_index: int, node_a: Hashable, node_b: Hashable) -> int: ...
+  def set_weight(self, layer_index: int, node_a: Hashable, node_b: Hashable, weight: int) -> None: ...
+  def set_reference(
+    self, reference: list[list[Hashable]], metric: Literal["kendall", "displacement"] = "kendall", weight: float = 1.0
+  ) -> None: ...
+  def clear_reference(self) -> None: ...
+  def set_edge_length_weight(self, weight: float) -> None: ...
+  def edge_length(self) -> float: ...
+  def add_objective(
+    self, callback: Callable[[int, list[Hashable]], tuple[list[float], float]], weight: float = 1.0
+  ) -> None: ...
+  def add_group_cohesion(self, labels: list[dict[Hashable, int]], weight: float = 1.0) -> None: ...
+  def clear_objectives(self) -> None: ...
+  def cost(self) -> float: ...
+
+class HierarchyOptimizer:
+  """Like `HierarchyOptimizerString` and `HierarchyOptimizerInt`, for nodes that are any hashable objects"""
+  def __init__(
+    self,
+    nodes: list[list[Hashable]] | list[IntArray],
+    edges: list[list[tuple[Hashable, Hashable, int]]] | list[IntArray],
+    hierarchy: list[list[list[int]]],
+    intra_edges: list[list[tuple[Hashable, Hashable, int]]] | None = None,
+  ): ...
+  @staticmethod
+  def from_networkx(
+    graph: Any, layer_attr: str = "layer", weight_attr: str = "weight", group_attr: str = "group"
+  ) -> HierarchyOptimizer: ...
+  @staticmethod
+  def from_group_tree(
+    trees: list[GroupTree], edges: list[list[tuple[Hashable, Hashable, int]]]
+  ) -> HierarchyOptimizer: ...
+  @staticmethod
+  def from_group_paths(
+    nodes: list[list[Hashable]],
+    edges: list[list[tuple[Hashable, Hashable, int]]],
+    paths: list[dict[Hashable, list[str]]],
+  ) -> HierarchyOptimizer: ...
+  def swap_nodes(self, temperature: float, max_iterations: int, layer_index: int, granularity: int | None) -> int: ...
+  def cooldown(
+    self, start_temp: float, end_temp: float, steps: int, max_iterations: int, layer_index: int, granularity: int | None
+  ) -> int: ...
+  def tabu_search(self, tenure: int, max_iterations: int, layer_index: int, granularity: int | None) -> int: ...
+  @overload
+  def optimize(self, config: OptimizeConfig, /) -> int: ...
+  @overload
+  def optimize(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> int: ...
+  @overload
+  def optimize_async(self, config: OptimizeConfig, /) -> asyncio.Future[int]: ...
+  @overload
+  def optimize_async(
+    self, start_temp: float = 1.0, end_temp: float = 0.1, steps: int = 5, max_iterations: int = 200, passes: int = 10
+  ) -> asyncio.Future[int]: ...
+  def optimize_with(self, strategies: list[Strategy], passes: int) -> int: ...
+  def polish(self, two_opt: bool = False, block_reversal: bool = False) -> int: ...
+  def get_nodes(self) -> list[list[Hashable]]: ...
+  def to_networkx_positions(self) -> dict[Hashable, tuple[float, float]]: ...
+  def __copy__(self) -> HierarchyOptimizer: ...
+  def __deepcopy__(self, memo: dict) -> HierarchyOptimizer: ...
+  def get_hierarchy(self) -> list[list[list[int]]]: ...
+  def get_group_names(self) -> list[list[list[str]]]: ...
+  def get_group_order(self, layer_index: int, granularity: int) -> list[str]: ...
+  def set_compound_groups(self, groups: list[tuple[str, int, int]]) -> None: ...
+  def get_compound_order(self) -> list[str]: ...
+  def get_group_tree(self, layer_index: int) -> GroupTree: ...
+  def count_crossings(self, granularity: int | None = None) -> int: ...
+  def group_crossings(self, granularity: int) -> list[list[tuple[str, int]]]: ...
+  def to_svg(self, highlight_crossings: bool = False, show_labels: bool = True) -> str: ...
+  def add_node(self, layer_index: int, node: Hashable, group_index: int | None = None) -> None: ...
+  def remove_node(self, layer_index: int, node: Hashable) -> None: ...
+  def add_edge(self, layer_index: int, node_a: Hashable, node_b: Hashable, weight: int) -> None: ...
+  def remove_edge(self, layer_index: int, node_a: Hashable, node_b: Hashable) -> int: ...
+  def set_weight(self, layer_index: int, node_a: Hashable, node_b: Hashable, weight: int) -> None: ...
+  def set_reference(
+    self, reference: list[list[Hashable]], metric: Literal["kendall", "displacement"] = "kendall", weight: float = 1.0
+  ) -> None: ...
+  def clear_reference(self) -> None: ...
+  def set_edge_length_weight(self, weight: float) -> None: ...
+  def edge_length(self) -> float: ...
+  def add_objective(
+    self, callback: Callable[[int, list[Hashable]], tuple[list[float], float]], weight: float = 1.0
+  ) -> None: ...
+  def add_group_cohesion(self, labels: list[dict[Hashable, int]], weight: float = 1.0) -> None: ...
+  def clear_objectives(self) -> None: ...
+  def cost(self) -> float: ...
 
 def generate_multipartite_graph(n_nodes: list[int]) -> tuple[list[list[int]], list[list[tuple[int, int, int]]]]: ...